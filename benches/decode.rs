@@ -1,6 +1,8 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
 use fleece::{Encoder, SharedKeys, Value};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
 
 const PEOPLE_ENCODED: &[u8] = include_bytes!("../1000people.fleece");
 
@@ -35,10 +37,55 @@ fn decode_people_sharedkeys(c: &mut Criterion) {
     });
 }
 
+// Only a few of the keys of each person, so most of each Dict is never visited
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct PersonSummary {
+    name: String,
+    age: u32,
+    email: String,
+}
+
+#[cfg(feature = "serde")]
+fn deserialize_people_summary(c: &mut Criterion) {
+    c.bench_function("deserialize_people_summary", |b| {
+        b.iter(|| {
+            let _: Vec<PersonSummary> =
+                fleece::from_bytes_lookup_fields(black_box(PEOPLE_ENCODED)).unwrap();
+        });
+    });
+}
+
+#[cfg(feature = "serde")]
+fn deserialize_people_summary_sharedkeys(c: &mut Criterion) {
+    let value = Value::from_bytes(PEOPLE_ENCODED).unwrap();
+    let mut encoder = Encoder::new();
+    encoder.set_shared_keys(SharedKeys::new());
+    encoder.write_fleece(value).unwrap();
+    let scope = encoder.finish_scoped();
+    let data = scope.data().unwrap();
+
+    c.bench_function("deserialize_people_summary_sharedkeys", |b| {
+        b.iter(|| {
+            let _: Vec<PersonSummary> = fleece::from_bytes_lookup_fields(black_box(&data)).unwrap();
+        });
+    });
+}
+
 criterion_group!(
     decode_benches,
     decode_people,
     decode_people_unchecked,
     decode_people_sharedkeys
 );
+#[cfg(feature = "serde")]
+criterion_group!(
+    serde_benches,
+    deserialize_people_summary,
+    deserialize_people_summary_sharedkeys
+);
+#[cfg(feature = "serde")]
+criterion_main!(decode_benches, serde_benches);
+#[cfg(not(feature = "serde"))]
 criterion_main!(decode_benches);
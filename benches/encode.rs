@@ -27,5 +27,47 @@ fn encode_people_sharedkeys(c: &mut Criterion) {
     });
 }
 
-criterion_group!(encode_benches, encode_people, encode_people_sharedkeys);
+// Encode each person as a separate document, with a new Encoder for each
+fn encode_small_documents(c: &mut Criterion) {
+    let people = Value::from_bytes(PEOPLE_ENCODED)
+        .unwrap()
+        .as_array()
+        .unwrap();
+    c.bench_function("encode_small_documents", |b| {
+        b.iter(|| {
+            for person in people {
+                let mut encoder = Encoder::new();
+                encoder.write_fleece(person).unwrap();
+                let _ = encoder.finish();
+            }
+        });
+    });
+}
+
+// Encode each person as a separate document, reusing the Encoder and output buffer
+fn encode_small_documents_reuse(c: &mut Criterion) {
+    let people = Value::from_bytes(PEOPLE_ENCODED)
+        .unwrap()
+        .as_array()
+        .unwrap();
+    let mut encoder = Encoder::new();
+    let mut out = Vec::new();
+    c.bench_function("encode_small_documents_reuse", |b| {
+        b.iter(|| {
+            for person in people {
+                encoder.write_fleece(person).unwrap();
+                out.clear();
+                encoder.finish_into(&mut out);
+            }
+        });
+    });
+}
+
+criterion_group!(
+    encode_benches,
+    encode_people,
+    encode_people_sharedkeys,
+    encode_small_documents,
+    encode_small_documents_reuse
+);
 criterion_main!(encode_benches);
@@ -0,0 +1,277 @@
+//! Derive macros for the `fleece` crate's `ToFleece` and `FromFleece` traits. Use them through
+//! the `derive` feature of `fleece`, rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+    parse_macro_input, parse_quote, Data, DeriveInput, Fields, Generics, LitStr, Type,
+    TypeParamBound,
+};
+
+/// Implement `fleece::ToFleece` for a struct with named fields, which is encoded as a Dict with a
+/// key for each field.
+#[proc_macro_derive(ToFleece, attributes(fleece))]
+pub fn derive_to_fleece(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    to_fleece(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implement `fleece::FromFleece` for a struct with named fields, which is decoded from a Dict by
+/// looking up the key of each field.
+#[proc_macro_derive(FromFleece, attributes(fleece))]
+pub fn derive_from_fleece(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from_fleece(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// A field of the struct, and its `#[fleece(...)]` attributes.
+struct Field<'a> {
+    ident: &'a syn::Ident,
+    ty: &'a Type,
+    key: String,
+    skip: bool,
+    default: bool,
+    flatten: bool,
+}
+
+impl<'a> Field<'a> {
+    fn parse(field: &'a syn::Field) -> syn::Result<Self> {
+        let ident = field.ident.as_ref().expect("named fields have identifiers");
+        let mut parsed = Field {
+            ident,
+            ty: &field.ty,
+            key: ident.to_string(),
+            skip: false,
+            default: false,
+            flatten: false,
+        };
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("fleece")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    parsed.key = meta.value()?.parse::<LitStr>()?.value();
+                } else if meta.path.is_ident("skip") {
+                    parsed.skip = true;
+                } else if meta.path.is_ident("default") {
+                    parsed.default = true;
+                } else if meta.path.is_ident("flatten") {
+                    parsed.flatten = true;
+                } else {
+                    return Err(meta.error("expected `rename`, `skip`, `default` or `flatten`"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(parsed)
+    }
+}
+
+fn fields(input: &DeriveInput) -> syn::Result<Vec<Field<'_>>> {
+    match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().map(Field::parse).collect(),
+            _ => Err(syn::Error::new_spanned(
+                &input.ident,
+                "only structs with named fields are supported",
+            )),
+        },
+        _ => Err(syn::Error::new_spanned(
+            &input.ident,
+            "only structs with named fields are supported",
+        )),
+    }
+}
+
+/// Add the bounds the struct's fields need to the struct's generics:
+/// - `bound` on each type parameter used in the type of one of `fields`. Parameters which are only
+///   used by other fields, such as skipped fields, aren't bounded. The field types themselves
+///   aren't bounded, as a recursive type such as `children: Vec<Self>` would never satisfy them.
+/// - The bound paired with each type in `predicates` which uses a type parameter, as a `where`
+///   clause, for fields which need a different bound.
+fn add_bounds(
+    generics: &Generics,
+    fields: &[&Type],
+    bound: &TypeParamBound,
+    predicates: &[(&Type, TypeParamBound)],
+) -> Generics {
+    let mut generics = generics.clone();
+    let params: Vec<syn::Ident> = generics.type_params().map(|p| p.ident.clone()).collect();
+    let uses_param = |ty: &Type| {
+        params
+            .iter()
+            .any(|param| uses_ident(ty.to_token_stream(), param))
+    };
+    for param in generics.type_params_mut() {
+        if fields
+            .iter()
+            .any(|ty| uses_ident(ty.to_token_stream(), &param.ident))
+        {
+            param.bounds.push(bound.clone());
+        }
+    }
+    let where_clause = generics.make_where_clause();
+    for (ty, bound) in predicates.iter().filter(|(ty, _)| uses_param(ty)) {
+        where_clause.predicates.push(parse_quote!(#ty: #bound));
+    }
+    generics
+}
+
+/// Whether `ident` appears anywhere in `tokens`.
+fn uses_ident(tokens: TokenStream2, ident: &syn::Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(token) => token == *ident,
+        TokenTree::Group(group) => uses_ident(group.stream(), ident),
+        _ => false,
+    })
+}
+
+fn to_fleece(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let fields: Vec<Field> = fields(input)?.into_iter().filter(|f| !f.skip).collect();
+    let (flattened, direct): (Vec<&Field>, Vec<&Field>) = fields.iter().partition(|f| f.flatten);
+
+    // The keys of flattened fields are only known through `ToFleece::KEYS`, so duplicates of those
+    // are checked when encoding, but the struct's own keys can be checked here
+    for (i, field) in direct.iter().enumerate() {
+        if direct[..i].iter().any(|other| other.key == field.key) {
+            return Err(syn::Error::new_spanned(
+                field.ident,
+                format!("duplicate key `{}`", field.key),
+            ));
+        }
+    }
+
+    let direct_types: Vec<&Type> = direct.iter().map(|f| f.ty).collect();
+    let flattened_types: Vec<&Type> = flattened.iter().map(|f| f.ty).collect();
+    let flattened_bounds: Vec<(&Type, TypeParamBound)> = flattened_types
+        .iter()
+        .map(|ty| (*ty, parse_quote!(::fleece::ToFleece)))
+        .collect();
+    let generics = add_bounds(
+        &input.generics,
+        &direct_types,
+        &parse_quote!(::fleece::encoder::Encodable),
+        &flattened_bounds,
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let keys = direct.iter().map(|field| &field.key);
+    let check_keys = if flattened.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            if let ::core::option::Option::Some(key) =
+                const { <Self as ::fleece::ToFleece>::KEYS.duplicate() }
+            {
+                return ::core::result::Result::Err(
+                    ::fleece::encoder::EncodeError::DuplicateKey(key),
+                );
+            }
+        }
+    };
+    let writes = fields.iter().map(|field| {
+        let ident = field.ident;
+        let key = &field.key;
+        if field.flatten {
+            quote! { ::fleece::ToFleece::write_fields(&self.#ident, encoder)?; }
+        } else {
+            quote! {
+                encoder.write_key(#key)?;
+                ::fleece::encoder::Encodable::write_to(&self.#ident, encoder)?;
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::fleece::ToFleece for #name #ty_generics #where_clause {
+            const KEYS: ::fleece::convert::FieldKeys = ::fleece::convert::FieldKeys {
+                keys: &[#(#keys),*],
+                flattened: &[#(&<#flattened_types as ::fleece::ToFleece>::KEYS),*],
+            };
+
+            fn write_fields(
+                &self,
+                encoder: &mut ::fleece::Encoder,
+            ) -> ::core::result::Result<(), ::fleece::encoder::EncodeError> {
+                #check_keys
+                #(#writes)*
+                ::core::result::Result::Ok(())
+            }
+        }
+    })
+}
+
+fn from_fleece(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let fields = fields(input)?;
+    let direct_types: Vec<&Type> = fields
+        .iter()
+        .filter(|f| !f.skip && !f.flatten)
+        .map(|f| f.ty)
+        .collect();
+    let other_bounds: Vec<(&Type, TypeParamBound)> = fields
+        .iter()
+        .filter_map(|f| match (f.skip, f.flatten) {
+            (true, _) => Some((f.ty, parse_quote!(::core::default::Default))),
+            (false, true) => Some((f.ty, parse_quote!(::fleece::FromFleece))),
+            (false, false) => None,
+        })
+        .collect();
+    let generics = add_bounds(
+        &input.generics,
+        &direct_types,
+        &parse_quote!(::fleece::FromFleece),
+        &other_bounds,
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let reads = fields.iter().map(|field| {
+        let ident = field.ident;
+        let ty = field.ty;
+        let key = &field.key;
+        let value = if field.skip {
+            quote! { ::core::default::Default::default() }
+        } else if field.flatten {
+            // The nested struct's keys are in the same Dict
+            quote! { <#ty as ::fleece::FromFleece>::from_fleece(value)? }
+        } else {
+            let missing = if field.default {
+                quote! { ::core::default::Default::default() }
+            } else {
+                quote! {
+                    <#ty as ::fleece::FromFleece>::from_missing()
+                        .ok_or(::fleece::convert::FromFleeceError::MissingField(#key))?
+                }
+            };
+            quote! {
+                match dict.get(#key) {
+                    ::core::option::Option::Some(field) => {
+                        <#ty as ::fleece::FromFleece>::from_fleece(field)?
+                    }
+                    ::core::option::Option::None => #missing,
+                }
+            }
+        };
+        quote! { #ident: #value, }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::fleece::FromFleece for #name #ty_generics #where_clause {
+            fn from_fleece(
+                value: &::fleece::Value,
+            ) -> ::core::result::Result<Self, ::fleece::convert::FromFleeceError> {
+                let dict = value.as_dict().ok_or_else(|| {
+                    ::fleece::convert::FromFleeceError::InvalidType {
+                        expected: "Dict",
+                        found: value.value_type(),
+                    }
+                })?;
+                ::core::result::Result::Ok(Self { #(#reads)* })
+            }
+        }
+    })
+}
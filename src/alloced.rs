@@ -12,6 +12,8 @@ where
     T: ?Sized,
 {
     pub(crate) buf: Arc<[u8]>,
+    // Retained so that extern pointers in `buf` can be read, see `Scope::new_with_extern`
+    pub(crate) extern_destination: Option<Arc<[u8]>>,
     pub(crate) value: *const T,
 }
 
@@ -39,6 +41,7 @@ impl AllocedValue {
         if self.value_type() == ValueType::Array {
             Some(AllocedArray {
                 buf: self.buf,
+                extern_destination: self.extern_destination,
                 value: core::ptr::from_ref(Array::from_value(unsafe { &*self.value })),
             })
         } else {
@@ -52,6 +55,7 @@ impl AllocedValue {
         if self.value_type() == ValueType::Dict {
             Some(AllocedDict {
                 buf: self.buf,
+                extern_destination: self.extern_destination,
                 value: core::ptr::from_ref(Dict::from_value(unsafe { &*self.value })),
             })
         } else {
@@ -62,6 +66,7 @@ impl AllocedValue {
     pub(crate) unsafe fn new_dangling(data: &[u8]) -> Self {
         Self {
             buf: Arc::from(data.to_vec()),
+            extern_destination: None,
             value: core::ptr::slice_from_raw_parts(NonNull::<u8>::dangling().as_ptr(), 0)
                 as *const Value,
         }
@@ -79,6 +84,7 @@ impl AllocedArray {
     pub fn empty() -> Self {
         AllocedArray {
             buf: EMPTY_ARRAY.clone(),
+            extern_destination: None,
             value: core::ptr::slice_from_raw_parts(EMPTY_ARRAY.as_ptr(), EMPTY_ARRAY.len())
                 as *const Array,
         }
@@ -91,6 +97,7 @@ impl AllocedDict {
     pub fn empty() -> Self {
         AllocedDict {
             buf: EMPTY_DICT.clone(),
+            extern_destination: None,
             value: core::ptr::slice_from_raw_parts(EMPTY_DICT.as_ptr(), EMPTY_DICT.len())
                 as *const Dict,
         }
@@ -101,6 +108,7 @@ impl<T: ?Sized> Clone for Alloced<T> {
     fn clone(&self) -> Self {
         Self {
             buf: self.buf.clone(),
+            extern_destination: self.extern_destination.clone(),
             value: self.value,
         }
     }
@@ -110,6 +118,7 @@ impl<T: ?Sized + fmt::Debug> fmt::Debug for Alloced<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Alloced")
             .field("buf", &self.buf)
+            .field("extern_destination", &self.extern_destination)
             .field("value_ptr", &self.value)
             .field("value", &self.value())
             .finish()
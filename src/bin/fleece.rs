@@ -0,0 +1,188 @@
+//! Command-line tool for inspecting and converting Fleece data.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+
+use fleece::encoder::WriterEncoder;
+use fleece::{DeepIterator, KeyPath, Value, ValueType};
+
+const USAGE: &str = "\
+Usage: fleece <command> [options] <file>
+
+Commands:
+  dump <file>                  Annotated hex dump of every value in the data
+  json [--pretty] <file>       Convert Fleece to JSON
+  encode [--json5] <file>      Convert JSON to Fleece, written to stdout
+  validate <file>              Validate the data, reporting the offset of the first error
+  query [--pretty] <keypath> <file>
+                               Print the value at a key path, such as `people[3].name`, as JSON
+  stats <file>                 Count the values in the data, by type
+
+<file> may be `-` to read from stdin.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((command, args)) = args.split_first() else {
+        eprintln!("{USAGE}");
+        return ExitCode::from(2);
+    };
+    let (flags, args): (Vec<&str>, Vec<&str>) = args
+        .iter()
+        .map(String::as_str)
+        .partition(|arg| arg.starts_with("--"));
+    let allowed: &[&str] = match command.as_str() {
+        "json" | "query" => &["--pretty"],
+        "encode" => &["--json5"],
+        _ => &[],
+    };
+    if let Some(flag) = flags.iter().find(|flag| !allowed.contains(flag)) {
+        eprintln!("fleece: unknown option `{flag}` for `{command}`\n\n{USAGE}");
+        return ExitCode::from(2);
+    }
+    let pretty = flags.contains(&"--pretty");
+    let json5 = flags.contains(&"--json5");
+
+    let result = match (command.as_str(), args.as_slice()) {
+        ("dump", [file]) => dump(file),
+        ("json", [file]) => json(file, pretty),
+        ("encode", [file]) => encode(file, json5),
+        ("validate", [file]) => validate(file),
+        ("query", [key_path, file]) => query(key_path, file, pretty),
+        ("stats", [file]) => stats(file),
+        ("help", []) => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("fleece: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, String>;
+
+fn read_input(file: &str) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let result = if file == "-" {
+        io::stdin().read_to_end(&mut data).map(|_| ())
+    } else {
+        std::fs::File::open(file).and_then(|mut f| f.read_to_end(&mut data).map(|_| ()))
+    };
+    result.map_err(|e| format!("{file}: {e}"))?;
+    Ok(data)
+}
+
+fn read_fleece(data: &[u8]) -> Result<&Value> {
+    Value::from_bytes(data).map_err(|_| invalid_message(data))
+}
+
+fn invalid_message(data: &[u8]) -> String {
+    match Value::validation_error(data) {
+        Some((offset, error)) => format!("invalid Fleece data at offset {offset:#06x}: {error}"),
+        None => String::from("invalid Fleece data"),
+    }
+}
+
+fn write_output(output: &[u8]) -> Result<()> {
+    io::stdout()
+        .write_all(output)
+        .map_err(|e| format!("stdout: {e}"))
+}
+
+fn dump(file: &str) -> Result<()> {
+    let data = read_input(file)?;
+    let mut out = String::new();
+    Value::dump_bytes(&data, &mut out).map_err(|e| e.to_string())?;
+    write_output(out.as_bytes())
+}
+
+fn json(file: &str, pretty: bool) -> Result<()> {
+    let data = read_input(file)?;
+    let root = read_fleece(&data)?;
+    write_output(to_json(root, pretty).as_bytes())
+}
+
+fn encode(file: &str, json5: bool) -> Result<()> {
+    let data = read_input(file)?;
+    let json = std::str::from_utf8(&data).map_err(|e| format!("{file}: {e}"))?;
+    let mut encoder = WriterEncoder::new(io::stdout());
+    let result = if json5 {
+        encoder.write_json5(json)
+    } else {
+        encoder.write_json(json)
+    };
+    result.map_err(|e| format!("{file}: {e}"))?;
+    encoder
+        .finish()
+        .map(|_| ())
+        .map_err(|e| format!("stdout: {e}"))
+}
+
+fn validate(file: &str) -> Result<()> {
+    let data = read_input(file)?;
+    read_fleece(&data)?;
+    println!("{file}: valid, {} bytes", data.len());
+    Ok(())
+}
+
+fn query(key_path: &str, file: &str, pretty: bool) -> Result<()> {
+    let key_path = KeyPath::new(key_path).map_err(|e| format!("invalid key path: {e}"))?;
+    let data = read_input(file)?;
+    let root = read_fleece(&data)?;
+    let value = key_path
+        .eval(root)
+        .ok_or_else(|| format!("no value at {key_path}"))?;
+    write_output(to_json(value, pretty).as_bytes())
+}
+
+fn stats(file: &str) -> Result<()> {
+    let data = read_input(file)?;
+    let root = read_fleece(&data)?;
+    let mut counts = BTreeMap::new();
+    let mut total = 0;
+    let mut max_depth = 0;
+    let mut string_bytes = 0;
+    let mut data_bytes = 0;
+    let mut iter = DeepIterator::new(root);
+    while let Some(value) = iter.next() {
+        total += 1;
+        max_depth = max_depth.max(iter.path().len());
+        match value.value_type() {
+            ValueType::String => string_bytes += value.to_str().len(),
+            ValueType::Data => data_bytes += value.to_data().len(),
+            _ => {}
+        }
+        *counts
+            .entry(format!("{:?}", value.value_type()))
+            .or_insert(0usize) += 1;
+    }
+    println!("Size:       {} bytes", data.len());
+    println!("Root:       {:?}", root.value_type());
+    println!("Values:     {total}");
+    println!("Max depth:  {max_depth}");
+    println!("Strings:    {string_bytes} bytes");
+    println!("Data:       {data_bytes} bytes");
+    for (value_type, count) in counts {
+        println!("  {value_type:<10}{count}");
+    }
+    Ok(())
+}
+
+fn to_json(value: &Value, pretty: bool) -> String {
+    let mut json = if pretty {
+        fleece::to_json_pretty(value)
+    } else {
+        fleece::to_json(value)
+    };
+    json.push('\n');
+    json
+}
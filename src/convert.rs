@@ -0,0 +1,301 @@
+//! Traits for encoding Rust types to Fleece and decoding them, without serde.
+//!
+//! With the `derive` feature, `#[derive(ToFleece, FromFleece)]` implements these traits for a
+//! struct with named fields, which is encoded as a Dict with a key for each field. Fields can be
+//! customised with `#[fleece(...)]` attributes:
+//!
+//! - `rename = "key"` uses a different key for the field.
+//! - `skip` leaves the field out of the Dict. It is set to [`Default::default`] when decoding.
+//! - `default` sets the field to [`Default::default`] if its key is missing when decoding.
+//! - `flatten` writes the keys of a nested struct into the same Dict, rather than a nested Dict.
+//!   Encoding fails with [`EncodeError::DuplicateKey`] if the struct writes one of the same keys.
+//!
+//! ```ignore
+//! #[derive(ToFleece, FromFleece)]
+//! struct Person {
+//!     name: String,
+//!     #[fleece(rename = "yearsOld")]
+//!     age: u32,
+//!     #[fleece(default)]
+//!     tags: Vec<String>,
+//! }
+//! ```
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::fmt;
+
+use crate::encoder::EncodeError;
+use crate::{Encoder, MutableArray, MutableDict, Value, ValueType};
+
+/// A type which is encoded as the keys and values of a Fleece Dict. This is usually implemented
+/// with `#[derive(ToFleece)]`. Every `ToFleece` type is [`Encodable`](crate::encoder::Encodable)
+/// as a Dict, so it can be written with [`Encoder::write_value`], or nested in other types.
+pub trait ToFleece {
+    /// The keys written by [`write_fields`](Self::write_fields), so `#[derive(ToFleece)]` can
+    /// check that a struct doesn't write the same key as a struct it flattens. By default, this is
+    /// [`FieldKeys::UNKNOWN`], which isn't checked.
+    const KEYS: FieldKeys = FieldKeys::UNKNOWN;
+
+    /// Write the fields of this value to the open Dict, as keys and values.
+    /// ## Errors
+    /// Errors from writing the keys and values to the Encoder.
+    fn write_fields(&self, encoder: &mut Encoder) -> Result<(), EncodeError>;
+}
+
+impl<T: ToFleece + ?Sized> ToFleece for &T {
+    const KEYS: FieldKeys = T::KEYS;
+
+    fn write_fields(&self, encoder: &mut Encoder) -> Result<(), EncodeError> {
+        (**self).write_fields(encoder)
+    }
+}
+
+/// The keys written by [`ToFleece::write_fields`]: those of the type itself, and those of each
+/// type it flattens into the same Dict.
+#[derive(Debug)]
+pub struct FieldKeys {
+    pub keys: &'static [&'static str],
+    pub flattened: &'static [&'static FieldKeys],
+}
+
+impl FieldKeys {
+    /// The keys of a type which doesn't list them.
+    pub const UNKNOWN: FieldKeys = FieldKeys {
+        keys: &[],
+        flattened: &[],
+    };
+
+    /// Return a key which is written more than once, if there is one.
+    #[must_use]
+    pub const fn duplicate(&self) -> Option<&'static str> {
+        let len = self._len();
+        let mut i = 0;
+        while i < len {
+            let key = self._get(i);
+            let mut j = i + 1;
+            while j < len {
+                if str_eq(key, self._get(j)) {
+                    return Some(key);
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+        None
+    }
+
+    const fn _len(&self) -> usize {
+        let mut len = self.keys.len();
+        let mut i = 0;
+        while i < self.flattened.len() {
+            len += self.flattened[i]._len();
+            i += 1;
+        }
+        len
+    }
+
+    /// The key at `index`, counting the type's own keys first, then those of each flattened type.
+    const fn _get(&self, mut index: usize) -> &'static str {
+        if index < self.keys.len() {
+            return self.keys[index];
+        }
+        index -= self.keys.len();
+        let mut i = 0;
+        loop {
+            let len = self.flattened[i]._len();
+            if index < len {
+                return self.flattened[i]._get(index);
+            }
+            index -= len;
+            i += 1;
+        }
+    }
+}
+
+// `==` can't be used on strings in a const fn
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// A type which can be decoded from a Fleece [`Value`]. This is usually implemented with
+/// `#[derive(FromFleece)]`, which decodes a struct from a Dict by looking up the key of each field.
+pub trait FromFleece: Sized {
+    /// Decode a value of this type.
+    /// ## Errors
+    /// If the value is not of the expected type, or a field is missing.
+    fn from_fleece(value: &Value) -> Result<Self, FromFleeceError>;
+
+    /// The value to use for a struct field when its key is missing from the Dict. By default,
+    /// the field is required, so this is [`None`].
+    #[must_use]
+    fn from_missing() -> Option<Self> {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub enum FromFleeceError {
+    MissingField(&'static str),
+    InvalidType {
+        expected: &'static str,
+        found: ValueType,
+    },
+    OutOfRange,
+}
+
+impl fmt::Display for FromFleeceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FromFleeceError::MissingField(field) => write!(f, "Missing field `{field}`"),
+            FromFleeceError::InvalidType { expected, found } => {
+                write!(f, "Expected {expected}, found {found:?}")
+            }
+            FromFleeceError::OutOfRange => write!(f, "Number out of range for the type"),
+        }
+    }
+}
+
+fn invalid_type(expected: &'static str, value: &Value) -> FromFleeceError {
+    FromFleeceError::InvalidType {
+        expected,
+        found: value.value_type(),
+    }
+}
+
+impl FromFleece for bool {
+    fn from_fleece(value: &Value) -> Result<Self, FromFleeceError> {
+        match value.value_type() {
+            ValueType::True => Ok(true),
+            ValueType::False => Ok(false),
+            _ => Err(invalid_type("Bool", value)),
+        }
+    }
+}
+
+macro_rules! impl_from_fleece_int {
+    ($($int:ty),+) => {
+        $(
+            impl FromFleece for $int {
+                fn from_fleece(value: &Value) -> Result<Self, FromFleeceError> {
+                    let converted = match value.value_type() {
+                        ValueType::Short | ValueType::Int => <$int>::try_from(value.to_int()).ok(),
+                        ValueType::UnsignedInt => <$int>::try_from(value.to_unsigned_int()).ok(),
+                        _ => return Err(invalid_type("Integer", value)),
+                    };
+                    converted.ok_or(FromFleeceError::OutOfRange)
+                }
+            }
+        )+
+    };
+}
+
+impl_from_fleece_int!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+fn is_number(value: &Value) -> bool {
+    matches!(
+        value.value_type(),
+        ValueType::Short
+            | ValueType::Int
+            | ValueType::UnsignedInt
+            | ValueType::Float
+            | ValueType::Double32
+            | ValueType::Double64
+    )
+}
+
+impl FromFleece for f64 {
+    fn from_fleece(value: &Value) -> Result<Self, FromFleeceError> {
+        if is_number(value) {
+            Ok(value.to_double())
+        } else {
+            Err(invalid_type("Number", value))
+        }
+    }
+}
+
+impl FromFleece for f32 {
+    #[allow(clippy::cast_possible_truncation)]
+    fn from_fleece(value: &Value) -> Result<Self, FromFleeceError> {
+        f64::from_fleece(value).map(|double| double as f32)
+    }
+}
+
+impl FromFleece for String {
+    fn from_fleece(value: &Value) -> Result<Self, FromFleeceError> {
+        match value.value_type() {
+            ValueType::String => Ok(value.to_str().into()),
+            _ => Err(invalid_type("String", value)),
+        }
+    }
+}
+
+// Null, undefined and missing fields are decoded as `None`
+impl<T: FromFleece> FromFleece for Option<T> {
+    fn from_fleece(value: &Value) -> Result<Self, FromFleeceError> {
+        match value.value_type() {
+            ValueType::Null | ValueType::Undefined => Ok(None),
+            _ => T::from_fleece(value).map(Some),
+        }
+    }
+
+    fn from_missing() -> Option<Self> {
+        Some(None)
+    }
+}
+
+impl<T: FromFleece> FromFleece for Vec<T> {
+    fn from_fleece(value: &Value) -> Result<Self, FromFleeceError> {
+        let array = value
+            .as_array()
+            .ok_or_else(|| invalid_type("Array", value))?;
+        array.into_iter().map(T::from_fleece).collect()
+    }
+}
+
+impl<T: FromFleece> FromFleece for BTreeMap<String, T> {
+    fn from_fleece(value: &Value) -> Result<Self, FromFleeceError> {
+        let dict = value.as_dict().ok_or_else(|| invalid_type("Dict", value))?;
+        dict.into_iter()
+            .map(|(key, value)| Ok((key.into(), T::from_fleece(value)?)))
+            .collect()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: FromFleece, S: core::hash::BuildHasher + Default> FromFleece
+    for std::collections::HashMap<String, T, S>
+{
+    fn from_fleece(value: &Value) -> Result<Self, FromFleeceError> {
+        let dict = value.as_dict().ok_or_else(|| invalid_type("Dict", value))?;
+        dict.into_iter()
+            .map(|(key, value)| Ok((key.into(), T::from_fleece(value)?)))
+            .collect()
+    }
+}
+
+impl FromFleece for MutableDict {
+    fn from_fleece(value: &Value) -> Result<Self, FromFleeceError> {
+        let dict = value.as_dict().ok_or_else(|| invalid_type("Dict", value))?;
+        Ok(MutableDict::clone_from(dict))
+    }
+}
+
+impl FromFleece for MutableArray {
+    fn from_fleece(value: &Value) -> Result<Self, FromFleeceError> {
+        let array = value
+            .as_array()
+            .ok_or_else(|| invalid_type("Array", value))?;
+        Ok(MutableArray::clone_from(array))
+    }
+}
@@ -1,10 +1,15 @@
 use alloc::{string::String, sync::Arc};
 use core::fmt;
 
+use crate::alloced::AllocedValue;
+use crate::encoder::NullValue;
+use crate::mutable::{self, ValueSlot};
 use crate::scope::Scope;
 use crate::value::array;
 use crate::value::pointer::Pointer;
-use crate::{Array, Dict, Error, Result, SharedKeys, Value, ValueType};
+use crate::{Array, Dict, Encoder, Error, Result, SharedKeys, Value, ValueType};
+use crate::{MutableArray, MutableDict};
+use serde::de::value::BorrowedStrDeserializer;
 use serde::de::{DeserializeSeed, Visitor};
 use serde::{de, forward_to_deserialize_any};
 
@@ -12,6 +17,8 @@ pub struct Deserializer<'value, 'sk> {
     value: &'value Value,
     shared_keys: SK<'sk>,
     is_dict_key: bool,
+    // Whether structs are deserialized by looking up each field. See `from_bytes_lookup_fields`.
+    lookup_fields: bool,
 }
 
 enum SK<'sk> {
@@ -20,8 +27,8 @@ enum SK<'sk> {
     Owned(Arc<SharedKeys>),
 }
 
-impl<'sk> SK<'sk> {
-    fn as_ref(&self) -> SK {
+impl SK<'_> {
+    fn as_ref(&self) -> SK<'_> {
         match self {
             SK::None => SK::None,
             SK::Ref(sk) => SK::Ref(sk),
@@ -38,7 +45,9 @@ impl<'sk> SK<'sk> {
     }
 }
 
-/// Deserialize a value from Fleece-encoded bytes.
+/// Deserialize a value from Fleece-encoded bytes. Strings and data are borrowed directly from
+/// `bytes`, so `T` may contain `&str` and `&[u8]` fields. Dict keys which are shared keys can't
+/// be borrowed, as they are not stored in `bytes`.
 /// # Errors
 /// Returns an error if the bytes are not valid Fleece-encoded data or if the data cannot be
 /// deserialized into the requested type.
@@ -51,6 +60,32 @@ where
     T::deserialize(&deserializer)
 }
 
+/// Like [`from_bytes`], but structs are deserialized by looking up each of their fields in the
+/// Dict, rather than visiting every key. This is faster for Dicts with many keys which are not
+/// fields, but those keys are ignored, even with `#[serde(deny_unknown_fields)]`.
+/// # Errors
+/// Returns an error if the bytes are not valid Fleece-encoded data or if the data cannot be
+/// deserialized into the requested type.
+pub fn from_bytes_lookup_fields<'a, T>(bytes: &'a [u8]) -> Result<T>
+where
+    T: serde::Deserialize<'a>,
+{
+    let value = Value::from_bytes(bytes)?;
+    let deserializer = Deserializer::init(value, true);
+    T::deserialize(&deserializer)
+}
+
+/// Deserialize a value from a [`MutableDict`], without encoding it to Fleece. Strings and data
+/// are borrowed from `dict`.
+/// # Errors
+/// Returns an error if the dict cannot be deserialized into the requested type.
+pub fn from_mutable<'a, T>(dict: &'a MutableDict) -> Result<T>
+where
+    T: serde::Deserialize<'a>,
+{
+    T::deserialize(MutableDeserializer::Dict(dict))
+}
+
 #[derive(Debug)]
 pub enum DeserializeError {
     NotArray,
@@ -83,15 +118,15 @@ impl fmt::Display for DeserializeError {
 }
 
 impl<'value, 'sk> Deserializer<'value, 'sk> {
-    fn init(value: &'value Value, is_wide: bool) -> Self {
+    fn init(value: &'value Value, lookup_fields: bool) -> Self {
         let sk = match Scope::find_shared_keys(value.bytes.as_ptr()) {
             Some(sk) => SK::Owned(sk),
             None => SK::None,
         };
-        Self::new(value, is_wide, sk)
+        Self::new(value, false, sk, lookup_fields)
     }
 
-    fn new(value: &'value Value, is_wide: bool, shared_keys: SK<'sk>) -> Self {
+    fn new(value: &'value Value, is_wide: bool, shared_keys: SK<'sk>, lookup_fields: bool) -> Self {
         let value = if value.value_type() == ValueType::Pointer {
             unsafe { Pointer::from_value(value).deref_unchecked(is_wide) }
         } else {
@@ -101,6 +136,7 @@ impl<'value, 'sk> Deserializer<'value, 'sk> {
             value,
             shared_keys,
             is_dict_key: false,
+            lookup_fields,
         }
     }
 
@@ -114,11 +150,12 @@ impl<'value, 'sk> Deserializer<'value, 'sk> {
             value,
             shared_keys,
             is_dict_key: true,
+            lookup_fields: false,
         }
     }
 }
 
-impl<'de, 'value, 'sk> de::Deserializer<'de> for &Deserializer<'value, 'sk> {
+impl<'de> de::Deserializer<'de> for &Deserializer<'de, '_> {
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
@@ -145,15 +182,17 @@ impl<'de, 'value, 'sk> de::Deserializer<'de> for &Deserializer<'value, 'sk> {
             ValueType::UnsignedInt => visitor.visit_u64(self.value.to_unsigned_int()),
             ValueType::Float => visitor.visit_f32(self.value.to_float()),
             ValueType::Double32 | ValueType::Double64 => visitor.visit_f64(self.value.to_double()),
-            ValueType::String => visitor.visit_str(self.value.to_str()),
-            ValueType::Data => visitor.visit_bytes(self.value.to_data()),
+            ValueType::String => visitor.visit_borrowed_str(self.value.to_str()),
+            ValueType::Data => visitor.visit_borrowed_bytes(self.value.to_data()),
             ValueType::Array => visitor.visit_seq(ArrayAccess::new(
                 Array::from_value(self.value),
                 self.shared_keys.as_ref(),
+                self.lookup_fields,
             )),
             ValueType::Dict => visitor.visit_map(DictAccess::new(
                 Dict::from_value(self.value),
                 self.shared_keys.as_ref(),
+                self.lookup_fields,
             )),
             ValueType::Pointer => {
                 unreachable!("A Pointer Value should not reach into `deserialize_any`!")
@@ -176,7 +215,11 @@ impl<'de, 'value, 'sk> de::Deserializer<'de> for &Deserializer<'value, 'sk> {
         V: Visitor<'de>,
     {
         if let Some(arr) = self.value.as_array() {
-            visitor.visit_seq(ArrayAccess::new(arr, self.shared_keys.as_ref()))
+            visitor.visit_seq(ArrayAccess::new(
+                arr,
+                self.shared_keys.as_ref(),
+                self.lookup_fields,
+            ))
         } else {
             Err(Error::Deserialize(DeserializeError::NotArray))
         }
@@ -187,22 +230,36 @@ impl<'de, 'value, 'sk> de::Deserializer<'de> for &Deserializer<'value, 'sk> {
         V: Visitor<'de>,
     {
         if let Some(dict) = self.value.as_dict() {
-            visitor.visit_map(DictAccess::new(dict, self.shared_keys.as_ref()))
+            visitor.visit_map(DictAccess::new(
+                dict,
+                self.shared_keys.as_ref(),
+                self.lookup_fields,
+            ))
         } else {
             Err(Error::Deserialize(DeserializeError::NotDict))
         }
     }
 
+    /// When deserializing with [`from_bytes_lookup_fields`], look up each of the struct's `fields`
+    /// by key rather than iterating over every entry of the Dict. Keys which are not in `fields`
+    /// are never visited, so they are ignored even with `#[serde(deny_unknown_fields)]`.
     fn deserialize_struct<V>(
         self,
         _name: &'static str,
-        _fields: &'static [&'static str],
+        fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_map(visitor)
+        if !self.lookup_fields {
+            return self.deserialize_map(visitor);
+        }
+        if let Some(dict) = self.value.as_dict() {
+            visitor.visit_map(StructAccess::new(dict, fields, self.shared_keys.as_ref()))
+        } else {
+            Err(Error::Deserialize(DeserializeError::NotDict))
+        }
     }
 
     fn deserialize_enum<V>(
@@ -220,7 +277,11 @@ impl<'de, 'value, 'sk> de::Deserializer<'de> for &Deserializer<'value, 'sk> {
             )));
         };
 
-        visitor.visit_enum(EnumAccess::new(array, self.shared_keys.as_ref()))
+        visitor.visit_enum(EnumAccess::new(
+            array,
+            self.shared_keys.as_ref(),
+            self.lookup_fields,
+        ))
     }
 
     fn is_human_readable(&self) -> bool {
@@ -236,18 +297,20 @@ impl<'de, 'value, 'sk> de::Deserializer<'de> for &Deserializer<'value, 'sk> {
 struct ArrayAccess<'iter, 'sk> {
     iter: array::Iter<'iter>,
     shared_keys: SK<'sk>,
+    lookup_fields: bool,
 }
 
 impl<'iter, 'sk> ArrayAccess<'iter, 'sk> {
-    fn new(array: &'iter Array, shared_keys: SK<'sk>) -> Self {
+    fn new(array: &'iter Array, shared_keys: SK<'sk>, lookup_fields: bool) -> Self {
         Self {
             iter: array.iter(),
             shared_keys,
+            lookup_fields,
         }
     }
 }
 
-impl<'iter, 'de, 'sk> de::SeqAccess<'de> for ArrayAccess<'iter, 'sk> {
+impl<'de> de::SeqAccess<'de> for ArrayAccess<'de, '_> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -261,6 +324,7 @@ impl<'iter, 'de, 'sk> de::SeqAccess<'de> for ArrayAccess<'iter, 'sk> {
                     next,
                     self.iter.width == 4,
                     self.shared_keys.as_ref(),
+                    self.lookup_fields,
                 ))
                 .map(Some),
         }
@@ -274,18 +338,20 @@ impl<'iter, 'de, 'sk> de::SeqAccess<'de> for ArrayAccess<'iter, 'sk> {
 struct DictAccess<'iter, 'sk> {
     iter: array::Iter<'iter>,
     shared_keys: SK<'sk>,
+    lookup_fields: bool,
 }
 
 impl<'iter, 'sk> DictAccess<'iter, 'sk> {
-    fn new(dict: &'iter Dict, shared_keys: SK<'sk>) -> Self {
+    fn new(dict: &'iter Dict, shared_keys: SK<'sk>, lookup_fields: bool) -> Self {
         Self {
             iter: dict.array.iter(),
             shared_keys,
+            lookup_fields,
         }
     }
 }
 
-impl<'iter, 'de, 'sk> de::MapAccess<'de> for DictAccess<'iter, 'sk> {
+impl<'de> de::MapAccess<'de> for DictAccess<'de, '_> {
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
@@ -314,6 +380,7 @@ impl<'iter, 'de, 'sk> de::MapAccess<'de> for DictAccess<'iter, 'sk> {
                 next,
                 self.iter.width == 4,
                 self.shared_keys.as_ref(),
+                self.lookup_fields,
             )),
         }
     }
@@ -323,18 +390,80 @@ impl<'iter, 'de, 'sk> de::MapAccess<'de> for DictAccess<'iter, 'sk> {
     }
 }
 
+struct StructAccess<'dict, 'sk> {
+    dict: &'dict Dict,
+    fields: core::slice::Iter<'static, &'static str>,
+    value: Option<&'dict Value>,
+    shared_keys: SK<'sk>,
+}
+
+impl<'dict, 'sk> StructAccess<'dict, 'sk> {
+    fn new(dict: &'dict Dict, fields: &'static [&'static str], shared_keys: SK<'sk>) -> Self {
+        Self {
+            dict,
+            fields: fields.iter(),
+            value: None,
+            shared_keys,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for StructAccess<'de, '_> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        // Fields which are missing from the Dict are skipped, serde handles them once we're done
+        for field in self.fields.by_ref() {
+            let value = match self.shared_keys.shared_keys() {
+                Some(sk) => self.dict.get_with_shared_keys(*field, sk),
+                None => self.dict.get(*field),
+            };
+            if let Some(value) = value {
+                self.value = Some(value);
+                return seed
+                    .deserialize(BorrowedStrDeserializer::new(field))
+                    .map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            None => Err(Error::Deserialize(DeserializeError::KeyWithoutValue)),
+            Some(value) => seed.deserialize(&Deserializer::new(
+                value,
+                self.dict.is_wide(),
+                self.shared_keys.as_ref(),
+                true,
+            )),
+        }
+    }
+}
+
 struct EnumAccess<'arr, 'sk> {
     array: &'arr Array,
     shared_keys: SK<'sk>,
+    lookup_fields: bool,
 }
 
 impl<'arr, 'sk> EnumAccess<'arr, 'sk> {
-    fn new(array: &'arr Array, shared_keys: SK<'sk>) -> Self {
-        Self { array, shared_keys }
+    fn new(array: &'arr Array, shared_keys: SK<'sk>, lookup_fields: bool) -> Self {
+        Self {
+            array,
+            shared_keys,
+            lookup_fields,
+        }
     }
 }
 
-impl<'arr, 'sk, 'de> de::EnumAccess<'de> for EnumAccess<'arr, 'sk> {
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de, '_> {
     type Error = Error;
     type Variant = Self;
 
@@ -354,13 +483,14 @@ impl<'arr, 'sk, 'de> de::EnumAccess<'de> for EnumAccess<'arr, 'sk> {
             variant,
             self.array.is_wide(),
             self.shared_keys.as_ref(),
+            self.lookup_fields,
         ))?;
 
         Ok((value, self))
     }
 }
 
-impl<'arr, 'sk, 'de> de::VariantAccess<'de> for EnumAccess<'arr, 'sk> {
+impl<'de> de::VariantAccess<'de> for EnumAccess<'de, '_> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
@@ -388,6 +518,7 @@ impl<'arr, 'sk, 'de> de::VariantAccess<'de> for EnumAccess<'arr, 'sk> {
             inner,
             self.array.is_wide(),
             self.shared_keys.as_ref(),
+            self.lookup_fields,
         ))
     }
 
@@ -405,7 +536,12 @@ impl<'arr, 'sk, 'de> de::VariantAccess<'de> for EnumAccess<'arr, 'sk> {
         if let Some(array) = inner.as_array() {
             if array.len() == len {
                 return de::Deserializer::deserialize_seq(
-                    &Deserializer::new(inner, self.array.is_wide(), self.shared_keys.as_ref()),
+                    &Deserializer::new(
+                        inner,
+                        self.array.is_wide(),
+                        self.shared_keys.as_ref(),
+                        self.lookup_fields,
+                    ),
                     visitor,
                 );
             }
@@ -438,7 +574,12 @@ impl<'arr, 'sk, 'de> de::VariantAccess<'de> for EnumAccess<'arr, 'sk> {
 
                 if correct_keys {
                     return de::Deserializer::deserialize_map(
-                        &Deserializer::new(inner, self.array.is_wide(), self.shared_keys.as_ref()),
+                        &Deserializer::new(
+                            inner,
+                            self.array.is_wide(),
+                            self.shared_keys.as_ref(),
+                            self.lookup_fields,
+                        ),
                         visitor,
                     );
                 }
@@ -449,3 +590,387 @@ impl<'arr, 'sk, 'de> de::VariantAccess<'de> for EnumAccess<'arr, 'sk> {
         )))
     }
 }
+
+// Fleece collections can be deserialized from any serde format, by building mutable collections.
+
+/// Deserializes any value into a [`ValueSlot`].
+struct SlotVisitor;
+
+impl<'de> Visitor<'de> for SlotVisitor {
+    type Value = ValueSlot;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> core::result::Result<ValueSlot, E> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> core::result::Result<ValueSlot, E> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> core::result::Result<ValueSlot, E> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn visit_f32<E: de::Error>(self, v: f32) -> core::result::Result<ValueSlot, E> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> core::result::Result<ValueSlot, E> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> core::result::Result<ValueSlot, E> {
+        Ok(ValueSlot::new(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> core::result::Result<ValueSlot, E> {
+        Ok(ValueSlot::new(v))
+    }
+
+    fn visit_none<E: de::Error>(self) -> core::result::Result<ValueSlot, E> {
+        Ok(ValueSlot::new(&NullValue))
+    }
+
+    // Formats such as JSON visit `null` as unit, so this is also null rather than undefined
+    fn visit_unit<E: de::Error>(self) -> core::result::Result<ValueSlot, E> {
+        Ok(ValueSlot::new(&NullValue))
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> core::result::Result<ValueSlot, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> core::result::Result<ValueSlot, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A>(self, seq: A) -> core::result::Result<ValueSlot, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        ArrayVisitor.visit_seq(seq).map(ValueSlot::new_array)
+    }
+
+    fn visit_map<A>(self, map: A) -> core::result::Result<ValueSlot, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        DictVisitor.visit_map(map).map(ValueSlot::new_dict)
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for SlotVisitor {
+    type Value = ValueSlot;
+
+    fn deserialize<D>(self, deserializer: D) -> core::result::Result<ValueSlot, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+struct ArrayVisitor;
+
+impl<'de> Visitor<'de> for ArrayVisitor {
+    type Value = MutableArray;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> core::result::Result<MutableArray, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut array = MutableArray::new();
+        while let Some(slot) = seq.next_element_seed(SlotVisitor)? {
+            array.push_slot(slot);
+        }
+        Ok(array)
+    }
+}
+
+struct DictVisitor;
+
+impl<'de> Visitor<'de> for DictVisitor {
+    type Value = MutableDict;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map with string keys")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> core::result::Result<MutableDict, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        let mut dict = MutableDict::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let slot = map.next_value_seed(SlotVisitor)?;
+            dict.insert_slot(&key, slot);
+        }
+        Ok(dict)
+    }
+}
+
+impl<'de> de::Deserialize<'de> for MutableArray {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(ArrayVisitor)
+    }
+}
+
+impl<'de> de::Deserialize<'de> for MutableDict {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(DictVisitor)
+    }
+}
+
+impl<'de> de::Deserialize<'de> for AllocedValue {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let slot = SlotVisitor.deserialize(deserializer)?;
+        let mut encoder = Encoder::new();
+        let written = match &slot {
+            ValueSlot::MutableArray(array) => encoder.write_mutable_array(array),
+            ValueSlot::MutableDict(dict) => encoder.write_mutable_dict(dict),
+            ValueSlot::Inline(_) | ValueSlot::Pointer(_) => {
+                encoder.write_fleece(slot.value().unwrap())
+            }
+        };
+        written.map_err(de::Error::custom)?;
+        Value::clone_from_bytes(&encoder.finish()).map_err(de::Error::custom)
+    }
+}
+
+// Deserializes from mutable collections. Enums have the same layout as with the Fleece `Deserializer`.
+#[derive(Clone, Copy)]
+enum MutableDeserializer<'a> {
+    Value(&'a Value),
+    Array(&'a MutableArray),
+    Dict(&'a MutableDict),
+}
+
+impl<'a> MutableDeserializer<'a> {
+    fn from_slot(slot: &'a ValueSlot) -> Self {
+        match slot {
+            ValueSlot::MutableArray(array) => Self::Array(array),
+            ValueSlot::MutableDict(dict) => Self::Dict(dict),
+            // Inline and pointer slots always hold a Value
+            ValueSlot::Inline(_) | ValueSlot::Pointer(_) => Self::Value(slot.value().unwrap()),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for MutableDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Self::Value(value) => de::Deserializer::deserialize_any(
+                &Deserializer::new(value, false, SK::None, false),
+                visitor,
+            ),
+            Self::Array(array) => visitor.visit_seq(MutableArrayAccess { iter: array.iter() }),
+            Self::Dict(dict) => visitor.visit_map(MutableDictAccess {
+                iter: dict.iter(),
+                value: None,
+            }),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Self::Value(value) if value.value_type() == ValueType::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Self::Array(array) => visitor.visit_enum(MutableEnumAccess { array }),
+            Self::Value(value) => Err(Error::Deserialize(DeserializeError::InvalidEnumType(
+                value.value_type(),
+            ))),
+            Self::Dict(_) => Err(Error::Deserialize(DeserializeError::InvalidEnumType(
+                ValueType::Dict,
+            ))),
+        }
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit
+        unit_struct newtype_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct MutableArrayAccess<'a> {
+    iter: mutable::array::Iter<'a>,
+}
+
+impl<'de> de::SeqAccess<'de> for MutableArrayAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.iter
+            .next()
+            .map(|item| seed.deserialize(MutableDeserializer::from_slot(item.slot())))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.iter.size_hint().1
+    }
+}
+
+struct MutableDictAccess<'a> {
+    iter: mutable::dict::Iter<'a>,
+    value: Option<&'a ValueSlot>,
+}
+
+impl<'de> de::MapAccess<'de> for MutableDictAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            None => Ok(None),
+            Some((key, item)) => {
+                self.value = Some(item.slot());
+                seed.deserialize(BorrowedStrDeserializer::new(key))
+                    .map(Some)
+            }
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            None => Err(Error::Deserialize(DeserializeError::KeyWithoutValue)),
+            Some(slot) => seed.deserialize(MutableDeserializer::from_slot(slot)),
+        }
+    }
+}
+
+struct MutableEnumAccess<'a> {
+    array: &'a MutableArray,
+}
+
+impl<'a> MutableEnumAccess<'a> {
+    fn get(&self, index: usize) -> Result<MutableDeserializer<'a>> {
+        self.array
+            .get_slot(index)
+            .map(MutableDeserializer::from_slot)
+            .ok_or_else(|| self.invalid_layout())
+    }
+
+    fn invalid_layout(&self) -> Error {
+        Error::Deserialize(DeserializeError::InvalidEnumLayout(format!(
+            "{:?}",
+            self.array
+        )))
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for MutableEnumAccess<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        // The variant name is at array index 0
+        let value = seed.deserialize(self.get(0)?)?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for MutableEnumAccess<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        if self.array.len() == 1 {
+            Ok(())
+        } else {
+            Err(self.invalid_layout())
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        // Inner variant data is at index 1 in the array
+        seed.deserialize(self.get(1)?)
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // Inner tuple is stored as an array at index 1
+        match self.get(1)? {
+            MutableDeserializer::Array(array) if array.len() == len => {
+                visitor.visit_seq(MutableArrayAccess { iter: array.iter() })
+            }
+            _ => Err(self.invalid_layout()),
+        }
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // Inner struct is stored as a dict at index 1
+        match self.get(1)? {
+            MutableDeserializer::Dict(dict) => visitor.visit_map(MutableDictAccess {
+                iter: dict.iter(),
+                value: None,
+            }),
+            _ => Err(self.invalid_layout()),
+        }
+    }
+}
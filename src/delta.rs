@@ -0,0 +1,265 @@
+//! JSON deltas between two Fleece values, compatible with the `JSONDelta` format of C Fleece.
+//!
+//! A delta describes how to turn an old value into a new value:
+//!
+//! - A deletion is written as `[]`.
+//! - A replacement is written as the new value itself. If the new value is an array or a dict,
+//!   it is wrapped in an array, as `[new]`, so that it can't be confused with the other forms.
+//! - Changes to a dict are written as a JSON object, mapping each changed key to a delta of its
+//!   value.
+//! - Changes to an array are written as a JSON object, mapping each changed index (as a decimal
+//!   string) to a delta of its element. Appended elements are replacements at indices past the end
+//!   of the old array. If the array got shorter, the key `"-"` gives its new length.
+//!
+//! Unchanged keys and elements are left out, so a delta is usually much smaller than the new
+//! value. Strings are always replaced whole; text diffs are not supported.
+
+use alloc::{string::ToString, vec::Vec};
+use core::fmt;
+
+use crate::{Array, Dict, Encoder, MutableDict, Value, ValueType};
+
+/// The key of an array delta which gives the new length of the array, if it got shorter.
+const ARRAY_LENGTH_KEY: &str = "-";
+
+#[derive(Debug)]
+pub enum DeltaError {
+    /// The delta is not UTF-8 encoded JSON.
+    InvalidUtf8,
+    /// The delta doesn't have a valid form, or doesn't match the structure of the old value.
+    InvalidDelta,
+    /// Applying the delta deletes the root.
+    InvalidRoot,
+    /// The result of applying the delta is not a dict.
+    NotADict,
+}
+
+impl fmt::Display for DeltaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeltaError::InvalidUtf8 => write!(f, "Delta is not valid UTF-8"),
+            DeltaError::InvalidDelta => write!(f, "Delta is invalid for the old value"),
+            DeltaError::InvalidRoot => write!(f, "Delta deletes the root value"),
+            DeltaError::NotADict => write!(f, "Result of the delta is not a dict"),
+        }
+    }
+}
+
+/// Create a JSON delta which turns `old` into `new`. Returns an empty vec if the values are
+/// equal, as compared by [`Value::deep_eq`].
+#[allow(clippy::missing_panics_doc)]
+#[must_use]
+pub fn create(old: &Value, new: &Value) -> Vec<u8> {
+    if old.deep_eq(new) {
+        return Vec::new();
+    }
+    let mut encoder = Encoder::new();
+    // The root is diffed like any other value, so a scalar replacement is written as is
+    _write_delta(&mut encoder, old, new)
+        .expect("deltas are written to a new Encoder, and only contain valid values");
+    crate::to_json(&*encoder.finish_value()).into_bytes()
+}
+
+/// Apply a delta created by [`create`] to `old`, returning the new value as a [`MutableDict`].
+/// An empty delta leaves the value unchanged.
+/// ## Errors
+/// - If the delta is not valid JSON, or doesn't match the structure of `old`.
+/// - If the new value is not a dict.
+pub fn apply(old: &Value, delta: &[u8]) -> crate::Result<MutableDict> {
+    let encoded = apply_encoded(old, delta)?;
+    let new = Value::from_bytes(&encoded)?;
+    let dict = new.as_dict().ok_or(DeltaError::NotADict)?;
+    Ok(MutableDict::clone_from(dict))
+}
+
+/// The same as [`apply`], but the new value is returned as encoded Fleece, and may be of any
+/// type, including a scalar.
+/// ## Errors
+/// - If the delta is not valid JSON, or doesn't match the structure of `old`.
+/// - If the delta deletes the root.
+pub fn apply_encoded(old: &Value, delta: &[u8]) -> crate::Result<Vec<u8>> {
+    let mut encoder = Encoder::new();
+    if delta.is_empty() {
+        encoder.write_fleece(old)?;
+        return Ok(encoder.finish());
+    }
+    let delta = core::str::from_utf8(delta).map_err(|_| DeltaError::InvalidUtf8)?;
+    let delta = crate::from_json(delta, None)?;
+    let delta = delta.root().ok_or(DeltaError::InvalidDelta)?;
+    if _is_deletion(&delta) {
+        return Err(DeltaError::InvalidRoot.into());
+    }
+    _apply(&mut encoder, Some(old), &delta)?;
+    Ok(encoder.finish())
+}
+
+fn _is_same_collection_type(old: &Value, new: &Value) -> bool {
+    let value_type = old.value_type();
+    matches!(value_type, ValueType::Array | ValueType::Dict) && value_type == new.value_type()
+}
+
+/// Write the delta between two values, which must not be equal.
+fn _write_delta(encoder: &mut Encoder, old: &Value, new: &Value) -> crate::Result<()> {
+    if !_is_same_collection_type(old, new) {
+        return _write_replacement(encoder, new);
+    }
+    encoder.begin_dict()?;
+    if let (Some(old), Some(new)) = (old.as_dict(), new.as_dict()) {
+        _write_dict_delta(encoder, old, new)?;
+    } else if let (Some(old), Some(new)) = (old.as_array(), new.as_array()) {
+        _write_array_delta(encoder, old, new)?;
+    }
+    encoder.end_dict()?;
+    Ok(())
+}
+
+fn _write_dict_delta(encoder: &mut Encoder, old: &Dict, new: &Dict) -> crate::Result<()> {
+    for (key, old_value) in old {
+        match new.get(key) {
+            Some(new_value) if new_value.value_type() != ValueType::Undefined => {
+                if !old_value.deep_eq(new_value) {
+                    encoder.write_key(key)?;
+                    _write_delta(encoder, old_value, new_value)?;
+                }
+            }
+            _ => {
+                encoder.write_key(key)?;
+                encoder.begin_array(0)?;
+                encoder.end_array()?;
+            }
+        }
+    }
+    for (key, new_value) in new {
+        if new_value.value_type() != ValueType::Undefined && old.get(key).is_none() {
+            encoder.write_key(key)?;
+            _write_replacement(encoder, new_value)?;
+        }
+    }
+    Ok(())
+}
+
+fn _write_array_delta(encoder: &mut Encoder, old: &Array, new: &Array) -> crate::Result<()> {
+    for (i, (old_value, new_value)) in old.iter().zip(new).enumerate() {
+        if !old_value.deep_eq(new_value) {
+            encoder.write_key(&i.to_string())?;
+            _write_delta(encoder, old_value, new_value)?;
+        }
+    }
+    for (i, new_value) in new.iter().enumerate().skip(old.len()) {
+        encoder.write_key(&i.to_string())?;
+        _write_replacement(encoder, new_value)?;
+    }
+    if new.len() < old.len() {
+        encoder.write_key(ARRAY_LENGTH_KEY)?;
+        encoder.write_value(new.len() as u64)?;
+    }
+    Ok(())
+}
+
+fn _write_replacement(encoder: &mut Encoder, new: &Value) -> crate::Result<()> {
+    match new.value_type() {
+        ValueType::Array | ValueType::Dict => _write_wrapped(encoder, new),
+        _ => Ok(encoder.write_fleece(new)?),
+    }
+}
+
+fn _write_wrapped(encoder: &mut Encoder, new: &Value) -> crate::Result<()> {
+    encoder.begin_array(1)?;
+    encoder.write_fleece(new)?;
+    encoder.end_array()?;
+    Ok(())
+}
+
+fn _is_deletion(delta: &Value) -> bool {
+    delta.as_array().is_some_and(Array::is_empty)
+}
+
+/// If the delta is a replacement, returns the new value.
+/// ## Errors
+/// If the delta is an array, but not a deletion or a wrapped replacement.
+fn _as_replacement(delta: &Value) -> Result<Option<&Value>, DeltaError> {
+    match delta.value_type() {
+        ValueType::Dict => Ok(None),
+        ValueType::Array => match delta.as_array() {
+            Some(array) if array.len() == 1 => Ok(array.get(0)),
+            _ => Err(DeltaError::InvalidDelta),
+        },
+        _ => Ok(Some(delta)),
+    }
+}
+
+/// Write the result of applying `delta` to `old`. The delta must not be a deletion.
+fn _apply(encoder: &mut Encoder, old: Option<&Value>, delta: &Value) -> crate::Result<()> {
+    if let Some(new) = _as_replacement(delta)? {
+        return Ok(encoder.write_fleece(new)?);
+    }
+    let delta = Dict::from_value(delta);
+    match old.map(Value::value_type) {
+        Some(ValueType::Dict) => _apply_to_dict(encoder, Dict::from_value(old.unwrap()), delta),
+        Some(ValueType::Array) => _apply_to_array(encoder, Array::from_value(old.unwrap()), delta),
+        _ => Err(DeltaError::InvalidDelta.into()),
+    }
+}
+
+fn _apply_to_dict(encoder: &mut Encoder, old: &Dict, delta: &Dict) -> crate::Result<()> {
+    encoder.begin_dict()?;
+    for (key, old_value) in old {
+        match delta.get(key) {
+            Some(value_delta) if _is_deletion(value_delta) => {}
+            Some(value_delta) => {
+                encoder.write_key(key)?;
+                _apply(encoder, Some(old_value), value_delta)?;
+            }
+            None => {
+                encoder.write_key(key)?;
+                encoder.write_fleece(old_value)?;
+            }
+        }
+    }
+    for (key, value_delta) in delta {
+        if old.get(key).is_none() && !_is_deletion(value_delta) {
+            encoder.write_key(key)?;
+            _apply(encoder, None, value_delta)?;
+        }
+    }
+    encoder.end_dict()?;
+    Ok(())
+}
+
+fn _apply_to_array(encoder: &mut Encoder, old: &Array, delta: &Dict) -> crate::Result<()> {
+    let mut len = old.len();
+    for (key, value_delta) in delta {
+        if key == ARRAY_LENGTH_KEY {
+            if !matches!(
+                value_delta.value_type(),
+                ValueType::Short | ValueType::Int | ValueType::UnsignedInt
+            ) {
+                return Err(DeltaError::InvalidDelta.into());
+            }
+            len = usize::try_from(value_delta.to_unsigned_int())
+                .ok()
+                .filter(|new_len| *new_len <= old.len())
+                .ok_or(DeltaError::InvalidDelta)?;
+        } else {
+            let index = key.parse::<usize>().map_err(|_| DeltaError::InvalidDelta)?;
+            // Appended elements must be contiguous, so each one needs its own entry in the delta.
+            // Checking this before opening the array keeps the length bounded by the input.
+            if _is_deletion(value_delta) || index >= old.len() + delta.len() {
+                return Err(DeltaError::InvalidDelta.into());
+            }
+            len = len.max(index + 1);
+        }
+    }
+    encoder.begin_array(len)?;
+    for i in 0..len {
+        let old_value = old.get(i);
+        match (delta.get(&i.to_string()), old_value) {
+            (Some(value_delta), _) => _apply(encoder, old_value, value_delta)?,
+            (None, Some(old_value)) => encoder.write_fleece(old_value)?,
+            // Elements appended past the end of the old array must all be in the delta
+            (None, None) => return Err(DeltaError::InvalidDelta.into()),
+        }
+    }
+    encoder.end_array()?;
+    Ok(())
+}
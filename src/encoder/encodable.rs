@@ -1,37 +1,98 @@
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
 use core::num::NonZeroUsize;
 
+use crate::alloced::Alloced;
 use crate::encoder::value_stack;
-use crate::encoder::{Encodable, NullValue, UndefinedValue};
+use crate::encoder::{ArraySlice, Encodable, Encoder, NullValue, Result, UndefinedValue};
 use crate::value::{array, varint};
 use crate::value::{pointer, SizedValue};
-use crate::{value, ValueType};
+use crate::{value, Array, Dict, MutableArray, MutableDict, ToFleece, Value, ValueType};
 
 // All the built-in implementations of [`Encodable`].
 
-impl<T: super::private::Sealed + ?Sized> super::private::Sealed for &T {}
-impl<T: Encodable + ?Sized> Encodable for &T {
-    #[inline]
-    fn write_fleece_to(&self, buf: &mut [u8], is_wide: bool) -> Option<NonZeroUsize> {
-        (*self).write_fleece_to(buf, is_wide)
-    }
+// References are written as the value they refer to. There can't be one implementation for every
+// `&T`, as it would overlap with the implementation for `T: ToFleece`, so each type has its own,
+// for one or two references, such as the `&&str` items of a `&[&str]`.
+macro_rules! impl_ref {
+    (@impl [$($generics:tt)*] $ty:ty) => {
+        impl<$($generics)*> super::private::Sealed for $ty {}
+        impl<$($generics)*> Encodable for $ty {
+            #[inline]
+            fn write_fleece_to(&self, buf: &mut [u8], is_wide: bool) -> Option<NonZeroUsize> {
+                (*self).write_fleece_to(buf, is_wide)
+            }
 
-    #[inline]
-    fn fleece_size(&self) -> usize {
-        (*self).fleece_size()
-    }
+            #[inline]
+            fn fleece_size(&self) -> usize {
+                (*self).fleece_size()
+            }
 
-    #[inline]
-    fn to_sized_value(&self) -> Option<SizedValue> {
-        (*self).to_sized_value()
-    }
+            #[inline]
+            fn to_sized_value(&self) -> Option<SizedValue> {
+                (*self).to_sized_value()
+            }
+
+            #[inline]
+            fn to_fleece_str(&self) -> Option<&str> {
+                (*self).to_fleece_str()
+            }
+
+            #[inline]
+            fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+                (*self).write_to(encoder)
+            }
+        }
+    };
+    ($([$($generics:tt)*] $ty:ty),+ $(,)?) => {
+        $(
+            impl_ref!(@impl [$($generics)*] &$ty);
+            impl_ref!(@impl [$($generics)*] &&$ty);
+        )+
+    };
 }
 
+impl_ref!(
+    [] i64,
+    [] u64,
+    [] isize,
+    [] usize,
+    [] i32,
+    [] u32,
+    [] u16,
+    [] i16,
+    [] u8,
+    [] i8,
+    [] f32,
+    [] f64,
+    [] bool,
+    [] NullValue,
+    [] UndefinedValue,
+    [] [u8],
+    [] str,
+    [] String,
+    [T: Encodable] Option<T>,
+    [] SizedValue,
+    [T: Encodable] Vec<T>,
+    [T: Encodable, const N: usize] [T; N],
+    [T: Encodable] ArraySlice<'_, T>,
+    [K: AsRef<str>, V: Encodable] BTreeMap<K, V>,
+    [] Value,
+    [] Array,
+    [] Dict,
+    [] MutableArray,
+    [] MutableDict,
+    [T: Encodable + ?Sized] Alloced<T>,
+);
+
+#[cfg(feature = "std")]
+impl_ref!([K: AsRef<str>, V: Encodable, S] std::collections::HashMap<K, V, S>);
+
 impl super::private::Sealed for i64 {}
 impl Encodable for i64 {
     #[allow(clippy::cast_possible_truncation)]
     fn write_fleece_to(&self, buf: &mut [u8], is_wide: bool) -> Option<NonZeroUsize> {
         // If this is small enough, store it as a short
-        if *self <= 2047 || *self >= -2048 {
+        if (-2048..=2047).contains(self) {
             #[allow(clippy::cast_possible_truncation)]
             return (*self as i16).write_fleece_to(buf, is_wide);
         }
@@ -46,18 +107,21 @@ impl Encodable for i64 {
     }
 
     fn fleece_size(&self) -> usize {
-        if *self <= 2047 || *self >= -2048 {
+        if (-2048..=2047).contains(self) {
             return 2;
         }
-        if *self >= 0 {
-            8 - self.trailing_zeros() as usize + 1
+        // The value bits, plus a sign bit
+        let bits = if *self >= 0 {
+            64 - self.leading_zeros() + 1
         } else {
-            8 - self.trailing_ones() as usize + 1
-        }
+            64 - self.leading_ones() + 1
+        };
+        bits.div_ceil(8) as usize + 1
     }
+
     #[allow(clippy::cast_possible_truncation)]
     fn to_sized_value(&self) -> Option<SizedValue> {
-        if *self <= 2047 || *self >= -2048 {
+        if (-2048..=2047).contains(self) {
             (*self as i16).to_sized_value()
         } else {
             None
@@ -88,8 +152,7 @@ impl Encodable for u64 {
         if *self <= 2047 {
             2
         } else {
-            let trailing_zeros = (self.trailing_zeros() + 7) / 8;
-            8 - trailing_zeros as usize + 1
+            (64 - self.leading_zeros()).div_ceil(8) as usize + 1
         }
     }
 
@@ -243,6 +306,9 @@ impl Encodable for u16 {
     }
 
     fn fleece_size(&self) -> usize {
+        if *self > 2047 {
+            return u64::from(*self).fleece_size();
+        }
         2
     }
 
@@ -270,6 +336,9 @@ impl Encodable for i16 {
     }
 
     fn fleece_size(&self) -> usize {
+        if *self > 2047 || *self < -2048 {
+            return i64::from(*self).fleece_size();
+        }
         2
     }
 
@@ -540,6 +609,29 @@ impl Encodable for str {
             _ => None,
         }
     }
+
+    fn to_fleece_str(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl super::private::Sealed for String {}
+impl Encodable for String {
+    fn write_fleece_to(&self, buf: &mut [u8], is_wide: bool) -> Option<NonZeroUsize> {
+        self.as_str().write_fleece_to(buf, is_wide)
+    }
+
+    fn fleece_size(&self) -> usize {
+        self.as_str().fleece_size()
+    }
+
+    fn to_sized_value(&self) -> Option<SizedValue> {
+        self.as_str().to_sized_value()
+    }
+
+    fn to_fleece_str(&self) -> Option<&str> {
+        Some(self)
+    }
 }
 
 impl<T> super::private::Sealed for Option<T> {}
@@ -566,6 +658,17 @@ where
             None => NullValue.to_sized_value(),
         }
     }
+
+    fn to_fleece_str(&self) -> Option<&str> {
+        self.as_ref().and_then(Encodable::to_fleece_str)
+    }
+
+    fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+        match self {
+            Some(value) => value.write_to(encoder),
+            None => NullValue.write_to(encoder),
+        }
+    }
 }
 
 impl super::private::Sealed for SizedValue {}
@@ -608,7 +711,6 @@ impl Encodable for SizedValue {
                 return None;
             }
             buf[0..2].copy_from_slice(&self.as_bytes()[..2]);
-            buf[0] &= 0xBF;
             unsafe { Some(NonZeroUsize::new_unchecked(2)) }
         }
     }
@@ -632,7 +734,12 @@ impl Encodable for SizedValue {
 
 // INVARIANTS:
 //   - `buf` MUST be large enough to encode `string`.
-fn write_valuestack_collection(buf: &mut [u8], tag: u8, len: usize, is_wide: bool) -> NonZeroUsize {
+pub(super) fn write_valuestack_collection(
+    buf: &mut [u8],
+    tag: u8,
+    len: usize,
+    is_wide: bool,
+) -> NonZeroUsize {
     #[allow(clippy::cast_possible_truncation)]
     let inline_size = len.min(array::VARINT_COUNT as usize) as u16;
 
@@ -715,3 +822,217 @@ impl Encodable for value_stack::Dict {
         None
     }
 }
+
+// Collections are written with `Encodable::write_to`, as they can't be written as a single value
+macro_rules! impl_collection_value {
+    () => {
+        fn write_fleece_to(&self, _buf: &mut [u8], _is_wide: bool) -> Option<NonZeroUsize> {
+            None
+        }
+
+        fn fleece_size(&self) -> usize {
+            0
+        }
+
+        fn to_sized_value(&self) -> Option<SizedValue> {
+            None
+        }
+    };
+}
+
+impl<T: Encodable> super::private::Sealed for Vec<T> {}
+impl<T: Encodable> Encodable for Vec<T> {
+    impl_collection_value!();
+
+    fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+        write_array(encoder, self)
+    }
+}
+
+// `[u8]` is encoded as Data, so arrays are implemented for fixed-size arrays rather than slices.
+// Without specialization, `[u8; N]` and `Vec<u8>` are still encoded as Arrays of ints, like any
+// other array; slice them (`&bytes[..]`) to encode Data.
+impl<T: Encodable, const N: usize> super::private::Sealed for [T; N] {}
+impl<T: Encodable, const N: usize> Encodable for [T; N] {
+    impl_collection_value!();
+
+    fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+        write_array(encoder, self)
+    }
+}
+
+impl<T: Encodable> super::private::Sealed for ArraySlice<'_, T> {}
+impl<T: Encodable> Encodable for ArraySlice<'_, T> {
+    impl_collection_value!();
+
+    fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+        write_array(encoder, self.0)
+    }
+}
+
+fn write_array<T: Encodable>(encoder: &mut Encoder, items: &[T]) -> Result<()> {
+    encoder.begin_array(items.len())?;
+    for item in items {
+        item.write_to(encoder)?;
+    }
+    encoder.end_array()
+}
+
+impl<K: AsRef<str>, V: Encodable> super::private::Sealed for BTreeMap<K, V> {}
+impl<K: AsRef<str>, V: Encodable> Encodable for BTreeMap<K, V> {
+    impl_collection_value!();
+
+    fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+        write_dict(encoder, self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: AsRef<str>, V: Encodable, S> super::private::Sealed for std::collections::HashMap<K, V, S> {}
+#[cfg(feature = "std")]
+impl<K: AsRef<str>, V: Encodable, S> Encodable for std::collections::HashMap<K, V, S> {
+    impl_collection_value!();
+
+    fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+        write_dict(encoder, self)
+    }
+}
+
+fn write_dict<'a, K, V>(
+    encoder: &mut Encoder,
+    entries: impl IntoIterator<Item = (&'a K, &'a V)>,
+) -> Result<()>
+where
+    K: AsRef<str> + 'a,
+    V: Encodable + 'a,
+{
+    encoder.begin_dict()?;
+    for (key, value) in entries {
+        encoder.write_key(key.as_ref())?;
+        value.write_to(encoder)?;
+    }
+    encoder.end_dict()
+}
+
+// Tuples are encoded as Arrays
+macro_rules! impl_tuple {
+    ($len:literal => $($name:ident $index:tt),+) => {
+        impl<$($name: Encodable),+> super::private::Sealed for ($($name,)+) {}
+        impl<$($name: Encodable),+> Encodable for ($($name,)+) {
+            impl_collection_value!();
+
+            fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+                encoder.begin_array($len)?;
+                $(self.$index.write_to(encoder)?;)+
+                encoder.end_array()
+            }
+        }
+
+        impl_ref!([$($name: Encodable),+] ($($name,)+));
+    };
+}
+
+impl_tuple!(1 => A 0);
+impl_tuple!(2 => A 0, B 1);
+impl_tuple!(3 => A 0, B 1, C 2);
+impl_tuple!(4 => A 0, B 1, C 2, D 3);
+impl_tuple!(5 => A 0, B 1, C 2, D 3, E 4);
+impl_tuple!(6 => A 0, B 1, C 2, D 3, E 4, F 5);
+impl_tuple!(7 => A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_tuple!(8 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+// Types outside this crate are written as a Dict of their fields
+impl<T: ToFleece> super::private::Sealed for T {}
+impl<T: ToFleece> Encodable for T {
+    impl_collection_value!();
+
+    fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+        encoder.begin_dict()?;
+        self.write_fields(encoder)?;
+        encoder.end_dict()
+    }
+}
+
+// Scalar Values are copied as they are, collections are written with `Encoder::write_fleece`
+impl super::private::Sealed for Value {}
+impl Encodable for Value {
+    fn write_fleece_to(&self, buf: &mut [u8], _is_wide: bool) -> Option<NonZeroUsize> {
+        let bytes = self._scalar_bytes()?;
+        buf.get_mut(..bytes.len())?.copy_from_slice(bytes);
+        NonZeroUsize::new(bytes.len())
+    }
+
+    fn fleece_size(&self) -> usize {
+        self._scalar_bytes().map_or(0, <[u8]>::len)
+    }
+
+    fn to_sized_value(&self) -> Option<SizedValue> {
+        match self._scalar_bytes()? {
+            &[byte0, byte1] => Some(SizedValue::new_narrow([byte0, byte1])),
+            _ => None,
+        }
+    }
+
+    fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+        encoder.write_fleece(self)
+    }
+}
+
+impl super::private::Sealed for Array {}
+impl Encodable for Array {
+    impl_collection_value!();
+
+    fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+        encoder.write_fleece(&self.value)
+    }
+}
+
+impl super::private::Sealed for Dict {}
+impl Encodable for Dict {
+    impl_collection_value!();
+
+    fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+        encoder.write_fleece(&self.array.value)
+    }
+}
+
+impl super::private::Sealed for MutableArray {}
+impl Encodable for MutableArray {
+    impl_collection_value!();
+
+    fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+        encoder.write_mutable_array(self)
+    }
+}
+
+impl super::private::Sealed for MutableDict {}
+impl Encodable for MutableDict {
+    impl_collection_value!();
+
+    fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+        encoder.write_mutable_dict(self)
+    }
+}
+
+impl<T: Encodable + ?Sized> super::private::Sealed for Alloced<T> {}
+impl<T: Encodable + ?Sized> Encodable for Alloced<T> {
+    fn write_fleece_to(&self, buf: &mut [u8], is_wide: bool) -> Option<NonZeroUsize> {
+        self.value().write_fleece_to(buf, is_wide)
+    }
+
+    fn fleece_size(&self) -> usize {
+        self.value().fleece_size()
+    }
+
+    fn to_sized_value(&self) -> Option<SizedValue> {
+        self.value().to_sized_value()
+    }
+
+    fn to_fleece_str(&self) -> Option<&str> {
+        self.value().to_fleece_str()
+    }
+
+    fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+        self.value().write_to(encoder)
+    }
+}
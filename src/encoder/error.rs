@@ -11,6 +11,10 @@ pub enum EncodeError {
     CollectionNotOpen,
     PointerTooLarge,
     MultiTopLevelCollection,
+    BufferFull,
+    ValueNotScalar,
+    OutputTooLarge,
+    DuplicateKey(&'static str),
 }
 
 impl fmt::Display for EncodeError {
@@ -30,6 +34,19 @@ impl fmt::Display for EncodeError {
             EncodeError::MultiTopLevelCollection => {
                 write!(f, "Multiple top level collections are not allowed")
             }
+            EncodeError::BufferFull => write!(f, "The output buffer is full"),
+            EncodeError::ValueNotScalar => {
+                write!(f, "Collections can't be written as a single value")
+            }
+            EncodeError::OutputTooLarge => {
+                write!(f, "The output is too large for its values to be pointed to")
+            }
+            EncodeError::DuplicateKey(key) => {
+                write!(
+                    f,
+                    "The key `{key}` is written more than once in the same Dict"
+                )
+            }
         }
     }
 }
@@ -1,33 +1,54 @@
-use core::borrow::Borrow;
 use core::cmp::Ordering;
 use core::num::NonZeroUsize;
 
+use crate::encoder::unique_strings::UniqueStrings;
 use crate::encoder::value_stack::{Collection, CollectionStack, DictKey};
+use crate::json;
+use crate::mutable::ValueSlot;
 use crate::scope::Scope;
 use crate::value::pointer::Pointer as ValuePointer;
 use crate::value::SizedValue;
 use crate::value::{pointer, ValueType};
-use crate::{value, SharedKeys, Value};
+use crate::{value, Array, Dict, MutableArray, MutableDict, SharedKeys, Value};
 use error::Result;
 
 use alloc::{sync::Arc, vec::Vec};
 
 mod encodable;
 mod error;
+mod slice;
+mod unique_strings;
 mod value_stack;
+#[cfg(feature = "std")]
+mod writer;
 
 use crate::alloced::AllocedValue;
 pub use error::EncodeError;
+pub use slice::SliceEncoder;
+#[cfg(feature = "std")]
+pub use writer::WriterEncoder;
 
 pub struct NullValue;
 pub struct UndefinedValue;
 
+/// Writes a slice of [`Encodable`] values as an Array. `[T]` can't be [`Encodable`] itself, as
+/// `[u8]` is written as Data, so slices are wrapped in this instead:
+/// `encoder.write_value(ArraySlice(&items[1..]))`.
+///
+/// `[u8; N]` and `Vec<u8>` are written as Arrays of ints, like other arrays and `Vec`s. Only
+/// `&[u8]` is written as Data.
+pub struct ArraySlice<'a, T>(pub &'a [T]);
+
 mod private {
     pub trait Sealed {}
 }
 
 // Implementations are in the `encodable` module
 /// This trait is required for a value to be written to the `Encoder`.
+///
+/// It is sealed, as the bytes written by [`write_fleece_to`](Self::write_fleece_to) are read
+/// without validation. Types outside this crate implement [`ToFleece`](crate::ToFleece) instead,
+/// usually with `#[derive(ToFleece)]`, and are written as a Dict of their fields.
 pub trait Encodable: private::Sealed {
     /// Write self to the given writer, encoded as Fleece. Return [`None`] if any write operations fail.
     /// Return [`Some`] with the number of bytes written if the value was written successfully.
@@ -40,6 +61,19 @@ pub trait Encodable: private::Sealed {
     /// return [`None`].
     /// Use [`SizedValue::from_narrow`] to construct the value.
     fn to_sized_value(&self) -> Option<SizedValue>;
+    /// Write this value to `encoder`. Scalars are written as a single value, using the methods
+    /// above. Collections open an Array or Dict, write each of their items, and close it again,
+    /// so they can't be written as a single value.
+    /// ## Errors
+    /// See [`Encoder::write_value`].
+    fn write_to(&self, encoder: &mut Encoder) -> Result<()> {
+        encoder._write_scalar(self)
+    }
+    /// If this value is a string, return it, so the `Encoder` can de-duplicate it. See
+    /// [`Encoder::set_unique_strings`].
+    fn to_fleece_str(&self) -> Option<&str> {
+        None
+    }
 }
 
 #[derive(Default)]
@@ -48,20 +82,28 @@ pub struct Encoder {
     shared_keys: Option<SharedKeys>,
     collection_stack: CollectionStack,
     top_collection_closed: bool,
+    // The strings which have been written, so repeated strings can be written as pointers.
+    unique_strings: UniqueStrings,
+    // Existing Fleece data which the output will be appended to. See `Encoder::new_with_base`.
+    base: Option<Arc<[u8]>>,
+    // The number of bytes which have been taken from the front of `out`. See `WriterEncoder`.
+    flushed: usize,
 }
 
 impl Encoder {
     #[must_use]
-    pub fn new() -> Encoder {
+    pub fn new() -> Self {
         Self::default()
     }
 
     /// A convenience function which is the same as [`Encoder::finish`], but returns an
-    /// [`AllocedValue`].
+    /// [`AllocedValue`]. If this Encoder was created with [`Encoder::new_with_base`], the value
+    /// is read from the base followed by the delta.
     #[allow(clippy::missing_panics_doc)]
     #[must_use]
-    pub fn finish_value(self) -> AllocedValue {
-        let vec = self.finish();
+    pub fn finish_value(mut self) -> AllocedValue {
+        self._end();
+        let vec = self._take_document();
         #[cfg(not(debug_assertions))]
         unsafe {
             Value::clone_from_bytes_unchecked(&vec)
@@ -74,12 +116,33 @@ impl Encoder {
     pub fn new_to_vec(out: Vec<u8>) -> Self {
         Self {
             out,
-            shared_keys: None,
             collection_stack: CollectionStack::new(),
-            top_collection_closed: false,
+            ..Self::default()
         }
     }
 
+    /// Create an Encoder which appends to existing Fleece data, rather than writing a new
+    /// document. The output of [`Encoder::finish`] is only the delta, which must be concatenated
+    /// to the end of `base` to be read. Values written to this Encoder may point back into
+    /// `base`.
+    ///
+    /// When a [`MutableDict`] or [`MutableArray`] is written as the top-level collection, it is
+    /// compared against the root of `base`, and any unchanged values or sub-trees are written as
+    /// pointers into `base`, rather than being copied.
+    ///
+    /// If `base` was encoded with [`SharedKeys`], the same [`SharedKeys`] should be given to this
+    /// Encoder with [`Encoder::set_shared_keys`].
+    /// ## Errors
+    /// If `base` is not valid Fleece data.
+    pub fn new_with_base(base: impl Into<Arc<[u8]>>) -> crate::Result<Self> {
+        let base = base.into();
+        Value::from_bytes(&base)?;
+        Ok(Self {
+            base: Some(base),
+            ..Self::default()
+        })
+    }
+
     /// Write the key string to this `Encoder`.
     /// ## Errors
     /// - If there is not an open Dict, or the top-level open collection is an Array.
@@ -97,25 +160,39 @@ impl Encoder {
 
     /// Write an [`Encodable`] type to the encoder. The parameter may be any borrowed form of an Encodable type.
     /// `R: Borrow<T>` enables us to pass something like a Rc<T> directly to this function
+    ///
+    /// Collections, such as a `Vec`, `BTreeMap`, tuple, [`Dict`] or [`MutableArray`], are written
+    /// as a whole, by opening an Array or Dict, writing each item, and closing it again.
+    ///
+    /// If there is no open collection, the value is written as the root of the document, in
+    /// place of a top-level collection.
     /// ## Errors
-    /// - If there is not an open collection (Array/Dict).
     /// - If the open collection is a Dict, and it is waiting for a key.
+    /// - If there is no open collection, and a root value has already been written.
     /// - I/O Errors related to writing to this Encoder's writer.
+    #[allow(clippy::needless_pass_by_value)]
     pub fn write_value<T>(&mut self, value: T) -> Result<()>
     where
         T: Encodable,
     {
+        value.write_to(self)
+    }
+
+    /// Write a value which isn't a collection to the open collection, or as the root.
+    fn _write_scalar<T: Encodable + ?Sized>(&mut self, value: &T) -> Result<()> {
         if self.collection_stack.empty() {
-            return Err(EncodeError::CollectionNotOpen);
+            return self._write_root_value(value);
         }
 
-        let value = value.borrow();
         if let Some(val) = value.to_sized_value() {
             // If the value can fit in a fixed-width Value, just push it to the current collection
             self._push(val)
         } else {
             // Otherwise, write it to output and push a pointer to it onto the current collection
-            let offset = self._write(value, false, false);
+            let offset = match value.to_fleece_str() {
+                Some(string) => self._write_string(string),
+                None => self._write(value, false, false),
+            };
             let Some(pointer) = SizedValue::new_pointer(offset) else {
                 return Err(EncodeError::PointerTooLarge);
             };
@@ -126,24 +203,21 @@ impl Encoder {
     /// Write a Fleece `Value` to the Encoder. If the value is an `Array` or `Dict`, all the
     /// elements will be written as well. This function cannot validate Fleece `Array` or `Dict`,
     /// so ensure they are valid before passing them to this function.
+    ///
+    /// If there is no open collection, the value is written as the root of the document.
     /// ## Errors
-    /// - If there is not an open collection (Array/Dict).
     /// - If the open collection is a Dict, and it is waiting for a key.
+    /// - If there is no open collection, and a root value has already been written.
     /// - If the value is invalid Fleece.
     /// - I/O errors related to writing to this Encoder's writer.
     pub fn write_fleece(&mut self, value: &Value) -> Result<()> {
-        // If the encoder has no open collections and the value is not a collection, return None
-        if self.collection_stack.empty()
-            && value.value_type() != ValueType::Dict
-            && value.value_type() != ValueType::Array
-        {
-            return Err(EncodeError::CollectionNotOpen);
-        }
         match value.value_type() {
-            ValueType::True => self._push(SizedValue::new_narrow(value::constants::TRUE)),
-            ValueType::False => self._push(SizedValue::new_narrow(value::constants::FALSE)),
-            ValueType::Null => self._push(SizedValue::new_narrow(value::constants::NULL)),
-            ValueType::Undefined => self._push(SizedValue::new_narrow(value::constants::UNDEFINED)),
+            ValueType::True => self.write_value(SizedValue::new_narrow(value::constants::TRUE)),
+            ValueType::False => self.write_value(SizedValue::new_narrow(value::constants::FALSE)),
+            ValueType::Null => self.write_value(SizedValue::new_narrow(value::constants::NULL)),
+            ValueType::Undefined => {
+                self.write_value(SizedValue::new_narrow(value::constants::UNDEFINED))
+            }
             ValueType::Short => self.write_value(value.to_short()),
             ValueType::UnsignedInt => self.write_value(value.to_unsigned_int()),
             ValueType::Int => self.write_value(value.to_int()),
@@ -178,10 +252,80 @@ impl Encoder {
         }
     }
 
+    /// Write a [`MutableDict`] to the Encoder, including all of its nested collections.
+    /// Keys are written with [`Encoder::write_key`], so they are encoded using this Encoder's
+    /// [`SharedKeys`] (if any), regardless of the [`SharedKeys`] the dict was created with.
+    /// ## Errors
+    /// - If the top open collection is a Dict, and it is waiting for a key.
+    /// - If the top-level collection has already been closed.
+    pub fn write_mutable_dict(&mut self, dict: &MutableDict) -> Result<()> {
+        if let Some(base) = self._top_level_base() {
+            return self._write_dict_with_base(dict, Self::_base_root(&base));
+        }
+        self._write_mutable_dict(dict)
+    }
+
+    /// Write a [`MutableArray`] to the Encoder, including all of its nested collections.
+    /// ## Errors
+    /// - If the top open collection is a Dict, and it is waiting for a key.
+    /// - If the top-level collection has already been closed.
+    pub fn write_mutable_array(&mut self, array: &MutableArray) -> Result<()> {
+        if let Some(base) = self._top_level_base() {
+            return self._write_array_with_base(array, Self::_base_root(&base));
+        }
+        self._write_mutable_array(array)
+    }
+
+    /// Parse a single JSON value and write it to the Encoder. The JSON is written as it is
+    /// parsed, without building an intermediate tree. Numbers without a fraction or exponent are
+    /// written as integers, other numbers are written as doubles.
+    ///
+    /// If the top open collection is a Dict, a key must have been written first.
+    ///
+    /// If an error is returned, the values parsed before the error have already been written, and
+    /// collections opened by the JSON are left open. The output is then incomplete, so the
+    /// Encoder should be [`reset`](Encoder::reset) before it is used again.
+    /// ## Errors
+    /// - If the JSON is invalid.
+    /// - Errors from writing the values, as in [`Encoder::write_value`].
+    pub fn write_json(&mut self, json: &str) -> crate::Result<()> {
+        json::parser::Parser::new(json).parse_into(self)
+    }
+
+    /// The same as [`Encoder::write_json`], but parses JSON5, which also allows comments,
+    /// trailing commas, unquoted keys, single-quoted strings, hexadecimal numbers, `Infinity` and
+    /// `NaN`. See <https://spec.json5.org>.
+    ///
+    /// As with [`Encoder::write_json`], the Encoder is left partly written if an error is returned.
+    /// ## Errors
+    /// - If the JSON5 is invalid. The error gives the line and column of the problem.
+    /// - Errors from writing the values, as in [`Encoder::write_value`].
+    pub fn write_json5(&mut self, json5: &str) -> crate::Result<()> {
+        json::parser::Parser::new_json5(json5).parse_into(self)
+    }
+
     pub fn set_shared_keys(&mut self, shared_keys: SharedKeys) {
         self.shared_keys = Some(shared_keys);
     }
 
+    /// Set whether repeated strings (including dict keys) are written only once, with later
+    /// copies written as pointers to the first. This is disabled by default, so the output is
+    /// the same as from earlier versions unless it is enabled.
+    ///
+    /// De-duplication makes the output smaller when strings are repeated, at the cost of keeping a
+    /// copy of each string in memory until the Encoder is finished. At most 4096 strings are
+    /// kept; once the table is full, new strings are written in full.
+    pub fn set_unique_strings(&mut self, enabled: bool) {
+        self.unique_strings.enabled = enabled;
+    }
+
+    /// Set the length (in bytes) of the longest string which will be de-duplicated, when
+    /// [`Encoder::set_unique_strings`] is enabled. Longer strings are always written in full.
+    /// The default is 100 bytes.
+    pub fn set_max_unique_string_len(&mut self, max_len: usize) {
+        self.unique_strings.max_len = max_len;
+    }
+
     /// # Errors
     /// - If the top-level collection is a Dict and is waiting for a key.
     /// - If the top-level collection has already been closed.
@@ -209,6 +353,7 @@ impl Encoder {
         for v in &array.values {
             self._write(v, is_wide, false);
         }
+        self.collection_stack.recycle(Collection::Array(array));
 
         self._finished_collection(offset)?;
 
@@ -273,22 +418,48 @@ impl Encoder {
             };
             self._write(&elem.val, is_wide, false);
         }
+        self.collection_stack.recycle(Collection::Dict(dict));
 
-        #[allow(clippy::cast_possible_truncation)]
         self._finished_collection(offset)?;
 
         Ok(())
     }
 
+    /// Close any open collections and return the encoded data. If this Encoder was created
+    /// with [`Encoder::new_with_base`], this is only the delta, not including the base.
     pub fn finish(mut self) -> Vec<u8> {
         self._end();
         self.out
     }
 
+    /// Close any open collections, append the encoded data to `out`, and [`reset`](Encoder::reset)
+    /// this Encoder so it can encode another document. Unlike [`Encoder::finish`], the Encoder's
+    /// buffers are kept, so encoding many small documents doesn't allocate for each one.
+    ///
+    /// If this Encoder was created with [`Encoder::new_with_base`], only the delta is appended.
+    pub fn finish_into(&mut self, out: &mut Vec<u8>) {
+        self._end();
+        out.extend_from_slice(&self.out);
+        self.reset();
+    }
+
+    /// Discard everything written to this Encoder, so it can encode a new document. The capacity
+    /// of its buffers is kept, as are its settings, such as its [`SharedKeys`] and base.
+    pub fn reset(&mut self) {
+        self.out.clear();
+        self.collection_stack.clear();
+        self.top_collection_closed = false;
+        self.unique_strings.clear();
+        self.flushed = 0;
+    }
+
+    /// Close any open collections and return the encoded data wrapped in a [`Scope`], which
+    /// also retains this Encoder's [`SharedKeys`]. If this Encoder was created with
+    /// [`Encoder::new_with_base`], the scope contains the base followed by the delta.
     pub fn finish_scoped(mut self) -> Arc<Scope> {
         self._end();
-        let shared_keys = self.shared_keys.map(Arc::new);
-        Scope::new(self.out, shared_keys)
+        let shared_keys = self.shared_keys.take().map(Arc::new);
+        Scope::new(self._take_document(), shared_keys)
     }
 
     /// This *MUST* follow the implementation at [`Value::dict_key_cmp`]
@@ -328,19 +499,16 @@ impl Encoder {
         is_wide: bool,
         is_collection: bool,
     ) -> u32 {
-        let offset = self.out.len();
+        let position = self._position();
+        let start = self.out.len();
         let size_required = if is_wide && !is_collection {
             value.fleece_size().max(4)
         } else {
             value.fleece_size()
         };
         self.out.extend(core::iter::repeat(0).take(size_required));
-        let written =
-            value.write_fleece_to(&mut self.out[offset..(offset + size_required)], is_wide);
-        assert_eq!(
-            written,
-            Some(unsafe { NonZeroUsize::new_unchecked(size_required) })
-        );
+        let written = value.write_fleece_to(&mut self.out[start..(start + size_required)], is_wide);
+        assert_eq!(written.map(NonZeroUsize::get), Some(size_required));
         // Pad to even
         if self.out.len() % 2 != 0 {
             self.out.push(0);
@@ -348,10 +516,249 @@ impl Encoder {
 
         #[allow(clippy::cast_possible_truncation)]
         {
-            offset as u32
+            position as u32
+        }
+    }
+
+    /// Write a string to the output buffer, or if the same string has already been written, return
+    /// the offset of the earlier copy.
+    fn _write_string(&mut self, string: &str) -> u32 {
+        if let Some(offset) = self.unique_strings.get(string) {
+            return offset;
+        }
+        let offset = self._write(string, false, false);
+        self.unique_strings.insert(string, offset);
+        offset
+    }
+
+    /// Write a scalar value as the root of the document.
+    fn _write_root_value<T: Encodable + ?Sized>(&mut self, value: &T) -> Result<()> {
+        if self.top_collection_closed {
+            return Err(EncodeError::MultiTopLevelCollection);
+        }
+        let offset = if let Some(val) = value.to_sized_value() {
+            if self._position() == 0 {
+                // A value which fits in 2 bytes is the whole document, rather than a root pointer
+                self._write(&val, false, false);
+                self.top_collection_closed = true;
+                return Ok(());
+            }
+            self._write(&val, false, false)
+        } else if let Some(string) = value.to_fleece_str() {
+            self._write_string(string)
+        } else {
+            self._write(value, false, false)
+        };
+        // With no open collections, this writes the root pointer to the value
+        self._finished_collection(offset)
+    }
+
+    #[inline]
+    fn _base_len(&self) -> usize {
+        self.base.as_ref().map_or(0, |base| base.len())
+    }
+
+    /// The position at which the next value will be written, measured from the start of the
+    /// base (if there is one).
+    #[inline]
+    fn _position(&self) -> usize {
+        self._base_len() + self.flushed + self.out.len()
+    }
+
+    /// The complete document, which is the base (if any) followed by the output.
+    fn _take_document(&mut self) -> Vec<u8> {
+        let out = core::mem::take(&mut self.out);
+        match self.base.take() {
+            Some(base) => [&base[..], &out[..]].concat(),
+            None => out,
         }
     }
 
+    /// The base, if there is one and no collections have been opened yet.
+    fn _top_level_base(&self) -> Option<Arc<[u8]>> {
+        if self.collection_stack.empty() && !self.top_collection_closed {
+            self.base.clone()
+        } else {
+            None
+        }
+    }
+
+    fn _base_root(base: &[u8]) -> &Value {
+        // The base was validated in `Encoder::new_with_base`.
+        unsafe { Value::from_bytes_unchecked(base) }
+    }
+
+    /// Write a pointer to a value which lies inside the base.
+    fn _write_base_pointer(&mut self, base_value: &Value) -> Result<()> {
+        let Some(base) = &self.base else {
+            unreachable!()
+        };
+        let offset = base_value.bytes.as_ptr() as usize - base.as_ptr() as usize;
+        #[allow(clippy::cast_possible_truncation)]
+        let offset = offset as u32;
+        if self.collection_stack.empty() {
+            return self._finished_collection(offset);
+        }
+        let Some(pointer) = SizedValue::new_pointer(offset) else {
+            return Err(EncodeError::PointerTooLarge);
+        };
+        self._push(pointer)
+    }
+
+    /// Write a slot, pointing to `base_value` instead if the slot is unchanged from it. If both
+    /// are collections of the same type, they are compared element-by-element.
+    fn _write_slot_with_base(
+        &mut self,
+        slot: &ValueSlot,
+        base_value: Option<&Value>,
+    ) -> Result<()> {
+        let Some(base_value) = base_value else {
+            return self._write_slot(slot);
+        };
+        match slot {
+            ValueSlot::Inline(_) | ValueSlot::Pointer(_) => {
+                let Some(value) = slot.value() else {
+                    unreachable!()
+                };
+                // Values which fit in a collection slot are cheaper to write than a pointer
+                if value.value_type() != ValueType::Array
+                    && value.value_type() != ValueType::Dict
+                    && value.required_size() > 2
+                    && Self::_scalar_eq(value, base_value)
+                {
+                    self._write_base_pointer(base_value)
+                } else {
+                    self.write_fleece(value)
+                }
+            }
+            ValueSlot::MutableArray(array) => self._write_array_with_base(array, base_value),
+            ValueSlot::MutableDict(dict) => self._write_dict_with_base(dict, base_value),
+        }
+    }
+
+    fn _write_dict_with_base(&mut self, dict: &MutableDict, base_value: &Value) -> Result<()> {
+        let Some(base_dict) = base_value.as_dict() else {
+            return self._write_mutable_dict(dict);
+        };
+        if self._dict_matches(dict, base_dict) {
+            return self._write_base_pointer(base_value);
+        }
+        self.begin_dict()?;
+        for (key, value) in dict {
+            let base_value = self._base_dict_get(base_dict, key);
+            self.write_key(key)?;
+            self._write_slot_with_base(value.slot(), base_value)?;
+        }
+        self.end_dict()
+    }
+
+    fn _write_array_with_base(&mut self, array: &MutableArray, base_value: &Value) -> Result<()> {
+        let Some(base_array) = base_value.as_array() else {
+            return self._write_mutable_array(array);
+        };
+        if self._array_matches(array, base_array) {
+            return self._write_base_pointer(base_value);
+        }
+        self.begin_array(array.len())?;
+        for (i, value) in array.iter().enumerate() {
+            let base_value = if i < base_array.len() {
+                base_array.get(i)
+            } else {
+                None
+            };
+            self._write_slot_with_base(value.slot(), base_value)?;
+        }
+        self.end_array()
+    }
+
+    fn _base_dict_get<'a>(&self, base_dict: &'a Dict, key: &str) -> Option<&'a Value> {
+        match &self.shared_keys {
+            Some(shared_keys) => base_dict.get_with_shared_keys(key, shared_keys),
+            None => base_dict.get(key),
+        }
+    }
+
+    fn _slot_matches(&self, slot: &ValueSlot, base_value: &Value) -> bool {
+        match slot {
+            ValueSlot::Inline(_) | ValueSlot::Pointer(_) => slot
+                .value()
+                .is_some_and(|value| Self::_scalar_eq(value, base_value)),
+            ValueSlot::MutableArray(array) => base_value
+                .as_array()
+                .is_some_and(|base_array| self._array_matches(array, base_array)),
+            ValueSlot::MutableDict(dict) => base_value
+                .as_dict()
+                .is_some_and(|base_dict| self._dict_matches(dict, base_dict)),
+        }
+    }
+
+    fn _dict_matches(&self, dict: &MutableDict, base_dict: &Dict) -> bool {
+        dict.len() == base_dict.len()
+            && dict.iter().all(|(key, value)| {
+                self._base_dict_get(base_dict, key)
+                    .is_some_and(|base_value| self._slot_matches(value.slot(), base_value))
+            })
+    }
+
+    fn _array_matches(&self, array: &MutableArray, base_array: &Array) -> bool {
+        array.len() == base_array.len()
+            && array
+                .iter()
+                .zip(base_array)
+                .all(|(value, base_value)| self._slot_matches(value.slot(), base_value))
+    }
+
+    /// Whether two non-collection values are the same type and value.
+    fn _scalar_eq(value: &Value, other: &Value) -> bool {
+        match (value.value_type(), other.value_type()) {
+            (ValueType::Null, ValueType::Null)
+            | (ValueType::Undefined, ValueType::Undefined)
+            | (ValueType::True, ValueType::True)
+            | (ValueType::False, ValueType::False) => true,
+            (ValueType::Short | ValueType::Int, ValueType::Short | ValueType::Int)
+            | (ValueType::UnsignedInt, ValueType::UnsignedInt) => value.to_int() == other.to_int(),
+            (
+                ValueType::Float | ValueType::Double32 | ValueType::Double64,
+                ValueType::Float | ValueType::Double32 | ValueType::Double64,
+            ) => value.to_double().to_bits() == other.to_double().to_bits(),
+            (ValueType::String, ValueType::String) | (ValueType::Data, ValueType::Data) => {
+                value.to_data() == other.to_data()
+            }
+            _ => false,
+        }
+    }
+
+    /// Write the contents of a [`MutableArray`] or [`MutableDict`] slot.
+    fn _write_slot(&mut self, slot: &ValueSlot) -> Result<()> {
+        match slot {
+            ValueSlot::Inline(_) | ValueSlot::Pointer(_) => {
+                let Some(value) = slot.value() else {
+                    unreachable!()
+                };
+                self.write_fleece(value)
+            }
+            ValueSlot::MutableArray(array) => self._write_mutable_array(array),
+            ValueSlot::MutableDict(dict) => self._write_mutable_dict(dict),
+        }
+    }
+
+    fn _write_mutable_dict(&mut self, dict: &MutableDict) -> Result<()> {
+        self.begin_dict()?;
+        for (key, value) in dict {
+            self.write_key(key)?;
+            self._write_slot(value.slot())?;
+        }
+        self.end_dict()
+    }
+
+    fn _write_mutable_array(&mut self, array: &MutableArray) -> Result<()> {
+        self.begin_array(array.len())?;
+        for value in array {
+            self._write_slot(value.slot())?;
+        }
+        self.end_array()
+    }
+
     fn _write_key_inline(&mut self, val: SizedValue) -> Result<()> {
         let Some(Collection::Dict(dict)) = self.collection_stack.top_mut() else {
             return Err(EncodeError::DictNotOpen);
@@ -379,7 +786,7 @@ impl Encoder {
 
     fn _write_key_pointer(&mut self, key: &str) -> Result<()> {
         // If we don't have shared keys, write the key to the output buffer and add a pointer to it in the Dict
-        let offset = self._write(key, false, false);
+        let offset = self._write_string(key);
         let Some(Collection::Dict(dict)) = self.collection_stack.top_mut() else {
             return Err(EncodeError::DictNotOpen);
         };
@@ -419,32 +826,35 @@ impl Encoder {
 
     #[allow(clippy::cast_possible_truncation)]
     fn _actual_pointer_offset(&self, offset_from_start: u32) -> u32 {
-        self.out.len() as u32 - offset_from_start
+        self._position() as u32 - offset_from_start
     }
 
     fn _array_should_be_wide(&self, array: &value_stack::Array) -> bool {
+        let mut len = self._position();
         for v in &array.values {
             if v.value_type() == ValueType::Pointer
-                && v.actual_pointer_offset(self.out.len()) > u32::from(pointer::MAX_NARROW)
+                && v.actual_pointer_offset(len) > u32::from(pointer::MAX_NARROW)
             {
                 return true;
             }
+            len += 2;
         }
         false
     }
 
     // Only Pointer might require more than 2 bytes, if any do then the whole dict needs to be wide
     fn _dict_should_be_wide(&self, dict: &value_stack::Dict) -> bool {
-        let mut len = self.out.len();
+        let mut len = self._position();
         for elem in &dict.values {
+            // Keys may point to strings written long before the dict, if they are de-duplicated
             if let DictKey::Pointer(_, offset) = &elem.key {
-                let offset = len - *offset as usize;
-                if len - offset > pointer::MAX_NARROW as usize {
+                if len - *offset as usize > pointer::MAX_NARROW as usize {
                     return true;
                 }
             }
+            len += 2;
             if elem.val.value_type() == ValueType::Pointer
-                && elem.val.actual_pointer_offset(self.out.len()) > u32::from(pointer::MAX_NARROW)
+                && elem.val.actual_pointer_offset(len) > u32::from(pointer::MAX_NARROW)
             {
                 return true;
             }
@@ -455,7 +865,7 @@ impl Encoder {
 
     fn _fix_array_pointers(&self, array: &mut value_stack::Array, is_wide: bool) {
         #[allow(clippy::cast_possible_truncation)]
-        let mut len = self.out.len() as u32;
+        let mut len = self._position() as u32;
         for elem in &mut array.values {
             if elem.value_type() == ValueType::Pointer {
                 *elem = Encoder::_fix_pointer(*elem, len);
@@ -466,7 +876,7 @@ impl Encoder {
 
     fn _fix_dict_pointers(&self, dict: &mut value_stack::Dict, is_wide: bool) {
         #[allow(clippy::cast_possible_truncation)]
-        let mut len = self.out.len() as u32;
+        let mut len = self._position() as u32;
         for elem in &mut dict.values {
             if let DictKey::Pointer(_, offset) = &mut elem.key {
                 *offset = len - *offset;
@@ -486,37 +896,30 @@ impl Encoder {
     }
 
     fn _finished_collection(&mut self, offset_from_start: u32) -> Result<()> {
-        if let Some(collection) = self.collection_stack.top_mut() {
+        if !self.collection_stack.empty() {
             let Some(pointer) = SizedValue::new_pointer(offset_from_start) else {
                 return Err(EncodeError::PointerTooLarge);
             };
-            match collection {
-                Collection::Dict(dict) => {
-                    dict.push_value(pointer);
-                }
-                Collection::Array(array) => {
-                    array.push(pointer);
-                }
-            }
+            // Fails if the enclosing collection is a dict which isn't waiting for a value
+            return self._push(pointer);
+        }
+        // The last collection is finished, write the root value at the end.
+        // This root value points to the outermost collection.
+        let offset = self._actual_pointer_offset(offset_from_start);
+        #[allow(clippy::cast_possible_truncation)]
+        let root = if offset <= u32::from(pointer::MAX_NARROW) {
+            SizedValue::new_pointer(offset).unwrap()
         } else {
-            // The last collection is finished, write the root value at the end.
-            // This root value points to the outermost collection.
-            let offset = self._actual_pointer_offset(offset_from_start);
-            #[allow(clippy::cast_possible_truncation)]
-            let root = if offset <= u32::from(pointer::MAX_NARROW) {
-                SizedValue::new_pointer(offset).unwrap()
-            } else {
-                // The root value must be 2 bytes, so if the pointer to the top-level collection
-                // is 4 bytes wide, we need to write that, then write another 2-byte pointer to that
-                let Some(inner_root) = SizedValue::new_pointer(offset) else {
-                    return Err(EncodeError::PointerTooLarge);
-                };
-                self._write(&inner_root, true, false);
-                SizedValue::new_pointer(4).unwrap()
+            // The root value must be 2 bytes, so if the pointer to the top-level collection
+            // is 4 bytes wide, we need to write that, then write another 2-byte pointer to that
+            let Some(inner_root) = SizedValue::new_pointer(offset) else {
+                return Err(EncodeError::PointerTooLarge);
             };
-            self._write(&root, false, false);
-            self.top_collection_closed = true;
-        }
+            self._write(&inner_root, true, false);
+            SizedValue::new_pointer(4).unwrap()
+        };
+        self._write(&root, false, false);
+        self.top_collection_closed = true;
         Ok(())
     }
 }
@@ -0,0 +1,369 @@
+use core::cmp::Ordering;
+
+use crate::encoder::encodable::write_valuestack_collection;
+use crate::encoder::{Encodable, EncodeError, Result};
+use crate::value::{array, pointer, tag, varint, SizedValue, Value, ValueType};
+
+/// The size of each entry on the stack of open collections.
+const RECORD_SIZE: usize = 4;
+/// The size of a key-value pair of records on the stack.
+const PAIR_SIZE: usize = RECORD_SIZE * 2;
+/// Set in a frame record if the collection is a dict.
+const FRAME_IS_DICT: u32 = 0x8000_0000;
+
+/// An encoder which writes Fleece into a caller-provided buffer, and never allocates.
+///
+/// The encoded values are written to the start of the buffer. The items of open collections are
+/// kept on a stack at the end of the buffer, four bytes per item (or per key and value in a
+/// dict), until the collection is ended and they are written out. So the buffer must have room
+/// for the encoded data, plus the items of all open collections. If it runs out of room, an
+/// [`EncodeError::BufferFull`] is returned.
+///
+/// The API is the same as [`Encoder`](super::Encoder), except that [`SharedKeys`](crate::SharedKeys)
+/// are not supported, as they need to allocate.
+pub struct SliceEncoder<'a> {
+    buf: &'a mut [u8],
+    // The length of the encoded data at the start of `buf`.
+    len: usize,
+    // The start of the stack of open collections at the end of `buf`. The stack grows downwards.
+    stack_start: usize,
+    // The position of the record for the innermost open collection. The items of the collection
+    // are the records below it.
+    frame: Option<usize>,
+    top_collection_closed: bool,
+}
+
+impl<'a> SliceEncoder<'a> {
+    /// Create an encoder which writes into `buf`. Any existing contents of `buf` are overwritten.
+    #[must_use]
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        let stack_start = buf.len();
+        Self {
+            buf,
+            len: 0,
+            stack_start,
+            frame: None,
+            top_collection_closed: false,
+        }
+    }
+
+    /// The number of bytes of `buf` which have been used, including the stack of open
+    /// collections.
+    #[must_use]
+    pub fn used(&self) -> usize {
+        self.len + self.buf.len() - self.stack_start
+    }
+
+    /// Write a key to the open dict. See [`Encoder::write_key`](super::Encoder::write_key).
+    /// ## Errors
+    /// - If there is not an open Dict, or the top-level open collection is an Array.
+    /// - If the last item pushed to the Dict was a key (it is waiting for a value).
+    /// - If the buffer is full.
+    pub fn write_key(&mut self, key: &str) -> Result<()> {
+        match self._frame() {
+            Some((_, true, count)) if count % 2 == 1 => Err(EncodeError::DictWaitingForValue),
+            Some((_, true, _)) => self._push_value(&key),
+            _ => Err(EncodeError::DictNotOpen),
+        }
+    }
+
+    /// Write a value to the open collection, or as the root value if there is no open collection.
+    /// See [`Encoder::write_value`](super::Encoder::write_value). Unlike the
+    /// [`Encoder`](super::Encoder), collections such as a `Vec` can't be written as a value; use
+    /// [`SliceEncoder::begin_array`] and [`SliceEncoder::begin_dict`] instead.
+    /// ## Errors
+    /// - If the open collection is a Dict, and it is waiting for a key.
+    /// - If there is no open collection, and a root value has already been written.
+    /// - If the value is a collection.
+    /// - If the buffer is full.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn write_value<T: Encodable>(&mut self, value: T) -> Result<()> {
+        match self._frame() {
+            None => self._write_root_value(&value),
+            Some((_, true, count)) if count % 2 == 0 => Err(EncodeError::DictWaitingForKey),
+            Some(_) => self._push_value(&value),
+        }
+    }
+
+    /// ## Errors
+    /// - If the top-level collection is a Dict and is waiting for a key.
+    /// - If the top-level collection has already been closed.
+    /// - If the buffer is full.
+    pub fn begin_array(&mut self) -> Result<()> {
+        self._begin(false)
+    }
+
+    /// ## Errors
+    /// - If there is no open collection.
+    /// - If the top open collection is not an Array.
+    /// - If the buffer is full.
+    pub fn end_array(&mut self) -> Result<()> {
+        match self._frame() {
+            Some((_, false, _)) => self._end(),
+            _ => Err(EncodeError::ArrayNotOpen),
+        }
+    }
+
+    /// ## Errors
+    /// - If the top-level collection is a Dict and is waiting for a key.
+    /// - If the top-level collection has already been closed.
+    /// - If the buffer is full.
+    pub fn begin_dict(&mut self) -> Result<()> {
+        self._begin(true)
+    }
+
+    /// ## Errors
+    /// - If the top open collection is not a Dict.
+    /// - If the open Dict has a key with no value.
+    /// - If the buffer is full.
+    pub fn end_dict(&mut self) -> Result<()> {
+        match self._frame() {
+            Some((_, true, count)) if count % 2 == 1 => Err(EncodeError::DictWaitingForValue),
+            Some((_, true, _)) => self._end(),
+            _ => Err(EncodeError::DictNotOpen),
+        }
+    }
+
+    /// Close any open collections, discarding any dangling keys, and return the encoded data,
+    /// which is at the start of the buffer.
+    /// ## Errors
+    /// If the buffer is too full to close the open collections.
+    pub fn finish(mut self) -> Result<&'a [u8]> {
+        while let Some((_, is_dict, count)) = self._frame() {
+            if is_dict && count % 2 == 1 {
+                self.stack_start += RECORD_SIZE;
+            }
+            self._end()?;
+        }
+        let buf = self.buf;
+        Ok(&buf[..self.len])
+    }
+}
+
+impl SliceEncoder<'_> {
+    /// The position of the innermost open collection's record, whether it is a dict, and the
+    /// number of records pushed to it.
+    fn _frame(&self) -> Option<(usize, bool, usize)> {
+        let frame = self.frame?;
+        let is_dict = self._record(frame) & FRAME_IS_DICT != 0;
+        Some((frame, is_dict, (frame - self.stack_start) / RECORD_SIZE))
+    }
+
+    fn _record(&self, position: usize) -> u32 {
+        let mut bytes = [0u8; RECORD_SIZE];
+        bytes.copy_from_slice(&self.buf[position..position + RECORD_SIZE]);
+        u32::from_ne_bytes(bytes)
+    }
+
+    fn _push_record(&mut self, record: [u8; RECORD_SIZE]) -> Result<()> {
+        if self.stack_start < self.len + RECORD_SIZE {
+            return Err(EncodeError::BufferFull);
+        }
+        self.stack_start -= RECORD_SIZE;
+        self.buf[self.stack_start..self.stack_start + RECORD_SIZE].copy_from_slice(&record);
+        Ok(())
+    }
+
+    /// Push a value to the open collection. Values which don't fit in a collection are written
+    /// to the output, and a pointer to them is pushed instead.
+    fn _push_value<T: Encodable + ?Sized>(&mut self, value: &T) -> Result<()> {
+        if let Some(sized) = value.to_sized_value() {
+            return self._push_record(*sized.as_bytes());
+        }
+        // Leave room for the pointer to the value
+        let position = self._write_output(value, RECORD_SIZE)?;
+        // While the collection is open, pointers hold the absolute position of their target
+        #[allow(clippy::cast_possible_truncation)]
+        let pointer =
+            SizedValue::new_pointer(position as u32).ok_or(EncodeError::PointerTooLarge)?;
+        self._push_record(*pointer.as_bytes())
+    }
+
+    /// Write a scalar value as the root of the document, as the [`Encoder`](super::Encoder) does.
+    fn _write_root_value<T: Encodable + ?Sized>(&mut self, value: &T) -> Result<()> {
+        if self.top_collection_closed {
+            return Err(EncodeError::MultiTopLevelCollection);
+        }
+        if let Some(sized) = value.to_sized_value() {
+            // A value which fits in 2 bytes is the whole document, rather than a root pointer
+            self._write_output(&sized, 0)?;
+            self.top_collection_closed = true;
+            return Ok(());
+        }
+        let position = self._write_output(value, 0)?;
+        #[allow(clippy::cast_possible_truncation)]
+        self._write_root(position as u32)
+    }
+
+    /// Write a value to the output, leaving `reserve` bytes free before the stack, and return the
+    /// position it was written at.
+    fn _write_output<T: Encodable + ?Sized>(&mut self, value: &T, reserve: usize) -> Result<usize> {
+        let position = self.len;
+        let size = value.fleece_size();
+        if position + size.next_multiple_of(2) + reserve > self.stack_start {
+            return Err(EncodeError::BufferFull);
+        }
+        // Collections can't be written as a single value
+        value
+            .write_fleece_to(&mut self.buf[position..position + size], false)
+            .ok_or(EncodeError::ValueNotScalar)?;
+        self.len += size;
+        // Pad to even
+        if self.len % 2 != 0 {
+            self.buf[self.len] = 0;
+            self.len += 1;
+        }
+        Ok(position)
+    }
+
+    fn _begin(&mut self, is_dict: bool) -> Result<()> {
+        if self.top_collection_closed {
+            return Err(EncodeError::MultiTopLevelCollection);
+        }
+        if let Some((_, true, count)) = self._frame() {
+            // A collection in a dict must follow a key
+            if count % 2 == 0 {
+                return Err(EncodeError::DictWaitingForKey);
+            }
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        let mut record = self.frame.map_or(0, |frame| frame as u32 + 1);
+        if is_dict {
+            record |= FRAME_IS_DICT;
+        }
+        self._push_record(record.to_ne_bytes())?;
+        self.frame = Some(self.stack_start);
+        Ok(())
+    }
+
+    /// End the innermost open collection, writing its header and items to the output.
+    fn _end(&mut self) -> Result<()> {
+        let Some((frame, is_dict, count)) = self._frame() else {
+            return Err(EncodeError::CollectionNotOpen);
+        };
+        if is_dict {
+            self._sort_dict(frame);
+        }
+
+        let header_position = self.len;
+        let len = if is_dict { count / 2 } else { count };
+        let mut header_size = 2;
+        if len >= array::VARINT_COUNT as usize {
+            header_size += varint::size_required(len as u64);
+        }
+        let first = header_position + header_size.next_multiple_of(2);
+        let is_wide = (0..count).any(|i| {
+            let item = self._item(frame, i);
+            item.value_type() == ValueType::Pointer
+                && first + i * 2 - item.pointer_offset() as usize > pointer::MAX_NARROW as usize
+        });
+        let width = if is_wide { 4 } else { 2 };
+        // The items are read from the stack as they are written, so they can't overlap it
+        let end = first + count * width;
+        if end > self.stack_start {
+            return Err(EncodeError::BufferFull);
+        }
+
+        let collection_tag = if is_dict { tag::DICT } else { tag::ARRAY };
+        self.buf[header_position..first].fill(0);
+        write_valuestack_collection(
+            &mut self.buf[header_position..],
+            collection_tag,
+            len,
+            is_wide,
+        );
+        for i in 0..count {
+            let mut item = self._item(frame, i);
+            let position = first + i * width;
+            if item.value_type() == ValueType::Pointer {
+                #[allow(clippy::cast_possible_truncation)]
+                let offset = (position - item.pointer_offset() as usize) as u32;
+                item = SizedValue::new_pointer(offset).ok_or(EncodeError::PointerTooLarge)?;
+            }
+            item.write_fleece_to(&mut self.buf[position..position + width], is_wide);
+        }
+        self.len = end;
+
+        // Pop the collection from the stack, and push a pointer to it to its parent
+        let parent = self._record(frame) & !FRAME_IS_DICT;
+        self.stack_start = frame + RECORD_SIZE;
+        self.frame = (parent != 0).then(|| parent as usize - 1);
+        #[allow(clippy::cast_possible_truncation)]
+        if self.frame.is_some() {
+            let pointer = SizedValue::new_pointer(header_position as u32)
+                .ok_or(EncodeError::PointerTooLarge)?;
+            self._push_record(*pointer.as_bytes())
+        } else {
+            self._write_root(header_position as u32)
+        }
+    }
+
+    /// The `index`th item pushed to the collection whose record is at `frame`.
+    fn _item(&self, frame: usize, index: usize) -> SizedValue {
+        let position = frame - (index + 1) * RECORD_SIZE;
+        let mut bytes = [0u8; RECORD_SIZE];
+        bytes.copy_from_slice(&self.buf[position..position + RECORD_SIZE]);
+        SizedValue::from_bytes(bytes)
+    }
+
+    /// Sort the keys and values of the dict whose record is at `frame` by key.
+    fn _sort_dict(&mut self, frame: usize) {
+        let (output, stack) = self.buf.split_at_mut(self.stack_start);
+        let output = &output[..self.len];
+        let pairs = &mut stack[..frame - self.stack_start];
+        // SAFETY: `[u8; PAIR_SIZE]` has the same alignment as `u8`, and a dict's records are
+        // always a whole number of pairs.
+        let pairs = unsafe {
+            core::slice::from_raw_parts_mut(
+                pairs.as_mut_ptr().cast::<[u8; PAIR_SIZE]>(),
+                pairs.len() / PAIR_SIZE,
+            )
+        };
+        // The stack grows downwards, so each pair is stored as the value followed by the key, and
+        // pairs must be sorted in descending order to be read in ascending order.
+        pairs.sort_unstable_by(|pair1, pair2| {
+            Self::_key_cmp(output, &pair2[RECORD_SIZE..], &pair1[RECORD_SIZE..])
+        });
+    }
+
+    fn _key_cmp(output: &[u8], key1: &[u8], key2: &[u8]) -> Ordering {
+        Self::_key_str(output, key1).cmp(Self::_key_str(output, key2))
+    }
+
+    fn _key_str<'o>(output: &'o [u8], key: &'o [u8]) -> &'o str {
+        let mut bytes = [0u8; RECORD_SIZE];
+        bytes.copy_from_slice(key);
+        let key_value = SizedValue::from_bytes(bytes);
+        // While the dict is open, pointers to keys hold the absolute position of the key
+        let key = if key_value.value_type() == ValueType::Pointer {
+            &output[key_value.pointer_offset() as usize..]
+        } else {
+            key
+        };
+        let key = unsafe { &*(core::ptr::from_ref::<[u8]>(key) as *const Value) };
+        key.to_str()
+    }
+
+    /// Write the root pointer at the end of the data, pointing to the top-level collection.
+    fn _write_root(&mut self, collection_position: u32) -> Result<()> {
+        #[allow(clippy::cast_possible_truncation)]
+        let offset = self.len as u32 - collection_position;
+        let needs_inner = offset > u32::from(pointer::MAX_NARROW);
+        if self.len + if needs_inner { 6 } else { 2 } > self.stack_start {
+            return Err(EncodeError::BufferFull);
+        }
+        let root = if needs_inner {
+            // The root must be 2 bytes, so point to a 4-byte pointer to the collection
+            let inner = SizedValue::new_pointer(offset).ok_or(EncodeError::PointerTooLarge)?;
+            inner.write_fleece_to(&mut self.buf[self.len..self.len + 4], true);
+            self.len += 4;
+            SizedValue::new_pointer(4).ok_or(EncodeError::PointerTooLarge)?
+        } else {
+            SizedValue::new_pointer(offset).ok_or(EncodeError::PointerTooLarge)?
+        };
+        root.write_fleece_to(&mut self.buf[self.len..self.len + 2], false);
+        self.len += 2;
+        self.top_collection_closed = true;
+        Ok(())
+    }
+}
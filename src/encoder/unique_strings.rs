@@ -0,0 +1,49 @@
+use alloc::{boxed::Box, collections::BTreeMap};
+
+/// Strings longer than this are not de-duplicated by default, as long strings are rarely repeated,
+/// and each one would be copied into the table.
+pub const DEFAULT_MAX_LEN: usize = 100;
+
+/// The most strings the table will hold. Once it is full, new strings are written in full, so the
+/// memory used by the table is bounded, however large the output grows.
+pub const MAX_COUNT: usize = 4096;
+
+/// A table of the strings which have been written to the output, and where they were written, so
+/// that repeated strings can be written as pointers to the first copy.
+pub struct UniqueStrings {
+    pub enabled: bool,
+    pub max_len: usize,
+    offsets: BTreeMap<Box<str>, u32>,
+}
+
+impl Default for UniqueStrings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_len: DEFAULT_MAX_LEN,
+            offsets: BTreeMap::new(),
+        }
+    }
+}
+
+impl UniqueStrings {
+    /// The offset of an earlier copy of `string` in the output, if there is one.
+    pub fn get(&self, string: &str) -> Option<u32> {
+        if self.enabled {
+            self.offsets.get(string).copied()
+        } else {
+            None
+        }
+    }
+
+    /// Record that `string` was written to the output at `offset`.
+    pub fn insert(&mut self, string: &str, offset: u32) {
+        if self.enabled && string.len() <= self.max_len && self.offsets.len() < MAX_COUNT {
+            self.offsets.insert(string.into(), offset);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.offsets.clear();
+    }
+}
@@ -5,6 +5,9 @@ use alloc::{boxed::Box, vec::Vec};
 #[derive(Default)]
 pub struct CollectionStack {
     collections: Vec<Collection>,
+    // The vectors of collections which have been ended, kept so their capacity can be reused
+    array_pool: Vec<Vec<SizedValue>>,
+    dict_pool: Vec<Vec<DictElement>>,
 }
 
 pub enum Collection {
@@ -60,8 +63,9 @@ impl CollectionStack {
                 return Err(EncodeError::DictWaitingForKey);
             }
         }
-        self.collections
-            .push(Collection::Array(Array::with_capacity(capacity)));
+        let mut values = self.array_pool.pop().unwrap_or_default();
+        values.reserve(capacity);
+        self.collections.push(Collection::Array(Array { values }));
         Ok(())
     }
 
@@ -72,7 +76,11 @@ impl CollectionStack {
                 return Err(EncodeError::DictWaitingForKey);
             }
         }
-        self.collections.push(Collection::Dict(Dict::new()));
+        let values = self.dict_pool.pop().unwrap_or_default();
+        self.collections.push(Collection::Dict(Dict {
+            values,
+            next_key: None,
+        }));
         Ok(())
     }
 
@@ -85,28 +93,36 @@ impl CollectionStack {
         }
         self.collections.pop()
     }
-}
 
-impl Array {
-    pub fn with_capacity(capacity: usize) -> Self {
-        Self {
-            values: Vec::with_capacity(capacity),
+    /// Keep the vector of a collection which has been ended, to be reused by the next collection.
+    pub fn recycle(&mut self, collection: Collection) {
+        match collection {
+            Collection::Array(mut array) => {
+                array.values.clear();
+                self.array_pool.push(array.values);
+            }
+            Collection::Dict(mut dict) => {
+                dict.values.clear();
+                self.dict_pool.push(dict.values);
+            }
         }
     }
 
+    /// Discard all open collections, keeping their vectors to be reused.
+    pub fn clear(&mut self) {
+        while let Some(collection) = self.collections.pop() {
+            self.recycle(collection);
+        }
+    }
+}
+
+impl Array {
     pub fn push(&mut self, value: SizedValue) {
         self.values.push(value);
     }
 }
 
 impl Dict {
-    pub fn new() -> Self {
-        Self {
-            values: Vec::new(),
-            next_key: None,
-        }
-    }
-
     pub fn push_key(&mut self, key: DictKey) -> Option<()> {
         if self.next_key.is_some() {
             return None;
@@ -0,0 +1,266 @@
+use alloc::string::ToString;
+use std::io;
+
+use super::{Encodable, EncodeError, Encoder, Result};
+use crate::json::parser::{JsonSink, Parser};
+use crate::value::pointer;
+use crate::{MutableArray, MutableDict, SharedKeys, Value, ValueType};
+
+/// Output is written to the writer once this many bytes have been buffered.
+const FLUSH_THRESHOLD: usize = 16 * 1024;
+/// The largest output which can be written. Values in open collections are referred to by their
+/// position in the output until the collection is written, and that must fit in a pointer.
+const MAX_OUTPUT_SIZE: usize = pointer::MAX_WIDE as usize;
+
+/// An [`Encoder`] which streams its output to an [`io::Write`], rather than keeping it all in
+/// memory. Values are written to the output as soon as they are complete, and are never changed
+/// afterwards, so only the open collections and a small buffer are kept in memory.
+///
+/// Output is flushed between calls, and while [`WriterEncoder::write_fleece`] and
+/// [`WriterEncoder::write_json`] write each value. Other collections, such as a `Vec` given to
+/// [`WriterEncoder::write_value`] or a [`MutableDict`], are buffered until they are written.
+///
+/// As with [`Encoder`], repeated strings are not de-duplicated unless
+/// [`WriterEncoder::set_unique_strings`] enables it, in which case the bounded table of strings is
+/// also kept in memory.
+///
+/// The output is limited to 1 GiB, as the largest offset a Fleece pointer can hold is just under
+/// 1 GiB. Once the output is larger than that, writes return [`EncodeError::OutputTooLarge`].
+///
+/// The output must be completed with [`WriterEncoder::finish`].
+pub struct WriterEncoder<W: io::Write> {
+    encoder: Encoder,
+    writer: W,
+    // The first error returned by `writer`. Once the writer has failed, output is discarded.
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> WriterEncoder<W> {
+    /// Create an Encoder which writes its output to `writer`. The writer may be borrowed, such as
+    /// a `&mut File`.
+    #[must_use]
+    pub fn new(writer: W) -> Self {
+        Self {
+            encoder: Encoder::new(),
+            writer,
+            error: None,
+        }
+    }
+
+    /// See [`Encoder::write_key`].
+    /// ## Errors
+    /// See [`Encoder::write_key`]. Also [`EncodeError::OutputTooLarge`] if the output is too large.
+    pub fn write_key(&mut self, key: &str) -> Result<()> {
+        self._check_size()?;
+        let result = self.encoder.write_key(key);
+        self._flush_if_full();
+        result
+    }
+
+    /// See [`Encoder::write_value`].
+    /// ## Errors
+    /// See [`Encoder::write_value`]. Also [`EncodeError::OutputTooLarge`] if the output is too
+    /// large.
+    pub fn write_value<T>(&mut self, value: T) -> Result<()>
+    where
+        T: Encodable,
+    {
+        self._check_size()?;
+        let result = self.encoder.write_value(value);
+        self._flush_if_full();
+        result
+    }
+
+    /// See [`Encoder::write_fleece`]. The output is flushed as each value in a collection is
+    /// written.
+    /// ## Errors
+    /// See [`Encoder::write_fleece`]. Also [`EncodeError::OutputTooLarge`] if the output is too
+    /// large.
+    pub fn write_fleece(&mut self, value: &Value) -> Result<()> {
+        match value.value_type() {
+            ValueType::Array => {
+                let Some(array) = value.as_array() else {
+                    unreachable!()
+                };
+                self.begin_array(array.len())?;
+                for val in array {
+                    self.write_fleece(val)?;
+                }
+                self.end_array()
+            }
+            ValueType::Dict => {
+                let Some(dict) = value.as_dict() else {
+                    unreachable!()
+                };
+                self.begin_dict()?;
+                for (key, value) in dict {
+                    self.write_key(key)?;
+                    self.write_fleece(value)?;
+                }
+                self.end_dict()
+            }
+            _ => {
+                self._check_size()?;
+                let result = self.encoder.write_fleece(value);
+                self._flush_if_full();
+                result
+            }
+        }
+    }
+
+    /// See [`Encoder::write_mutable_dict`].
+    /// ## Errors
+    /// See [`Encoder::write_mutable_dict`]. Also [`EncodeError::OutputTooLarge`] if the output is too
+    /// large.
+    pub fn write_mutable_dict(&mut self, dict: &MutableDict) -> Result<()> {
+        self._check_size()?;
+        let result = self.encoder.write_mutable_dict(dict);
+        self._flush_if_full();
+        result
+    }
+
+    /// See [`Encoder::write_mutable_array`].
+    /// ## Errors
+    /// See [`Encoder::write_mutable_array`]. Also [`EncodeError::OutputTooLarge`] if the output is too
+    /// large.
+    pub fn write_mutable_array(&mut self, array: &MutableArray) -> Result<()> {
+        self._check_size()?;
+        let result = self.encoder.write_mutable_array(array);
+        self._flush_if_full();
+        result
+    }
+
+    /// See [`Encoder::write_json`]. The output is flushed as the JSON is parsed.
+    /// ## Errors
+    /// See [`Encoder::write_json`].
+    pub fn write_json(&mut self, json: &str) -> crate::Result<()> {
+        Parser::new(json).parse_into(self)
+    }
+
+    /// See [`Encoder::write_json5`]. The output is flushed as the JSON5 is parsed.
+    /// ## Errors
+    /// See [`Encoder::write_json5`].
+    pub fn write_json5(&mut self, json5: &str) -> crate::Result<()> {
+        Parser::new_json5(json5).parse_into(self)
+    }
+
+    /// See [`Encoder::set_shared_keys`].
+    pub fn set_shared_keys(&mut self, shared_keys: SharedKeys) {
+        self.encoder.set_shared_keys(shared_keys);
+    }
+
+    /// See [`Encoder::set_unique_strings`].
+    pub fn set_unique_strings(&mut self, enabled: bool) {
+        self.encoder.set_unique_strings(enabled);
+    }
+
+    /// See [`Encoder::set_max_unique_string_len`].
+    pub fn set_max_unique_string_len(&mut self, max_len: usize) {
+        self.encoder.set_max_unique_string_len(max_len);
+    }
+
+    /// ## Errors
+    /// See [`Encoder::begin_array`]. Also [`EncodeError::OutputTooLarge`] if the output is too large.
+    pub fn begin_array(&mut self, capacity: usize) -> Result<()> {
+        self._check_size()?;
+        self.encoder.begin_array(capacity)
+    }
+
+    /// ## Errors
+    /// See [`Encoder::end_array`]. Also [`EncodeError::OutputTooLarge`] if the output is too large.
+    pub fn end_array(&mut self) -> Result<()> {
+        self._check_size()?;
+        let result = self.encoder.end_array();
+        self._flush_if_full();
+        result
+    }
+
+    /// ## Errors
+    /// See [`Encoder::begin_dict`]. Also [`EncodeError::OutputTooLarge`] if the output is too large.
+    pub fn begin_dict(&mut self) -> Result<()> {
+        self._check_size()?;
+        self.encoder.begin_dict()
+    }
+
+    /// ## Errors
+    /// See [`Encoder::end_dict`]. Also [`EncodeError::OutputTooLarge`] if the output is too large.
+    pub fn end_dict(&mut self) -> Result<()> {
+        self._check_size()?;
+        let result = self.encoder.end_dict();
+        self._flush_if_full();
+        result
+    }
+
+    /// Close any open collections, write the remaining output to the writer, and flush it.
+    /// Returns the writer.
+    /// ## Errors
+    /// The first error returned by the writer, including errors from earlier writes. An error
+    /// wrapping [`EncodeError::OutputTooLarge`] if the output is too large.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self._check_size().is_err() {
+            return Err(io::Error::other(EncodeError::OutputTooLarge.to_string()));
+        }
+        self.encoder._end();
+        self._flush();
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: io::Write> WriterEncoder<W> {
+    /// Values are written at their position in the output, which is later used to point to them,
+    /// so nothing more can be written once the output is too large for that.
+    fn _check_size(&self) -> Result<()> {
+        if self.encoder._position() > MAX_OUTPUT_SIZE {
+            return Err(EncodeError::OutputTooLarge);
+        }
+        Ok(())
+    }
+
+    fn _flush_if_full(&mut self) {
+        if self.encoder.out.len() >= FLUSH_THRESHOLD {
+            self._flush();
+        }
+    }
+
+    /// Write all of the Encoder's output to the writer. Values in the output are never modified
+    /// after they are written, so they can be flushed at any time.
+    fn _flush(&mut self) {
+        if self.error.is_none() {
+            if let Err(error) = self.writer.write_all(&self.encoder.out) {
+                self.error = Some(error);
+            }
+        }
+        self.encoder.flushed += self.encoder.out.len();
+        self.encoder.out.clear();
+    }
+}
+
+impl<W: io::Write> JsonSink for WriterEncoder<W> {
+    fn write_key(&mut self, key: &str) -> Result<()> {
+        WriterEncoder::write_key(self, key)
+    }
+
+    fn write_value<T: Encodable>(&mut self, value: T) -> Result<()> {
+        WriterEncoder::write_value(self, value)
+    }
+
+    fn begin_array(&mut self, capacity: usize) -> Result<()> {
+        WriterEncoder::begin_array(self, capacity)
+    }
+
+    fn end_array(&mut self) -> Result<()> {
+        WriterEncoder::end_array(self)
+    }
+
+    fn begin_dict(&mut self) -> Result<()> {
+        WriterEncoder::begin_dict(self)
+    }
+
+    fn end_dict(&mut self) -> Result<()> {
+        WriterEncoder::end_dict(self)
+    }
+}
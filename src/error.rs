@@ -1,4 +1,8 @@
+use crate::convert::FromFleeceError;
+use crate::delta::DeltaError;
 use crate::encoder::EncodeError;
+use crate::json::JsonError;
+use crate::keypath::KeyPathError;
 use crate::value::DecodeError;
 use alloc::string::String;
 use core::fmt;
@@ -12,6 +16,10 @@ pub use crate::ser::SerializeError;
 pub enum Error {
     Encode(EncodeError),
     Decode(DecodeError),
+    Json(JsonError),
+    Delta(DeltaError),
+    KeyPath(KeyPathError),
+    FromFleece(FromFleeceError),
     Message(String),
     #[cfg(feature = "serde")]
     Serialize(SerializeError),
@@ -24,6 +32,10 @@ impl fmt::Display for Error {
         match self {
             Error::Encode(e) => write!(f, "Encode {e}"),
             Error::Decode(e) => write!(f, "Decode {e}"),
+            Error::Json(e) => write!(f, "JSON {e}"),
+            Error::Delta(e) => write!(f, "Delta {e}"),
+            Error::KeyPath(e) => write!(f, "KeyPath {e}"),
+            Error::FromFleece(e) => write!(f, "FromFleece {e}"),
             Error::Message(m) => write!(f, "{m}"),
             #[cfg(feature = "serde")]
             Error::Serialize(e) => write!(f, "Serialize {e}"),
@@ -48,6 +60,30 @@ impl From<DecodeError> for Error {
     }
 }
 
+impl From<JsonError> for Error {
+    fn from(value: JsonError) -> Self {
+        Error::Json(value)
+    }
+}
+
+impl From<DeltaError> for Error {
+    fn from(value: DeltaError) -> Self {
+        Error::Delta(value)
+    }
+}
+
+impl From<KeyPathError> for Error {
+    fn from(value: KeyPathError) -> Self {
+        Error::KeyPath(value)
+    }
+}
+
+impl From<FromFleeceError> for Error {
+    fn from(value: FromFleeceError) -> Self {
+        Error::FromFleece(value)
+    }
+}
+
 #[cfg(feature = "serde")]
 impl From<SerializeError> for Error {
     fn from(value: SerializeError) -> Self {
@@ -0,0 +1,69 @@
+use core::fmt;
+
+/// An error encountered while parsing JSON, with the position it occurred at.
+#[derive(Debug)]
+pub struct JsonError {
+    pub kind: JsonErrorKind,
+    /// The line the error occurred on, starting from 1.
+    pub line: usize,
+    /// The column the error occurred at, in characters, starting from 1.
+    pub column: usize,
+}
+
+#[derive(Debug)]
+pub enum JsonErrorKind {
+    UnexpectedEnd,
+    UnexpectedCharacter(char),
+    InvalidNumber,
+    InvalidEscape,
+    InvalidUnicode,
+    ControlCharacterInString,
+    TrailingCharacters,
+    UnterminatedComment,
+    TooDeep,
+}
+
+impl JsonError {
+    /// Create an error for the given byte offset in `input`.
+    pub(crate) fn at(kind: JsonErrorKind, input: &str, offset: usize) -> Self {
+        let mut offset = offset.min(input.len());
+        while !input.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let before = &input[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            kind,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.kind, self.line, self.column
+        )
+    }
+}
+
+impl fmt::Display for JsonErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonErrorKind::UnexpectedEnd => write!(f, "Unexpected end of input"),
+            JsonErrorKind::UnexpectedCharacter(c) => write!(f, "Unexpected character {c:?}"),
+            JsonErrorKind::InvalidNumber => write!(f, "Invalid number"),
+            JsonErrorKind::InvalidEscape => write!(f, "Invalid escape sequence"),
+            JsonErrorKind::InvalidUnicode => write!(f, "Invalid unicode escape"),
+            JsonErrorKind::ControlCharacterInString => {
+                write!(f, "Unescaped control character in string")
+            }
+            JsonErrorKind::TrailingCharacters => write!(f, "Trailing characters after value"),
+            JsonErrorKind::UnterminatedComment => write!(f, "Unterminated comment"),
+            JsonErrorKind::TooDeep => write!(f, "Collections nested too deeply"),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Conversion between Fleece and JSON.
+
+mod error;
+pub(crate) mod parser;
+mod writer;
+
+use alloc::{string::String, sync::Arc};
+use core::fmt;
+
+use crate::alloced::Alloced;
+use crate::{Array, Dict, Encoder, MutableArray, MutableDict, Scope, SharedKeys, Value};
+pub use error::{JsonError, JsonErrorKind};
+pub use writer::JsonWriter;
+
+/// A type which can be written as JSON by a [`JsonWriter`].
+pub trait ToJson {
+    /// Write `self` as JSON to the given writer.
+    /// ## Errors
+    /// If the underlying [`fmt::Write`] fails.
+    fn write_json<W: fmt::Write>(&self, writer: &mut JsonWriter<W>) -> fmt::Result;
+}
+
+/// Convert a Fleece value to a compact JSON string.
+///
+/// Shared keys are decoded using the [`SharedKeys`](crate::SharedKeys) of the
+/// [`Scope`](crate::Scope) containing the data, `Data` values are written as base64 strings, and
+/// `Undefined` values are skipped in dicts and written as `null` in arrays.
+///
+/// If a dict uses shared keys, but the data isn't in a [`Scope`](crate::Scope) with
+/// [`SharedKeys`](crate::SharedKeys), its int keys can't be decoded and are written as strings of
+/// the int, e.g. `"5"`. The result is still valid JSON, but it doesn't round-trip.
+#[must_use]
+pub fn to_json<T: ToJson + ?Sized>(value: &T) -> String {
+    let mut out = String::new();
+    // Writing to a String can't fail
+    let _ = to_json_writer(value, &mut out);
+    out
+}
+
+/// The same as [`to_json`], but the JSON is pretty-printed with two-space indentation.
+#[must_use]
+pub fn to_json_pretty<T: ToJson + ?Sized>(value: &T) -> String {
+    let mut out = String::new();
+    // Writing to a String can't fail
+    let _ = to_json_writer_pretty(value, &mut out);
+    out
+}
+
+/// Write a Fleece value as compact JSON to `out`. See [`to_json`].
+/// ## Errors
+/// If writing to `out` fails.
+pub fn to_json_writer<T, W>(value: &T, out: W) -> fmt::Result
+where
+    T: ToJson + ?Sized,
+    W: fmt::Write,
+{
+    value.write_json(&mut JsonWriter::new(out))
+}
+
+/// Write a Fleece value as pretty-printed JSON to `out`. See [`to_json_pretty`].
+/// ## Errors
+/// If writing to `out` fails.
+pub fn to_json_writer_pretty<T, W>(value: &T, out: W) -> fmt::Result
+where
+    T: ToJson + ?Sized,
+    W: fmt::Write,
+{
+    value.write_json(&mut JsonWriter::new_pretty(out))
+}
+
+impl ToJson for Value {
+    fn write_json<W: fmt::Write>(&self, writer: &mut JsonWriter<W>) -> fmt::Result {
+        writer.write_value(self)
+    }
+}
+
+impl ToJson for Dict {
+    fn write_json<W: fmt::Write>(&self, writer: &mut JsonWriter<W>) -> fmt::Result {
+        writer.write_dict(self)
+    }
+}
+
+impl ToJson for Array {
+    fn write_json<W: fmt::Write>(&self, writer: &mut JsonWriter<W>) -> fmt::Result {
+        writer.write_array(self)
+    }
+}
+
+impl ToJson for MutableDict {
+    fn write_json<W: fmt::Write>(&self, writer: &mut JsonWriter<W>) -> fmt::Result {
+        writer.write_mutable_dict(self)
+    }
+}
+
+impl ToJson for MutableArray {
+    fn write_json<W: fmt::Write>(&self, writer: &mut JsonWriter<W>) -> fmt::Result {
+        writer.write_mutable_array(self)
+    }
+}
+
+impl<T: ToJson + ?Sized> ToJson for Alloced<T> {
+    fn write_json<W: fmt::Write>(&self, writer: &mut JsonWriter<W>) -> fmt::Result {
+        (**self).write_json(writer)
+    }
+}
+
+/// Parse JSON into Fleece, returning the encoded data wrapped in a [`Scope`]. If `shared_keys`
+/// are given, dict keys are encoded with them, and the [`SharedKeys`] are retained by the
+/// [`Scope`].
+///
+/// The JSON is written directly to an [`Encoder`] as it is parsed. Numbers without a fraction or
+/// exponent are encoded as integers, other numbers are encoded as doubles.
+/// ## Errors
+/// - If the JSON is invalid. The [`JsonError`] gives the line and column of the error.
+pub fn from_json(json: &str, shared_keys: Option<SharedKeys>) -> crate::Result<Arc<Scope>> {
+    let mut encoder = Encoder::new();
+    if let Some(shared_keys) = shared_keys {
+        encoder.set_shared_keys(shared_keys);
+    }
+    encoder.write_json(json)?;
+    Ok(encoder.finish_scoped())
+}
+
+/// The same as [`from_json`], but parses JSON5, which also allows comments, trailing commas,
+/// unquoted keys, single-quoted strings, hexadecimal numbers, `Infinity` and `NaN`.
+/// See <https://spec.json5.org>.
+/// ## Errors
+/// - If the JSON5 is invalid. The [`JsonError`] gives the line and column of the error.
+pub fn from_json5(json5: &str, shared_keys: Option<SharedKeys>) -> crate::Result<Arc<Scope>> {
+    let mut encoder = Encoder::new();
+    if let Some(shared_keys) = shared_keys {
+        encoder.set_shared_keys(shared_keys);
+    }
+    encoder.write_json5(json5)?;
+    Ok(encoder.finish_scoped())
+}
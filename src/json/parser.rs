@@ -0,0 +1,536 @@
+use alloc::string::String;
+
+use super::error::{JsonError, JsonErrorKind};
+use crate::encoder::{Encodable, EncodeError};
+use crate::{Encoder, Error, Result};
+
+// Deeper nesting than this is rejected, rather than risking a stack overflow.
+const MAX_DEPTH: usize = 512;
+
+/// A JSON parser which writes each value directly to an [`Encoder`] as it is parsed, so no
+/// intermediate tree is built.
+///
+/// In JSON5 mode, the parser also accepts comments, trailing commas, unquoted keys,
+/// single-quoted strings, hexadecimal numbers, `Infinity` and `NaN`, and the other extensions
+/// described at <https://spec.json5.org>.
+pub(crate) struct Parser<'a> {
+    input: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize,
+    json5: bool,
+    // Strings containing escapes are unescaped into here. Strings without escapes are borrowed
+    // directly from the input.
+    scratch: String,
+}
+
+type SinkResult = core::result::Result<(), EncodeError>;
+
+/// The methods of an encoder which the [`Parser`] writes values through. This lets a
+/// [`WriterEncoder`](crate::encoder::WriterEncoder) flush its output while a document is parsed.
+pub(crate) trait JsonSink {
+    fn write_key(&mut self, key: &str) -> SinkResult;
+    fn write_value<T: Encodable>(&mut self, value: T) -> SinkResult;
+    fn begin_array(&mut self, capacity: usize) -> SinkResult;
+    fn end_array(&mut self) -> SinkResult;
+    fn begin_dict(&mut self) -> SinkResult;
+    fn end_dict(&mut self) -> SinkResult;
+}
+
+impl JsonSink for Encoder {
+    fn write_key(&mut self, key: &str) -> SinkResult {
+        Encoder::write_key(self, key)
+    }
+
+    fn write_value<T: Encodable>(&mut self, value: T) -> SinkResult {
+        Encoder::write_value(self, value)
+    }
+
+    fn begin_array(&mut self, capacity: usize) -> SinkResult {
+        Encoder::begin_array(self, capacity)
+    }
+
+    fn end_array(&mut self) -> SinkResult {
+        Encoder::end_array(self)
+    }
+
+    fn begin_dict(&mut self) -> SinkResult {
+        Encoder::begin_dict(self)
+    }
+
+    fn end_dict(&mut self) -> SinkResult {
+        Encoder::end_dict(self)
+    }
+}
+
+/// A string parsed by [`Parser::_parse_string`].
+enum ParsedStr {
+    Input(usize, usize),
+    Scratch,
+}
+
+impl<'a> Parser<'a> {
+    pub(crate) fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            bytes: input.as_bytes(),
+            pos: 0,
+            depth: 0,
+            json5: false,
+            scratch: String::new(),
+        }
+    }
+
+    pub(crate) fn new_json5(input: &'a str) -> Self {
+        Self {
+            json5: true,
+            ..Self::new(input)
+        }
+    }
+
+    /// Parse a single JSON value from the input and write it to `encoder`.
+    pub(crate) fn parse_into(mut self, encoder: &mut impl JsonSink) -> Result<()> {
+        self._skip_whitespace()?;
+        self._parse_value(encoder)?;
+        self._skip_whitespace()?;
+        if self.pos < self.bytes.len() {
+            return Err(self._error(JsonErrorKind::TrailingCharacters));
+        }
+        Ok(())
+    }
+}
+
+impl Parser<'_> {
+    fn _parse_value(&mut self, encoder: &mut impl JsonSink) -> Result<()> {
+        match self._peek() {
+            Some(b'{') => self._parse_dict(encoder),
+            Some(b'[') => self._parse_array(encoder),
+            Some(b'"') => {
+                let string = self._parse_string()?;
+                Ok(encoder.write_value(self._str(&string))?)
+            }
+            Some(b'\'') if self.json5 => {
+                let string = self._parse_string()?;
+                Ok(encoder.write_value(self._str(&string))?)
+            }
+            Some(b'-' | b'0'..=b'9') => self._parse_number(encoder),
+            Some(b'+' | b'.' | b'I' | b'N') if self.json5 => self._parse_number(encoder),
+            Some(b't') => {
+                self._expect_literal("true")?;
+                Ok(encoder.write_value(true)?)
+            }
+            Some(b'f') => {
+                self._expect_literal("false")?;
+                Ok(encoder.write_value(false)?)
+            }
+            Some(b'n') => {
+                self._expect_literal("null")?;
+                Ok(encoder.write_value(crate::encoder::NullValue)?)
+            }
+            Some(_) => Err(self._unexpected_character()),
+            None => Err(self._error(JsonErrorKind::UnexpectedEnd)),
+        }
+    }
+
+    fn _parse_array(&mut self, encoder: &mut impl JsonSink) -> Result<()> {
+        self._enter()?;
+        encoder.begin_array(0)?;
+        self.pos += 1;
+        self._skip_whitespace()?;
+        if self._peek() == Some(b']') {
+            self.pos += 1;
+        } else {
+            loop {
+                self._skip_whitespace()?;
+                if self.json5 && self._peek() == Some(b']') {
+                    // Trailing comma
+                    self.pos += 1;
+                    break;
+                }
+                self._parse_value(encoder)?;
+                self._skip_whitespace()?;
+                match self._next() {
+                    Some(b',') => {}
+                    Some(b']') => break,
+                    _ => return Err(self._unexpected_previous()),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(encoder.end_array()?)
+    }
+
+    fn _parse_dict(&mut self, encoder: &mut impl JsonSink) -> Result<()> {
+        self._enter()?;
+        encoder.begin_dict()?;
+        self.pos += 1;
+        self._skip_whitespace()?;
+        if self._peek() == Some(b'}') {
+            self.pos += 1;
+        } else {
+            loop {
+                self._skip_whitespace()?;
+                let key = match self._peek() {
+                    Some(b'"') => self._parse_string()?,
+                    Some(b'}') if self.json5 => {
+                        // Trailing comma
+                        self.pos += 1;
+                        break;
+                    }
+                    Some(b'\'') if self.json5 => self._parse_string()?,
+                    Some(_) if self.json5 => self._parse_identifier()?,
+                    _ => return Err(self._unexpected_character()),
+                };
+                encoder.write_key(self._str(&key))?;
+                self._skip_whitespace()?;
+                if self._next() != Some(b':') {
+                    return Err(self._unexpected_previous());
+                }
+                self._skip_whitespace()?;
+                self._parse_value(encoder)?;
+                self._skip_whitespace()?;
+                match self._next() {
+                    Some(b',') => {}
+                    Some(b'}') => break,
+                    _ => return Err(self._unexpected_previous()),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(encoder.end_dict()?)
+    }
+
+    /// Parse a number, writing it as an integer if it has no fraction or exponent (and fits in a
+    /// 64-bit integer), otherwise as a double.
+    fn _parse_number(&mut self, encoder: &mut impl JsonSink) -> Result<()> {
+        let start = self.pos;
+        let negative = self._peek() == Some(b'-');
+        if negative || (self.json5 && self._peek() == Some(b'+')) {
+            self.pos += 1;
+        }
+        let digits_start = self.pos;
+        match self._peek() {
+            Some(b'0') if self.json5 && matches!(self._peek_at(1), Some(b'x' | b'X')) => {
+                return self._parse_hex_number(encoder, negative);
+            }
+            Some(b'I') if self.json5 => {
+                self._expect_literal("Infinity")?;
+                let infinity = if negative {
+                    f64::NEG_INFINITY
+                } else {
+                    f64::INFINITY
+                };
+                return Ok(encoder.write_value(infinity)?);
+            }
+            Some(b'N') if self.json5 => {
+                self._expect_literal("NaN")?;
+                return Ok(encoder.write_value(f64::NAN)?);
+            }
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => self._skip_digits(),
+            // JSON5 allows a leading decimal point
+            Some(b'.') if self.json5 => {}
+            _ => return Err(self._error(JsonErrorKind::InvalidNumber)),
+        }
+        let mut is_float = false;
+        if self._peek() == Some(b'.') {
+            is_float = true;
+            self.pos += 1;
+            let has_integer_part = self.pos - 1 > digits_start;
+            if matches!(self._peek(), Some(b'0'..=b'9')) {
+                self._skip_digits();
+            } else if !self.json5 || !has_integer_part {
+                // JSON5 allows a trailing decimal point, but not a lone one
+                return Err(self._error(JsonErrorKind::InvalidNumber));
+            }
+        }
+        if matches!(self._peek(), Some(b'e' | b'E')) {
+            is_float = true;
+            self.pos += 1;
+            if matches!(self._peek(), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if !matches!(self._peek(), Some(b'0'..=b'9')) {
+                return Err(self._error(JsonErrorKind::InvalidNumber));
+            }
+            self._skip_digits();
+        }
+
+        // Rust's number parsing doesn't accept a leading '+' on floats with a leading '.'
+        let number = if self.input.as_bytes()[start] == b'+' {
+            &self.input[start + 1..self.pos]
+        } else {
+            &self.input[start..self.pos]
+        };
+        if !is_float {
+            if let Ok(int) = number.parse::<i64>() {
+                return Ok(encoder.write_value(int)?);
+            }
+            if let Ok(uint) = number.parse::<u64>() {
+                return Ok(encoder.write_value(uint)?);
+            }
+        }
+        match number.parse::<f64>() {
+            Ok(double) => Ok(encoder.write_value(double)?),
+            Err(_) => Err(JsonError::at(JsonErrorKind::InvalidNumber, self.input, start).into()),
+        }
+    }
+
+    /// Parse a JSON5 hexadecimal integer, starting at the `0x`.
+    fn _parse_hex_number(&mut self, encoder: &mut impl JsonSink, negative: bool) -> Result<()> {
+        self.pos += 2;
+        let digits_start = self.pos;
+        while matches!(self._peek(), Some(b) if b.is_ascii_hexdigit()) {
+            self.pos += 1;
+        }
+        let Ok(uint) = u64::from_str_radix(&self.input[digits_start..self.pos], 16) else {
+            return Err(self._error(JsonErrorKind::InvalidNumber));
+        };
+        if !negative {
+            return Ok(encoder.write_value(uint)?);
+        }
+        match i64::try_from(uint) {
+            Ok(int) => Ok(encoder.write_value(-int)?),
+            // i64::MIN doesn't fit in a positive i64
+            Err(_) if uint == i64::MIN.unsigned_abs() => Ok(encoder.write_value(i64::MIN)?),
+            Err(_) => Err(self._error(JsonErrorKind::InvalidNumber)),
+        }
+    }
+
+    /// Parse an unquoted JSON5 key. Keys may contain letters, digits, `_` and `$`, and may not
+    /// start with a digit.
+    fn _parse_identifier(&mut self) -> Result<ParsedStr> {
+        let start = self.pos;
+        for c in self.input[start..].chars() {
+            let valid = if self.pos == start {
+                c.is_alphabetic() || c == '_' || c == '$'
+            } else {
+                c.is_alphanumeric() || c == '_' || c == '$'
+            };
+            if !valid {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        if self.pos == start {
+            return Err(self._unexpected_character());
+        }
+        Ok(ParsedStr::Input(start, self.pos))
+    }
+
+    /// Parse a string, starting at the opening quote. In JSON5 mode, this may be a single quote.
+    fn _parse_string(&mut self) -> Result<ParsedStr> {
+        let quote = self.bytes[self.pos];
+        self.pos += 1;
+        let start = self.pos;
+        // Fast path for strings without escapes, which are borrowed from the input
+        loop {
+            match self._peek() {
+                Some(b) if b == quote => {
+                    self.pos += 1;
+                    return Ok(ParsedStr::Input(start, self.pos - 1));
+                }
+                Some(b'\\') => break,
+                Some(0x00..=0x1F) => {
+                    return Err(self._error(JsonErrorKind::ControlCharacterInString))
+                }
+                Some(_) => self.pos += 1,
+                None => return Err(self._error(JsonErrorKind::UnexpectedEnd)),
+            }
+        }
+
+        self.scratch.clear();
+        self.scratch.push_str(&self.input[start..self.pos]);
+        loop {
+            let run_start = self.pos;
+            while matches!(self._peek(), Some(b) if b != quote && b != b'\\' && b >= 0x20) {
+                self.pos += 1;
+            }
+            self.scratch.push_str(&self.input[run_start..self.pos]);
+            match self._next() {
+                Some(b) if b == quote => return Ok(ParsedStr::Scratch),
+                Some(b'\\') => self._parse_escape()?,
+                Some(_) => {
+                    self.pos -= 1;
+                    return Err(self._error(JsonErrorKind::ControlCharacterInString));
+                }
+                None => return Err(self._error(JsonErrorKind::UnexpectedEnd)),
+            }
+        }
+    }
+
+    /// Parse an escape sequence (after the backslash) into the scratch buffer.
+    fn _parse_escape(&mut self) -> Result<()> {
+        let c = match self._next() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{08}',
+            Some(b'f') => '\u{0C}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => self._parse_unicode_escape()?,
+            Some(b'\'') if self.json5 => '\'',
+            Some(b'v') if self.json5 => '\u{0B}',
+            Some(b'0') if self.json5 && !matches!(self._peek(), Some(b'0'..=b'9')) => '\0',
+            Some(b'x') if self.json5 => {
+                let hex = self._parse_hex(2)?;
+                // Two hex digits are always a valid char
+                char::from_u32(hex).unwrap_or_default()
+            }
+            // A line continuation, which is not included in the string
+            Some(b'\n') if self.json5 => return Ok(()),
+            Some(b'\r') if self.json5 => {
+                if self._peek() == Some(b'\n') {
+                    self.pos += 1;
+                }
+                return Ok(());
+            }
+            Some(_) => {
+                self.pos -= 1;
+                return Err(self._error(JsonErrorKind::InvalidEscape));
+            }
+            None => return Err(self._error(JsonErrorKind::UnexpectedEnd)),
+        };
+        self.scratch.push(c);
+        Ok(())
+    }
+
+    /// Parse the hex digits of a `\u` escape, including the second half of a surrogate pair.
+    fn _parse_unicode_escape(&mut self) -> Result<char> {
+        let high = self._parse_hex(4)?;
+        let code_point = if (0xD800..0xDC00).contains(&high) {
+            if self.bytes.get(self.pos..self.pos + 2) != Some(b"\\u") {
+                return Err(self._error(JsonErrorKind::InvalidUnicode));
+            }
+            self.pos += 2;
+            let low = self._parse_hex(4)?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self._error(JsonErrorKind::InvalidUnicode));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code_point).ok_or_else(|| self._error(JsonErrorKind::InvalidUnicode))
+    }
+
+    fn _parse_hex(&mut self, digits: usize) -> Result<u32> {
+        let hex = self
+            .input
+            .get(self.pos..self.pos + digits)
+            // `from_str_radix` would also accept a sign
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self._error(JsonErrorKind::InvalidUnicode))?;
+        let value =
+            u32::from_str_radix(hex, 16).map_err(|_| self._error(JsonErrorKind::InvalidUnicode))?;
+        self.pos += digits;
+        Ok(value)
+    }
+
+    fn _str(&self, string: &ParsedStr) -> &str {
+        match string {
+            ParsedStr::Input(start, end) => &self.input[*start..*end],
+            ParsedStr::Scratch => &self.scratch,
+        }
+    }
+
+    fn _expect_literal(&mut self, literal: &str) -> Result<()> {
+        for expected in literal.bytes() {
+            if self._peek() != Some(expected) {
+                return Err(self._unexpected_character());
+            }
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    fn _enter(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self._error(JsonErrorKind::TooDeep));
+        }
+        Ok(())
+    }
+
+    fn _skip_digits(&mut self) {
+        while matches!(self._peek(), Some(b'0'..=b'9')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skip whitespace, and in JSON5 mode, comments.
+    fn _skip_whitespace(&mut self) -> Result<()> {
+        loop {
+            match self._peek() {
+                Some(b' ' | b'\t' | b'\n' | b'\r') => self.pos += 1,
+                // Vertical tab and form feed
+                Some(0x0B | 0x0C) if self.json5 => self.pos += 1,
+                Some(b'/') if self.json5 => match self._peek_at(1) {
+                    Some(b'/') => {
+                        while !matches!(self._peek(), Some(b'\n') | None) {
+                            self.pos += 1;
+                        }
+                    }
+                    Some(b'*') => {
+                        let start = self.pos;
+                        let Some(end) = self.input[self.pos + 2..].find("*/") else {
+                            return Err(JsonError::at(
+                                JsonErrorKind::UnterminatedComment,
+                                self.input,
+                                start,
+                            )
+                            .into());
+                        };
+                        self.pos += 2 + end + 2;
+                    }
+                    _ => return Err(self._unexpected_character()),
+                },
+                // Non-breaking space and other unicode whitespace
+                Some(0x80..) if self.json5 => match self.input[self.pos..].chars().next() {
+                    Some(c) if c.is_whitespace() || c == '\u{FEFF}' => {
+                        self.pos += c.len_utf8();
+                    }
+                    _ => return Ok(()),
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    #[inline]
+    fn _peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    #[inline]
+    fn _peek_at(&self, offset: usize) -> Option<u8> {
+        self.bytes.get(self.pos + offset).copied()
+    }
+
+    #[inline]
+    fn _next(&mut self) -> Option<u8> {
+        let next = self._peek();
+        self.pos += 1;
+        next
+    }
+
+    /// An error for the character at the current position.
+    fn _unexpected_character(&self) -> Error {
+        match self.input.get(self.pos..).and_then(|s| s.chars().next()) {
+            Some(c) => self._error(JsonErrorKind::UnexpectedCharacter(c)),
+            None => self._error(JsonErrorKind::UnexpectedEnd),
+        }
+    }
+
+    /// An error for the character consumed by the last call to [`Parser::_next`].
+    fn _unexpected_previous(&mut self) -> Error {
+        self.pos -= 1;
+        self._unexpected_character()
+    }
+
+    fn _error(&self, kind: JsonErrorKind) -> Error {
+        JsonError::at(kind, self.input, self.pos).into()
+    }
+}
@@ -0,0 +1,264 @@
+use core::fmt;
+
+use crate::mutable::ValueSlot;
+use crate::scope::Scope;
+use crate::value::pointer::Pointer;
+use crate::{Array, Dict, MutableArray, MutableDict, Value, ValueType};
+
+/// Writes Fleece values as JSON to a [`fmt::Write`].
+pub struct JsonWriter<W: fmt::Write> {
+    out: W,
+    pretty: bool,
+    depth: usize,
+}
+
+impl<W: fmt::Write> JsonWriter<W> {
+    /// Create a writer which writes compact JSON, with no whitespace.
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            pretty: false,
+            depth: 0,
+        }
+    }
+
+    /// Create a writer which writes pretty-printed JSON, with two-space indentation.
+    pub fn new_pretty(out: W) -> Self {
+        Self {
+            out,
+            pretty: true,
+            depth: 0,
+        }
+    }
+
+    /// Consume the writer, returning the underlying [`fmt::Write`].
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Write any Fleece value as JSON. `Undefined` is written as `null`.
+    /// ## Errors
+    /// If the underlying [`fmt::Write`] fails.
+    pub fn write_value(&mut self, value: &Value) -> fmt::Result {
+        match value.value_type() {
+            ValueType::Null | ValueType::Undefined => self.out.write_str("null"),
+            ValueType::False => self.out.write_str("false"),
+            ValueType::True => self.out.write_str("true"),
+            ValueType::Short | ValueType::Int => write!(self.out, "{}", value.to_int()),
+            ValueType::UnsignedInt => write!(self.out, "{}", value.to_unsigned_int()),
+            ValueType::Float => self._write_float(value.to_float()),
+            ValueType::Double32 | ValueType::Double64 => self._write_double(value.to_double()),
+            ValueType::String => self._write_string(value.to_str()),
+            ValueType::Data => self._write_base64(value.to_data()),
+            ValueType::Array => self.write_array(Array::from_value(value)),
+            ValueType::Dict => self.write_dict(Dict::from_value(value)),
+            // A raw collection slot may still be a pointer
+            ValueType::Pointer => {
+                self.write_value(unsafe { Pointer::from_value(value).deref_unchecked(false) })
+            }
+        }
+    }
+
+    /// Write a Fleece [`Array`] as a JSON array.
+    /// ## Errors
+    /// If the underlying [`fmt::Write`] fails.
+    pub fn write_array(&mut self, array: &Array) -> fmt::Result {
+        self._begin('[')?;
+        for (i, value) in array.iter().enumerate() {
+            self._next_item(i == 0)?;
+            self.write_value(value)?;
+        }
+        self._end(']', array.is_empty())
+    }
+
+    /// Write a Fleece [`Dict`] as a JSON object. Shared keys are decoded with the
+    /// [`SharedKeys`](crate::SharedKeys) of the [`Scope`] containing the dict. If there are none,
+    /// int keys are written as strings of the int, e.g. `"5"`.
+    /// ## Errors
+    /// If the underlying [`fmt::Write`] fails.
+    pub fn write_dict(&mut self, dict: &Dict) -> fmt::Result {
+        let shared_keys = Scope::find_shared_keys(dict.array.value.bytes.as_ptr());
+        self._begin('{')?;
+        let mut first = true;
+        let mut iter = dict.array.iter();
+        while let (Some(key), Some(value)) = (iter.next(), iter.next()) {
+            if value.value_type() == ValueType::Undefined {
+                continue;
+            }
+            self._next_item(first)?;
+            first = false;
+            if key.value_type() == ValueType::Short {
+                let shared_key = key.to_unsigned_short();
+                if let Some(key) = shared_keys.as_ref().and_then(|sk| sk.decode(shared_key)) {
+                    self._write_key(key)?;
+                } else {
+                    // Without SharedKeys, the best we can do is write the integer key
+                    write!(self.out, "\"{shared_key}\"")?;
+                    self._write_colon()?;
+                }
+            } else {
+                self._write_key(key.to_str())?;
+            }
+            self.write_value(value)?;
+        }
+        self._end('}', first)
+    }
+
+    /// Write a [`MutableArray`] as a JSON array.
+    /// ## Errors
+    /// If the underlying [`fmt::Write`] fails.
+    pub fn write_mutable_array(&mut self, array: &MutableArray) -> fmt::Result {
+        self._begin('[')?;
+        for (i, value) in array.iter().enumerate() {
+            self._next_item(i == 0)?;
+            self._write_slot(value.slot())?;
+        }
+        self._end(']', array.is_empty())
+    }
+
+    /// Write a [`MutableDict`] as a JSON object.
+    /// ## Errors
+    /// If the underlying [`fmt::Write`] fails.
+    pub fn write_mutable_dict(&mut self, dict: &MutableDict) -> fmt::Result {
+        self._begin('{')?;
+        let mut first = true;
+        for (key, value) in dict {
+            let slot = value.slot();
+            if slot
+                .value()
+                .is_some_and(|v| v.value_type() == ValueType::Undefined)
+            {
+                continue;
+            }
+            self._next_item(first)?;
+            first = false;
+            self._write_key(key)?;
+            self._write_slot(slot)?;
+        }
+        self._end('}', first)
+    }
+}
+
+impl<W: fmt::Write> JsonWriter<W> {
+    fn _write_slot(&mut self, slot: &ValueSlot) -> fmt::Result {
+        match slot {
+            ValueSlot::Inline(_) | ValueSlot::Pointer(_) => {
+                // Inline and Pointer slots always hold a value
+                self.write_value(slot.value().unwrap())
+            }
+            ValueSlot::MutableArray(array) => self.write_mutable_array(array),
+            ValueSlot::MutableDict(dict) => self.write_mutable_dict(dict),
+        }
+    }
+
+    fn _begin(&mut self, open: char) -> fmt::Result {
+        self.depth += 1;
+        self.out.write_char(open)
+    }
+
+    fn _next_item(&mut self, first: bool) -> fmt::Result {
+        if !first {
+            self.out.write_char(',')?;
+        }
+        self._newline()
+    }
+
+    fn _end(&mut self, close: char, empty: bool) -> fmt::Result {
+        self.depth -= 1;
+        if !empty {
+            self._newline()?;
+        }
+        self.out.write_char(close)
+    }
+
+    fn _newline(&mut self) -> fmt::Result {
+        if self.pretty {
+            self.out.write_char('\n')?;
+            for _ in 0..self.depth {
+                self.out.write_str("  ")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn _write_key(&mut self, key: &str) -> fmt::Result {
+        self._write_string(key)?;
+        self._write_colon()
+    }
+
+    fn _write_colon(&mut self) -> fmt::Result {
+        if self.pretty {
+            self.out.write_str(": ")
+        } else {
+            self.out.write_char(':')
+        }
+    }
+
+    // JSON has no representation of NaN or infinity, so they are written as null.
+    // Debug formatting is used so whole numbers keep a decimal point, and are read back as floats.
+    fn _write_float(&mut self, float: f32) -> fmt::Result {
+        if float.is_finite() {
+            write!(self.out, "{float:?}")
+        } else {
+            self.out.write_str("null")
+        }
+    }
+
+    fn _write_double(&mut self, double: f64) -> fmt::Result {
+        if double.is_finite() {
+            write!(self.out, "{double:?}")
+        } else {
+            self.out.write_str("null")
+        }
+    }
+
+    fn _write_string(&mut self, string: &str) -> fmt::Result {
+        self.out.write_char('"')?;
+        let mut start = 0;
+        for (i, c) in string.char_indices() {
+            let escaped = match c {
+                '"' => "\\\"",
+                '\\' => "\\\\",
+                '\n' => "\\n",
+                '\r' => "\\r",
+                '\t' => "\\t",
+                '\u{08}' => "\\b",
+                '\u{0C}' => "\\f",
+                c if c < ' ' => "",
+                _ => continue,
+            };
+            self.out.write_str(&string[start..i])?;
+            if escaped.is_empty() {
+                write!(self.out, "\\u{:04x}", c as u32)?;
+            } else {
+                self.out.write_str(escaped)?;
+            }
+            start = i + c.len_utf8();
+        }
+        self.out.write_str(&string[start..])?;
+        self.out.write_char('"')
+    }
+
+    fn _write_base64(&mut self, data: &[u8]) -> fmt::Result {
+        const ALPHABET: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        self.out.write_char('"')?;
+        for chunk in data.chunks(3) {
+            let b = [
+                chunk[0],
+                chunk.get(1).copied().unwrap_or(0),
+                chunk.get(2).copied().unwrap_or(0),
+            ];
+            let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    let index = (n >> (18 - 6 * i)) & 0x3F;
+                    self.out.write_char(char::from(ALPHABET[index as usize]))?;
+                } else {
+                    self.out.write_char('=')?;
+                }
+            }
+        }
+        self.out.write_char('"')
+    }
+}
@@ -0,0 +1,265 @@
+//! Paths for finding values nested within Fleece collections.
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::fmt;
+
+use crate::alloced::AllocedValue;
+use crate::mutable::{dict::Ref, ValueSlot};
+use crate::value::PathItem;
+use crate::{Array, Dict, MutableDict, Scope, SharedKeys, Value};
+
+/// A compiled path through nested dicts and arrays, such as `people[3].address.city`.
+///
+/// - Dict keys are separated by `.`. Any `.`, `[` or `\` in a key must be escaped with `\`.
+/// - Array indices are written in brackets. Negative indices count back from the end of the
+///   array, so `[-1]` is the last element.
+/// - The path may start with `$`, which refers to the root. `$` alone is the root itself.
+///
+/// A [`KeyPath`] can be parsed once with [`KeyPath::new`], and then evaluated many times against
+/// a [`Value`], [`Dict`], [`MutableDict`] or the root of a [`Scope`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyPath {
+    components: Vec<PathComponent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathComponent {
+    Key(String),
+    Index(isize),
+}
+
+#[derive(Debug)]
+pub enum KeyPathError {
+    EmptyKey { position: usize },
+    InvalidIndex { position: usize },
+    UnclosedBracket { position: usize },
+    UnexpectedCharacter { position: usize, character: char },
+    TrailingEscape { position: usize },
+}
+
+impl KeyPath {
+    /// Parse a path, such as `people[3].address.city` or `$[-1].name`.
+    /// ## Errors
+    /// If the path is not valid. The error gives the byte position of the problem.
+    pub fn new(path: &str) -> Result<Self, KeyPathError> {
+        let mut components = Vec::new();
+        let mut chars = path.char_indices().peekable();
+        if let Some((_, '$')) = chars.peek() {
+            chars.next();
+        }
+        // The first key doesn't need to be preceded by a `.`
+        let mut expect_key = !matches!(chars.peek(), None | Some((_, '.' | '[')));
+
+        while expect_key || chars.peek().is_some() {
+            if !expect_key {
+                match chars.next() {
+                    Some((_, '.')) => {}
+                    Some((start, '[')) => {
+                        components.push(PathComponent::Index(Self::_parse_index(
+                            path, start, &mut chars,
+                        )?));
+                        continue;
+                    }
+                    Some((position, character)) => {
+                        return Err(KeyPathError::UnexpectedCharacter {
+                            position,
+                            character,
+                        })
+                    }
+                    None => unreachable!(),
+                }
+            }
+            expect_key = false;
+
+            let position = chars.peek().map_or(path.len(), |(i, _)| *i);
+            let mut key = String::new();
+            while let Some(&(index, c)) = chars.peek() {
+                match c {
+                    '.' | '[' => break,
+                    '\\' => {
+                        chars.next();
+                        let (_, escaped) = chars
+                            .next()
+                            .ok_or(KeyPathError::TrailingEscape { position: index })?;
+                        key.push(escaped);
+                    }
+                    c => {
+                        chars.next();
+                        key.push(c);
+                    }
+                }
+            }
+            if key.is_empty() {
+                return Err(KeyPathError::EmptyKey { position });
+            }
+            components.push(PathComponent::Key(key));
+        }
+
+        Ok(Self { components })
+    }
+
+    /// The components of this path, in order from the root.
+    #[must_use]
+    pub fn components(&self) -> &[PathComponent] {
+        &self.components
+    }
+
+    /// Find the value at this path, starting from `root`. Shared keys are decoded using the
+    /// [`SharedKeys`] of the [`Scope`] containing `root`.
+    /// Returns [`None`] if any component of the path does not exist.
+    #[must_use]
+    pub fn eval<'a>(&self, root: &'a Value) -> Option<&'a Value> {
+        let shared_keys = Scope::find_shared_keys(root.bytes.as_ptr());
+        self._eval(root, shared_keys.as_deref())
+    }
+
+    /// The same as [`KeyPath::eval`], starting from a [`Dict`].
+    #[must_use]
+    pub fn eval_dict<'a>(&self, root: &'a Dict) -> Option<&'a Value> {
+        self.eval(&root.array.value)
+    }
+
+    /// The same as [`KeyPath::eval`], starting from the root of a [`Scope`]. Shared keys are
+    /// decoded using the scope's [`SharedKeys`].
+    /// Returns [`None`] if the scope has no root, or any component of the path does not exist.
+    #[must_use]
+    pub fn eval_scope(&self, scope: &Scope) -> Option<AllocedValue> {
+        let root = scope.root()?;
+        let value = self._eval(&root, scope.shared_keys().map(Arc::as_ref))?;
+        Some(AllocedValue {
+            value: core::ptr::from_ref(value),
+            buf: root.buf,
+            extern_destination: root.extern_destination,
+        })
+    }
+
+    /// The same as [`KeyPath::eval`], starting from a [`MutableDict`]. The result may be a
+    /// value, or a nested [`MutableArray`](crate::MutableArray) or [`MutableDict`].
+    /// Returns [`None`] for the root path `$`, as the root is not held in a slot.
+    #[must_use]
+    pub fn eval_mutable<'a>(&self, root: &'a MutableDict) -> Option<Ref<'a>> {
+        let (first, rest) = self.components.split_first()?;
+        let PathComponent::Key(key) = first else {
+            return None;
+        };
+        let mut slot = root.get_slot(key)?;
+        for component in rest {
+            slot = match (slot, component) {
+                (ValueSlot::MutableDict(dict), PathComponent::Key(key)) => dict.get_slot(key)?,
+                (ValueSlot::MutableArray(array), PathComponent::Index(index)) => {
+                    array.get_slot(Self::_resolve_index(*index, array.len())?)?
+                }
+                // Other slots hold scalars, which have no children
+                _ => return None,
+            };
+        }
+        Some(Ref::new(slot))
+    }
+}
+
+impl KeyPath {
+    fn _eval<'a>(&self, root: &'a Value, shared_keys: Option<&SharedKeys>) -> Option<&'a Value> {
+        let mut value = root;
+        for component in &self.components {
+            value = match component {
+                PathComponent::Key(key) => {
+                    let dict = value.as_dict()?;
+                    match shared_keys {
+                        Some(shared_keys) => dict.get_with_shared_keys(key.as_str(), shared_keys),
+                        None => dict.get(key.as_str()),
+                    }?
+                }
+                PathComponent::Index(index) => {
+                    let array: &Array = value.as_array()?;
+                    array.get(Self::_resolve_index(*index, array.len())?)?
+                }
+            };
+        }
+        Some(value)
+    }
+
+    /// Convert a possibly negative index to a position in a collection of length `len`.
+    fn _resolve_index(index: isize, len: usize) -> Option<usize> {
+        if index < 0 {
+            len.checked_sub(index.unsigned_abs())
+        } else {
+            Some(index.unsigned_abs())
+        }
+    }
+
+    fn _parse_index(
+        path: &str,
+        start: usize,
+        chars: &mut core::iter::Peekable<core::str::CharIndices>,
+    ) -> Result<isize, KeyPathError> {
+        for (i, c) in chars.by_ref() {
+            if c == ']' {
+                return path[start + 1..i]
+                    .parse()
+                    .map_err(|_| KeyPathError::InvalidIndex { position: start });
+            }
+        }
+        Err(KeyPathError::UnclosedBracket { position: start })
+    }
+}
+
+impl From<&[PathItem<'_>]> for KeyPath {
+    /// Convert a path returned by a [`DeepIterator`](crate::DeepIterator) to a [`KeyPath`].
+    fn from(path: &[PathItem<'_>]) -> Self {
+        let components = path
+            .iter()
+            .map(|item| match item {
+                PathItem::Key(key) => PathComponent::Key(String::from(*key)),
+                // Indices are always much smaller than isize::MAX
+                #[allow(clippy::cast_possible_wrap)]
+                PathItem::Index(index) => PathComponent::Index(*index as isize),
+            })
+            .collect();
+        Self { components }
+    }
+}
+
+impl fmt::Display for KeyPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("$")?;
+        for component in &self.components {
+            match component {
+                PathComponent::Key(key) => {
+                    f.write_str(".")?;
+                    for c in key.chars() {
+                        if matches!(c, '.' | '[' | '\\') {
+                            f.write_str("\\")?;
+                        }
+                        write!(f, "{c}")?;
+                    }
+                }
+                PathComponent::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for KeyPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyPathError::EmptyKey { position } => write!(f, "Empty key at position {position}"),
+            KeyPathError::InvalidIndex { position } => {
+                write!(f, "Invalid array index at position {position}")
+            }
+            KeyPathError::UnclosedBracket { position } => {
+                write!(f, "Unclosed '[' at position {position}")
+            }
+            KeyPathError::UnexpectedCharacter {
+                position,
+                character,
+            } => write!(
+                f,
+                "Unexpected character {character:?} at position {position}"
+            ),
+            KeyPathError::TrailingEscape { position } => {
+                write!(f, "Nothing to escape after '\\' at position {position}")
+            }
+        }
+    }
+}
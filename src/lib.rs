@@ -2,12 +2,18 @@
 
 #[macro_use]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
 pub mod alloced;
+pub mod convert;
 #[cfg(feature = "serde")]
 mod de;
+pub mod delta;
 pub mod encoder;
 pub mod error;
+pub mod json;
+pub mod keypath;
 pub mod mutable;
 mod scope;
 #[cfg(feature = "serde")]
@@ -17,13 +23,24 @@ pub mod shared_keys;
 mod tests;
 pub mod value;
 
+pub use convert::{FromFleece, ToFleece};
 #[cfg(feature = "serde")]
 pub use de::from_bytes;
 #[cfg(feature = "serde")]
+pub use de::from_bytes_lookup_fields;
+#[cfg(feature = "serde")]
+pub use de::from_mutable;
+#[cfg(feature = "serde")]
 pub use de::Deserializer;
 pub use encoder::Encoder;
 pub use error::Error;
 pub use error::Result;
+#[cfg(feature = "derive")]
+pub use fleece_derive::{FromFleece, ToFleece};
+pub use json::{
+    from_json, from_json5, to_json, to_json_pretty, to_json_writer, to_json_writer_pretty,
+};
+pub use keypath::KeyPath;
 pub use mutable::MutableArray;
 pub use mutable::MutableDict;
 pub use scope::Scope;
@@ -32,10 +49,13 @@ pub use ser::to_bytes;
 #[cfg(feature = "serde")]
 pub use ser::to_bytes_with_shared_keys;
 #[cfg(feature = "serde")]
+pub use ser::to_mutable;
+#[cfg(feature = "serde")]
 pub use ser::Serializer;
 pub use shared_keys::SharedKeys;
 pub use value::array::Array;
 pub use value::dict::Dict;
+pub use value::DeepIterator;
 pub use value::Value;
 pub use value::ValueType;
 
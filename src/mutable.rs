@@ -8,13 +8,13 @@ pub use dict::MutableDict;
 
 use crate::{
     encoder::{Encodable, NullValue, UndefinedValue},
-    value, Value,
+    value, Encoder, Value,
 };
 
 const INLINE_CAPACITY: usize = 15;
 
 #[derive(Debug)]
-enum ValueSlot {
+pub(crate) enum ValueSlot {
     Inline([u8; INLINE_CAPACITY]),
     Pointer(Box<Value>),
     MutableArray(Box<MutableArray>),
@@ -22,10 +22,24 @@ enum ValueSlot {
 }
 
 impl ValueSlot {
-    pub fn new<T>(value: T) -> Self
+    /// ## Panics
+    /// If `value` is a collection which fails to encode. This can only happen if a
+    /// [`ToFleece`](crate::ToFleece) implementation writes unbalanced collections.
+    pub fn new<T>(value: &T) -> Self
     where
-        T: Encodable,
+        T: Encodable + ?Sized,
     {
+        // Collections are written with `Encodable::write_to`, and have no size as a single value.
+        // Encode them, and copy the result into a mutable collection.
+        if value.fleece_size() == 0 {
+            let mut encoder = Encoder::new();
+            value
+                .write_to(&mut encoder)
+                .expect("Failed to encode a collection");
+            let data = encoder.finish();
+            let root = Value::from_bytes(&data).expect("Failed to encode a collection");
+            return Self::new_from_fleece(root, false);
+        }
         if value.fleece_size() <= INLINE_CAPACITY {
             let mut buf = [0u8; INLINE_CAPACITY];
             value.write_fleece_to(&mut buf, false);
@@ -39,15 +53,17 @@ impl ValueSlot {
 
     pub fn new_from_fleece(value: &Value, is_wide: bool) -> Self {
         match value.value_type() {
-            crate::ValueType::Null => Self::new(NullValue),
-            crate::ValueType::Undefined => Self::new(UndefinedValue),
-            crate::ValueType::False => Self::new(false),
-            crate::ValueType::True => Self::new(true),
-            crate::ValueType::Short => Self::new(value.to_short()),
-            crate::ValueType::Int => Self::new(value.to_int()),
-            crate::ValueType::UnsignedInt => Self::new(value.to_unsigned_int()),
-            crate::ValueType::Float => Self::new(value.to_float()),
-            crate::ValueType::Double32 | crate::ValueType::Double64 => Self::new(value.to_double()),
+            crate::ValueType::Null => Self::new(&NullValue),
+            crate::ValueType::Undefined => Self::new(&UndefinedValue),
+            crate::ValueType::False => Self::new(&false),
+            crate::ValueType::True => Self::new(&true),
+            crate::ValueType::Short => Self::new(&value.to_short()),
+            crate::ValueType::Int => Self::new(&value.to_int()),
+            crate::ValueType::UnsignedInt => Self::new(&value.to_unsigned_int()),
+            crate::ValueType::Float => Self::new(&value.to_float()),
+            crate::ValueType::Double32 | crate::ValueType::Double64 => {
+                Self::new(&value.to_double())
+            }
             crate::ValueType::String => Self::new(value.to_str()),
             crate::ValueType::Data => Self::new(value.to_data()),
             crate::ValueType::Array => {
@@ -5,7 +5,7 @@ use alloc::vec::Vec;
 use crate::{
     alloced::{AllocedArray, AllocedValue},
     encoder::Encodable,
-    Array, Scope, Value,
+    Array, Encoder, Scope, Value,
 };
 
 use super::{MutableDict, ValueSlot};
@@ -48,6 +48,10 @@ impl MutableArray {
         self.list.get(index).and_then(ValueSlot::value)
     }
 
+    pub(crate) fn get_slot(&self, index: usize) -> Option<&ValueSlot> {
+        self.list.get(index)
+    }
+
     pub fn get_array(&self, index: usize) -> Option<&MutableArray> {
         self.list.get(index).and_then(ValueSlot::array)
     }
@@ -76,13 +80,16 @@ impl MutableArray {
         self.list.is_empty()
     }
 
+    /// Set the entry at `index` to the given value. Collections are copied into a nested
+    /// [`MutableArray`] or [`MutableDict`], as in [`MutableArray::push`].
     /// # Panics
-    /// Panics if `index >= len`
+    /// Panics if `index >= len`, or if `value` is a collection which fails to encode.
+    #[allow(clippy::needless_pass_by_value)]
     pub fn set<T>(&mut self, index: usize, value: T)
     where
         T: Encodable,
     {
-        let slot = ValueSlot::new(value);
+        let slot = ValueSlot::new(&value);
         self.replace(index, slot);
     }
 
@@ -101,6 +108,11 @@ impl MutableArray {
         self.replace(index, slot);
     }
 
+    /// Append the given value. Collections, such as a `Vec` or a Fleece [`Array`](crate::Array),
+    /// are copied into a nested [`MutableArray`] or [`MutableDict`].
+    /// # Panics
+    /// If `value` is a collection which fails to encode, which can only happen if a
+    /// [`ToFleece`](crate::ToFleece) implementation writes unbalanced collections.
     pub fn push<T>(&mut self, value: &T)
     where
         T: Encodable + ?Sized,
@@ -114,6 +126,11 @@ impl MutableArray {
         self.list.push(slot);
     }
 
+    #[cfg(feature = "serde")]
+    pub(crate) fn push_slot(&mut self, slot: ValueSlot) {
+        self.list.push(slot);
+    }
+
     pub fn remove(&mut self, index: usize) {
         if index >= self.list.len() {
             return;
@@ -123,10 +140,21 @@ impl MutableArray {
     }
 
     #[must_use]
-    pub fn iter(&self) -> Iter {
+    pub fn iter(&self) -> Iter<'_> {
         self.into_iter()
     }
 
+    /// Encode this array (and all nested collections) to Fleece.
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder
+            .write_mutable_array(self)
+            .expect("writing an array to an empty Encoder can't fail");
+        encoder.finish()
+    }
+
     #[inline]
     fn replace(&mut self, index: usize, slot: ValueSlot) {
         let _ = core::mem::replace(&mut self.list[index], slot);
@@ -150,6 +178,10 @@ impl<'a> Ref<'a> {
         Self { slot }
     }
 
+    pub(crate) fn slot(&self) -> &'a ValueSlot {
+        self.slot
+    }
+
     #[must_use]
     pub fn is_value(&self) -> bool {
         self.slot.is_value()
@@ -196,7 +228,7 @@ impl<'a> Iterator for Iter<'a> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let len = self.arr.len();
+        let len = self.arr.len().saturating_sub(self.index);
         (len, Some(len))
     }
 }
@@ -2,13 +2,14 @@ use alloc::{
     collections::{btree_map, BTreeMap},
     string::{String, ToString},
     sync::Arc,
+    vec::Vec,
 };
 use core::{cmp, ops::Index};
 
 use crate::{
     alloced::{AllocedDict, AllocedValue},
     encoder::Encodable,
-    Dict, Scope, SharedKeys, Value, ValueType,
+    Dict, Encoder, Scope, SharedKeys, Value, ValueType,
 };
 
 use super::{MutableArray, ValueSlot};
@@ -86,6 +87,11 @@ impl MutableDict {
         self.map.get(&encoded_key).and_then(ValueSlot::value)
     }
 
+    pub(crate) fn get_slot<'r>(&'r self, key: &str) -> Option<&'r ValueSlot> {
+        let encoded_key = self.encode_key(key);
+        self.map.get(&encoded_key)
+    }
+
     #[must_use]
     pub fn get_array<'r>(&'r self, key: &str) -> Option<&'r MutableArray> {
         let encoded_key = self.encode_key(key);
@@ -120,13 +126,23 @@ impl MutableDict {
     }
 
     /// Set a key in the dictionary to the given value. This inserts if it doesn't exist, or updates if it does.
-    /// Accepts any [`Encodable`] value, and encodes it to Fleece.
+    /// Accepts any [`Encodable`] value, and encodes it to Fleece. Collections, such as a `Vec` or
+    /// a Fleece [`Dict`](crate::Dict), are copied into a nested [`MutableArray`] or [`MutableDict`].
+    /// # Panics
+    /// If `value` is a collection which fails to encode, which can only happen if a
+    /// [`ToFleece`](crate::ToFleece) implementation writes unbalanced collections.
+    #[allow(clippy::needless_pass_by_value)]
     pub fn insert<T>(&mut self, key: &str, value: T)
     where
         T: Encodable,
     {
         let encoded_key = self.encode_key(key);
-        let slot = ValueSlot::new(value);
+        let slot = ValueSlot::new(&value);
+        self.map.insert(encoded_key, slot);
+    }
+
+    pub(crate) fn insert_slot(&mut self, key: &str, slot: ValueSlot) {
+        let encoded_key = self.encode_key(key);
         self.map.insert(encoded_key, slot);
     }
 
@@ -149,11 +165,100 @@ impl MutableDict {
         self.map.remove(&encoded_key);
     }
 
+    /// Apply a JSON Merge Patch ([RFC 7396](https://www.rfc-editor.org/rfc/rfc7396)) to this dict.
+    /// For each key in `patch`:
+    /// - A `null` value removes the key from this dict.
+    /// - A dict value is merged recursively into the dict at that key. If there isn't a dict at
+    ///   that key, it is merged into a new, empty dict.
+    /// - Any other value replaces the value at that key.
+    ///
+    /// Keys which aren't in `patch` are left untouched. If `patch` is not a dict, it would replace
+    /// this dict entirely, which isn't possible in place, so it is ignored.
+    pub fn merge_patch(&mut self, patch: &Value) {
+        let Some(patch) = patch.as_dict() else {
+            return;
+        };
+        let is_wide = patch.is_wide();
+        for (key, value) in patch {
+            match value.value_type() {
+                ValueType::Null => self.remove(key),
+                ValueType::Undefined => {}
+                ValueType::Dict => {
+                    if let Some(dict) = self.get_dict_mut(key) {
+                        dict.merge_patch(value);
+                    } else {
+                        // Merging into an empty dict is the patch without its nulls, so copy the
+                        // patch once rather than inserting each of its values
+                        let mut slot = ValueSlot::new_from_fleece(value, is_wide);
+                        if let Some(dict) = slot.dict_mut() {
+                            dict._remove_nulls();
+                        }
+                        self.insert_slot(key, slot);
+                    }
+                }
+                _ => {
+                    let encoded_key = self.encode_key(key);
+                    self.map
+                        .insert(encoded_key, ValueSlot::new_from_fleece(value, is_wide));
+                }
+            }
+        }
+    }
+
+    /// Remove the keys with a `null` (or undefined) value from this dict, and any dicts nested in
+    /// it, as a merge patch would.
+    fn _remove_nulls(&mut self) {
+        self.map.retain(|_, slot| {
+            if let Some(dict) = slot.dict_mut() {
+                dict._remove_nulls();
+            }
+            !slot
+                .value()
+                .is_some_and(|v| matches!(v.value_type(), ValueType::Null | ValueType::Undefined))
+        });
+    }
+
     #[must_use]
-    pub fn iter(&self) -> Iter {
+    pub fn iter(&self) -> Iter<'_> {
         self.into_iter()
     }
 
+    /// The [`SharedKeys`] used to encode the keys of this dict, if any.
+    #[inline]
+    #[must_use]
+    pub fn shared_keys(&self) -> Option<&Arc<SharedKeys>> {
+        self.shared_keys.as_ref()
+    }
+
+    /// Encode this dict (and all nested collections) to Fleece. Keys are written as plain
+    /// strings, so the result can be read without [`SharedKeys`].
+    /// Use [`MutableDict::encode_scoped`] to encode with this dict's [`SharedKeys`].
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder
+            .write_mutable_dict(self)
+            .expect("writing a dict to an empty Encoder can't fail");
+        encoder.finish()
+    }
+
+    /// Encode this dict (and all nested collections) to Fleece, using this dict's
+    /// [`SharedKeys`] (if any). Keys which are not yet shared will be added to a copy of the
+    /// [`SharedKeys`], which is retained by the returned [`Scope`].
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn encode_scoped(&self) -> Arc<Scope> {
+        let mut encoder = Encoder::new();
+        if let Some(shared_keys) = &self.shared_keys {
+            encoder.set_shared_keys(SharedKeys::clone(shared_keys));
+        }
+        encoder
+            .write_mutable_dict(self)
+            .expect("writing a dict to an empty Encoder can't fail");
+        encoder.finish_scoped()
+    }
+
     /// Attempts to encode `key` with `SharedKeys`, and return a `Key::Shared`.
     /// Otherwise returns a `Key::String`.
     fn encode_key(&self, key: &str) -> Key {
@@ -182,10 +287,14 @@ pub struct Ref<'a> {
 }
 
 impl<'a> Ref<'a> {
-    fn new(slot: &'a ValueSlot) -> Self {
+    pub(crate) fn new(slot: &'a ValueSlot) -> Self {
         Self { slot }
     }
 
+    pub(crate) fn slot(&self) -> &'a ValueSlot {
+        self.slot
+    }
+
     #[must_use]
     pub fn is_value(&self) -> bool {
         self.slot.is_value()
@@ -1,5 +1,6 @@
 use alloc::sync::{Arc, Weak};
-use core::{ops::Deref, ptr::NonNull};
+use alloc::vec::Vec;
+use core::ptr::NonNull;
 use crossbeam_utils::sync::ShardedLock;
 use lazy_static::lazy_static;
 use rangemap::RangeMap;
@@ -11,15 +12,22 @@ pub struct Scope {
     shared_keys: Option<Arc<SharedKeys>>,
     weak_data: Weak<[u8]>,
     strong_data: Option<Arc<[u8]>>,
+    extern_destination: Option<Arc<[u8]>>,
     root: Option<NonNull<Value>>,
 }
 
 impl Scope {
+    // Will not panic, because `read` only fails if the lock was poisoned.
+    // The lock is only poisoned if a write operation panics. We don't have any panics.
+    #[allow(clippy::missing_panics_doc)]
     /// Find [`SharedKeys`] which are held by some [`Scope`] containing the given data.
     #[inline]
     #[must_use]
     pub fn find_shared_keys(containing_data: *const u8) -> Option<Arc<SharedKeys>> {
-        Scope::containing(containing_data).and_then(|s| s.shared_keys.clone())
+        // Extern destinations are registered too, so that Dicts in them can find the SharedKeys
+        let scope_map = SCOPE_MAP.read().unwrap();
+        let entry = scope_map.get(&(containing_data as usize))?;
+        entry.scope.upgrade().and_then(|s| s.shared_keys.clone())
     }
 
     #[must_use]
@@ -38,12 +46,22 @@ impl Scope {
         }
     }
 
+    /// The data which extern pointers in this scope's data point into, if any.
+    #[must_use]
+    #[inline]
+    pub fn extern_destination(&self) -> Option<&Arc<[u8]>> {
+        self.extern_destination.as_ref()
+    }
+
     /// The root [`Value`] contained in the data retained by this scope. Returns [`None`] if the data has been deallocated.
+    /// The returned value also retains the extern destination, so extern pointers in it can still
+    /// be read once this scope is removed or dropped.
     #[must_use]
     pub fn root(&self) -> Option<AllocedValue> {
         self.data().and_then(|data| {
             self.root.map(|root| AllocedValue {
                 buf: data,
+                extern_destination: self.extern_destination.clone(),
                 value: root.as_ptr(),
             })
         })
@@ -68,30 +86,76 @@ impl Scope {
         if let Some(range) = self.range() {
             scope_map.remove(range);
         }
+        if let Some(extern_destination) = &self.extern_destination {
+            let owned: Vec<_> = scope_map
+                .overlapping(&_range_of(extern_destination))
+                .filter(|(_, entry)| core::ptr::eq(entry.scope.as_ptr(), self))
+                .map(|(range, _)| range.clone())
+                .collect();
+            for range in owned {
+                scope_map.remove(range);
+            }
+        }
+    }
+
+    /// Create a new scope which retains the data it is given ownership of, and optionally retains the given [`SharedKeys`].
+    pub fn new(data: impl Into<Arc<[u8]>>, shared_keys: Option<Arc<SharedKeys>>) -> Arc<Self> {
+        Self::_new(data.into(), shared_keys, None)
+    }
+
+    /// Like [`Scope::new`], but also retains an extern destination. Extern pointers in `data` are
+    /// resolved relative to the end of `extern_destination`, as is done for Fleece documents
+    /// which are appended to another document.
+    pub fn new_with_extern(
+        data: impl Into<Arc<[u8]>>,
+        shared_keys: Option<Arc<SharedKeys>>,
+        extern_destination: impl Into<Arc<[u8]>>,
+    ) -> Arc<Self> {
+        Self::_new(data.into(), shared_keys, Some(extern_destination.into()))
     }
 
     // Will not panic, because `write` only fails if the lock was poisoned.
     // The lock is only poisoned if a write operation panics. We don't have any panics.
     #[allow(clippy::missing_panics_doc)]
-    /// Create a new scope which retains the data it is given ownership of, and optionally retains the given [`SharedKeys`].
-    pub fn new(data: impl Into<Arc<[u8]>>, shared_keys: Option<Arc<SharedKeys>>) -> Arc<Self> {
-        let mut scope_map = SCOPE_MAP.write().unwrap();
-        let strong_data = data.into();
+    fn _new(
+        strong_data: Arc<[u8]>,
+        shared_keys: Option<Arc<SharedKeys>>,
+        extern_destination: Option<Arc<[u8]>>,
+    ) -> Arc<Self> {
         let weak_data = Arc::downgrade(&strong_data);
 
         let start = strong_data.as_ptr() as usize;
         let end = start + strong_data.len();
 
+        // Register the extern destination first, as validation resolves extern pointers with it
+        if let Some(extern_destination) = &extern_destination {
+            _register_extern_destination(&strong_data, extern_destination);
+        }
+        // Find the root before taking the lock, as validation may need to read the scope map.
         let root = Self::root_or_none(&strong_data);
 
         let scope = Arc::new(Scope {
             shared_keys,
             weak_data,
             strong_data: Some(strong_data),
+            extern_destination,
             root,
         });
 
-        scope_map.insert(start..end, ScopeEntry(Arc::downgrade(&scope)));
+        let mut scope_map = SCOPE_MAP.write().unwrap();
+        scope_map.insert(start..end, ScopeEntry::new(&scope, false));
+        // Register the extern destination too, so that Dicts in it can find the SharedKeys. If
+        // it is already part of a live Scope, that Scope is left to describe it.
+        if let Some(extern_destination) = &scope.extern_destination {
+            let range = _range_of(extern_destination);
+            if !range.is_empty()
+                && !scope_map
+                    .overlapping(&range)
+                    .any(|(_, entry)| entry.scope.strong_count() > 0)
+            {
+                scope_map.insert(range, ScopeEntry::new(&scope, true));
+            }
+        }
         scope
     }
 
@@ -107,8 +171,49 @@ impl Scope {
     pub fn containing(data: *const u8) -> Option<Arc<Self>> {
         let scope_map = SCOPE_MAP.read().unwrap();
         let entry = scope_map.get(&(data as usize))?;
-        entry.upgrade()
+        if entry.is_extern_destination {
+            return None;
+        }
+        entry.scope.upgrade()
+    }
+
+    /// Find the start of the data containing `data`, and the extern destination which extern
+    /// pointers in that data point into. This doesn't depend on a [`Scope`], so it is found as
+    /// long as both are still allocated, even once the [`Scope`] which registered them is removed
+    /// or dropped.
+    #[must_use]
+    pub(crate) fn find_extern_destination(data: *const u8) -> Option<(*const u8, Arc<[u8]>)> {
+        let extern_map = EXTERN_MAP.read().unwrap();
+        let entry = extern_map.get(&(data as usize))?;
+        // If the data has been deallocated, the range may have been reused by unrelated data
+        let data = entry.data.upgrade()?;
+        Some((data.as_ptr(), entry.extern_destination.upgrade()?))
+    }
+}
+
+fn _range_of(data: &[u8]) -> core::ops::Range<usize> {
+    let start = data.as_ptr() as usize;
+    start..start + data.len()
+}
+
+fn _register_extern_destination(data: &Arc<[u8]>, extern_destination: &Arc<[u8]>) {
+    let mut extern_map = EXTERN_MAP.write().unwrap();
+    // Forget data which has since been deallocated, as its range may be reused
+    let deallocated: Vec<_> = extern_map
+        .iter()
+        .filter(|(_, entry)| entry.data.strong_count() == 0)
+        .map(|(range, _)| range.clone())
+        .collect();
+    for range in deallocated {
+        extern_map.remove(range);
     }
+    extern_map.insert(
+        _range_of(data),
+        ExternEntry {
+            data: Arc::downgrade(data),
+            extern_destination: Arc::downgrade(extern_destination),
+        },
+    );
 }
 
 impl PartialEq for Scope {
@@ -126,35 +231,58 @@ impl PartialEq for Scope {
 impl Eq for Scope {}
 
 #[derive(Clone)]
-struct ScopeEntry(Weak<Scope>);
+struct ScopeEntry {
+    scope: Weak<Scope>,
+    // Whether this is the extern destination of the scope, rather than its data. These are only
+    // registered so that SharedKeys can be found for them.
+    is_extern_destination: bool,
+}
+
+impl ScopeEntry {
+    fn new(scope: &Arc<Scope>, is_extern_destination: bool) -> Self {
+        Self {
+            scope: Arc::downgrade(scope),
+            is_extern_destination,
+        }
+    }
+}
 
 impl PartialEq for ScopeEntry {
     fn eq(&self, other: &Self) -> bool {
-        let Some(self_scope) = self.0.upgrade() else {
+        let Some(self_scope) = self.scope.upgrade() else {
             return false;
         };
-        let Some(other_scope) = other.0.upgrade() else {
+        let Some(other_scope) = other.scope.upgrade() else {
             return false;
         };
-        self_scope.eq(&other_scope)
+        self.is_extern_destination == other.is_extern_destination && self_scope.eq(&other_scope)
     }
 }
 
 impl Eq for ScopeEntry {}
 
-impl Deref for ScopeEntry {
-    type Target = Weak<Scope>;
+#[derive(Clone)]
+struct ExternEntry {
+    data: Weak<[u8]>,
+    extern_destination: Weak<[u8]>,
+}
 
-    #[inline]
-    fn deref(&self) -> &Self::Target {
-        &self.0
+impl PartialEq for ExternEntry {
+    fn eq(&self, other: &Self) -> bool {
+        Weak::ptr_eq(&self.data, &other.data)
+            && Weak::ptr_eq(&self.extern_destination, &other.extern_destination)
     }
 }
 
+impl Eq for ExternEntry {}
+
 unsafe impl Send for Scope {}
 unsafe impl Sync for Scope {}
 
 lazy_static! {
     static ref SCOPE_MAP: ShardedLock<RangeMap<usize, ScopeEntry>> =
         ShardedLock::new(RangeMap::new());
+    // Kept apart from `SCOPE_MAP`, as values read from a Scope's data can outlive the Scope.
+    static ref EXTERN_MAP: ShardedLock<RangeMap<usize, ExternEntry>> =
+        ShardedLock::new(RangeMap::new());
 }
@@ -1,13 +1,18 @@
-use alloc::string::ToString;
+use alloc::string::{String, ToString};
 use alloc::{sync::Arc, vec::Vec};
+#[cfg(feature = "std")]
+use core::cell::Cell;
 use core::fmt;
 
 use serde::ser;
 use serde::ser::{Impossible, SerializeMap, SerializeSeq, SerializeTuple};
 
-use crate::encoder::{EncodeError, NullValue, UndefinedValue};
+use crate::alloced::Alloced;
+use crate::encoder::{NullValue, UndefinedValue};
+use crate::mutable::ValueSlot;
 use crate::scope::Scope;
-use crate::{Encoder, SharedKeys};
+use crate::value::pointer::Pointer;
+use crate::{Array, Dict, Encoder, MutableArray, MutableDict, SharedKeys, Value, ValueType};
 use crate::{Error, Result};
 
 pub struct Serializer {
@@ -26,13 +31,12 @@ where
     T: ser::Serialize,
 {
     let mut serializer = Serializer::new();
-    match value.serialize(&mut serializer) {
-        Ok(()) => Ok(serializer.encoder.finish()),
-        Err(Error::Encode(EncodeError::CollectionNotOpen)) => {
-            Err(Error::Serialize(SerializeError::ValueNotCollection))
-        }
-        Err(other) => Err(other),
+    value.serialize(&mut serializer)?;
+    let data = serializer.encoder.finish();
+    if !is_collection_root(&data) {
+        return Err(Error::Serialize(SerializeError::ValueNotCollection));
     }
+    Ok(data)
 }
 
 /// Serialize the given value into Fleece, using [`SharedKeys`].
@@ -48,19 +52,45 @@ where
 {
     let mut serializer = Serializer::new();
     serializer.set_shared_keys(SharedKeys::new());
-    match value.serialize(&mut serializer) {
-        Ok(()) => Ok(serializer.encoder.finish_scoped()),
-        Err(Error::Encode(EncodeError::CollectionNotOpen)) => {
-            Err(Error::Serialize(SerializeError::ValueNotCollection))
-        }
-        Err(other) => Err(other),
+    value.serialize(&mut serializer)?;
+    let scope = serializer.encoder.finish_scoped();
+    if !scope.data().is_some_and(|data| is_collection_root(&data)) {
+        return Err(Error::Serialize(SerializeError::ValueNotCollection));
+    }
+    Ok(scope)
+}
+
+/// Serialize the given value into a new [`MutableDict`], without encoding it to Fleece.
+/// The `value` parameter must be a map or non-unit struct.
+/// Maps must have string (or char) keys.
+/// # Errors
+/// - Map keys which are not Strings.
+/// - If the `value` is not some sort of map or non-unit struct.
+pub fn to_mutable<T>(value: &T) -> Result<MutableDict>
+where
+    T: ser::Serialize + ?Sized,
+{
+    match value.serialize(MutableSerializer)? {
+        ValueSlot::MutableDict(dict) => Ok(*dict),
+        _ => Err(Error::Serialize(SerializeError::ValueNotDict)),
     }
 }
 
+// The Encoder allows a scalar root value, but serialized values must be collections
+fn is_collection_root(data: &[u8]) -> bool {
+    // The data was just written by the Encoder, so it is valid
+    !data.is_empty()
+        && matches!(
+            unsafe { Value::from_bytes_unchecked(data) }.value_type(),
+            ValueType::Array | ValueType::Dict
+        )
+}
+
 #[derive(Debug)]
 pub enum SerializeError {
     KeyNotString(KeyType),
     ValueNotCollection,
+    ValueNotDict,
 }
 
 impl fmt::Display for SerializeError {
@@ -73,6 +103,9 @@ impl fmt::Display for SerializeError {
                 f,
                 "The value parameter must be an enum, sequence, map or non-unit struct"
             ),
+            SerializeError::ValueNotDict => {
+                write!(f, "The value parameter must be a map or non-unit struct")
+            }
         }
     }
 }
@@ -89,7 +122,7 @@ impl Serializer {
     }
 }
 
-impl<'ser> serde::Serializer for &'ser mut Serializer {
+impl serde::Serializer for &mut Serializer {
     type Ok = ();
     type Error = Error;
     type SerializeSeq = Self;
@@ -192,10 +225,20 @@ impl<'ser> serde::Serializer for &'ser mut Serializer {
         self.encoder.end_array().map_err(Error::Encode)
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + ser::Serialize,
     {
+        #[cfg(feature = "std")]
+        if name == RAW_VALUE_TOKEN {
+            if let Some(raw_value) = RawValue::take() {
+                // SAFETY: The Value was recorded by a `Value::serialize` call which hasn't
+                // returned yet, so it is still borrowed.
+                let raw_value = unsafe { &*raw_value };
+                return self.encoder.write_fleece(raw_value).map_err(Error::Encode);
+            }
+        }
         ser::Serialize::serialize(value, self)
     }
 
@@ -278,7 +321,7 @@ impl<'ser> serde::Serializer for &'ser mut Serializer {
     }
 }
 
-impl<'ser> SerializeSeq for &'ser mut Serializer {
+impl SerializeSeq for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -309,11 +352,22 @@ pub enum KeyType {
     Map,
 }
 
-struct MapKeySerializer<'ser> {
-    ser: &'ser mut Serializer,
+// Where a `MapKeySerializer` writes the key
+trait KeySink {
+    fn write_key(&mut self, key: &str) -> Result<()>;
+}
+
+impl KeySink for Serializer {
+    fn write_key(&mut self, key: &str) -> Result<()> {
+        Ok(self.encoder.write_key(key)?)
+    }
+}
+
+struct MapKeySerializer<'ser, K: KeySink> {
+    sink: &'ser mut K,
 }
 
-impl<'ser> serde::Serializer for MapKeySerializer<'ser> {
+impl<K: KeySink> serde::Serializer for MapKeySerializer<'_, K> {
     type Ok = ();
     type Error = Error;
     type SerializeSeq = Impossible<(), Error>;
@@ -380,7 +434,7 @@ impl<'ser> serde::Serializer for MapKeySerializer<'ser> {
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok> {
-        Ok(self.ser.encoder.write_key(v)?)
+        self.sink.write_key(v)
     }
 
     fn serialize_bytes(self, _: &[u8]) -> Result<Self::Ok> {
@@ -504,7 +558,7 @@ impl<'ser> serde::Serializer for MapKeySerializer<'ser> {
     }
 }
 
-impl<'ser> SerializeMap for &'ser mut Serializer {
+impl SerializeMap for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -512,7 +566,7 @@ impl<'ser> SerializeMap for &'ser mut Serializer {
     where
         T: ?Sized + ser::Serialize,
     {
-        ser::Serialize::serialize(key, MapKeySerializer { ser: self })
+        ser::Serialize::serialize(key, MapKeySerializer { sink: &mut **self })
     }
 
     fn serialize_value<T>(&mut self, value: &T) -> Result<()>
@@ -527,7 +581,7 @@ impl<'ser> SerializeMap for &'ser mut Serializer {
     }
 }
 
-impl<'ser> SerializeTuple for &'ser mut Serializer {
+impl SerializeTuple for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -543,7 +597,7 @@ impl<'ser> SerializeTuple for &'ser mut Serializer {
     }
 }
 
-impl<'ser> ser::SerializeTupleStruct for &'ser mut Serializer {
+impl ser::SerializeTupleStruct for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -559,7 +613,7 @@ impl<'ser> ser::SerializeTupleStruct for &'ser mut Serializer {
     }
 }
 
-impl<'ser> ser::SerializeTupleVariant for &'ser mut Serializer {
+impl ser::SerializeTupleVariant for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -576,7 +630,7 @@ impl<'ser> ser::SerializeTupleVariant for &'ser mut Serializer {
     }
 }
 
-impl<'ser> ser::SerializeStruct for &'ser mut Serializer {
+impl ser::SerializeStruct for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -593,7 +647,7 @@ impl<'ser> ser::SerializeStruct for &'ser mut Serializer {
     }
 }
 
-impl<'ser> ser::SerializeStructVariant for &'ser mut Serializer {
+impl ser::SerializeStructVariant for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -609,3 +663,515 @@ impl<'ser> ser::SerializeStructVariant for &'ser mut Serializer {
         self.encoder.end_array().map_err(Error::Encode)
     }
 }
+
+// With the `std` feature, a Value is serialized as a newtype struct with this name, containing
+// its contents. While it is being serialized, the Value is recorded in `RAW_VALUE`, so the Fleece
+// `Serializer` can copy it with `Encoder::write_fleece` rather than visiting it. Other serializers
+// just see the contents. Any `Serialize` impl can use this name, so it only matters if a Value was
+// recorded. Without `std` there is nowhere to record the Value, so it is serialized as its
+// contents, which the Fleece `Serializer` encodes to the same bytes.
+#[cfg(feature = "std")]
+const RAW_VALUE_TOKEN: &str = "$fleece::private::RawValue";
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static RAW_VALUE: Cell<Option<*const Value>> = const { Cell::new(None) };
+}
+
+/// Records a Value in `RAW_VALUE` while it is being serialized. Dropping it restores the Value
+/// recorded by the enclosing `Value::serialize` call, even if serialization panics, so only
+/// Values which are still borrowed are ever recorded.
+#[cfg(feature = "std")]
+struct RawValue {
+    previous: Option<*const Value>,
+}
+
+#[cfg(feature = "std")]
+impl RawValue {
+    fn record(value: &Value) -> Self {
+        Self {
+            previous: RAW_VALUE.replace(Some(core::ptr::from_ref(value))),
+        }
+    }
+
+    /// Take the Value recorded by the innermost `Value::serialize` call, if it hasn't been taken
+    /// already. It can only be dereferenced until that call returns.
+    fn take() -> Option<*const Value> {
+        RAW_VALUE.take()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for RawValue {
+    fn drop(&mut self) {
+        RAW_VALUE.set(self.previous);
+    }
+}
+
+#[cfg(feature = "std")]
+struct ValueContents<'a>(&'a Value);
+
+#[cfg(feature = "std")]
+impl ser::Serialize for ValueContents<'_> {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serialize_value_contents(self.0, serializer)
+    }
+}
+
+fn serialize_value_contents<S>(
+    value: &Value,
+    serializer: S,
+) -> core::result::Result<S::Ok, S::Error>
+where
+    S: ser::Serializer,
+{
+    match value.value_type() {
+        ValueType::Null => serializer.serialize_none(),
+        ValueType::Undefined => serializer.serialize_unit(),
+        ValueType::False | ValueType::True => serializer.serialize_bool(value.to_bool()),
+        ValueType::Short | ValueType::Int => serializer.serialize_i64(value.to_int()),
+        ValueType::UnsignedInt => serializer.serialize_u64(value.to_unsigned_int()),
+        ValueType::Float => serializer.serialize_f32(value.to_float()),
+        ValueType::Double32 | ValueType::Double64 => serializer.serialize_f64(value.to_double()),
+        ValueType::String => serializer.serialize_str(value.to_str()),
+        ValueType::Data => serializer.serialize_bytes(value.to_data()),
+        ValueType::Array => serializer.collect_seq(Array::from_value(value)),
+        ValueType::Dict => serializer.collect_map(Dict::from_value(value)),
+        ValueType::Pointer => serialize_value_contents(
+            unsafe { Pointer::from_value(value).deref_unchecked(false) },
+            serializer,
+        ),
+    }
+}
+
+/// Values are serialized as their contents. With the `std` feature, the Fleece serializer
+/// ([`to_bytes`] and friends) copies them with [`Encoder::write_fleece`] instead, which is faster
+/// for large collections but gives the same output.
+impl ser::Serialize for Value {
+    #[cfg(feature = "std")]
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let _recorded = RawValue::record(self);
+        serializer.serialize_newtype_struct(RAW_VALUE_TOKEN, &ValueContents(self))
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serialize_value_contents(self, serializer)
+    }
+}
+
+impl ser::Serialize for Array {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        self.value.serialize(serializer)
+    }
+}
+
+impl ser::Serialize for Dict {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        self.array.value.serialize(serializer)
+    }
+}
+
+impl<T: ser::Serialize + ?Sized> ser::Serialize for Alloced<T> {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        self.value().serialize(serializer)
+    }
+}
+
+impl ser::Serialize for ValueSlot {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match self {
+            ValueSlot::MutableArray(array) => array.serialize(serializer),
+            ValueSlot::MutableDict(dict) => dict.serialize(serializer),
+            // Inline and pointer slots always hold a Value
+            ValueSlot::Inline(_) | ValueSlot::Pointer(_) => {
+                self.value().unwrap().serialize(serializer)
+            }
+        }
+    }
+}
+
+impl ser::Serialize for MutableArray {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.collect_seq(self.iter().map(|item| item.slot()))
+    }
+}
+
+impl ser::Serialize for MutableDict {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.collect_map(self.iter().map(|(key, item)| (key, item.slot())))
+    }
+}
+
+// Serializes values into `ValueSlot`s, building mutable collections rather than encoding them.
+// Enums have the same layout as with the Fleece `Serializer`.
+struct MutableSerializer;
+
+// Array [ VARIANT_NAME ] or Array [ VARIANT_NAME, VARIANT_DATA ]
+fn variant_array(variant: &'static str, data: Option<ValueSlot>) -> ValueSlot {
+    let mut array = MutableArray::new();
+    array.push(variant);
+    if let Some(data) = data {
+        array.push_slot(data);
+    }
+    ValueSlot::new_array(array)
+}
+
+impl serde::Serializer for MutableSerializer {
+    type Ok = ValueSlot;
+    type Error = Error;
+    type SerializeSeq = MutableArraySerializer;
+    type SerializeTuple = MutableArraySerializer;
+    type SerializeTupleStruct = MutableArraySerializer;
+    type SerializeTupleVariant = MutableArraySerializer;
+    type SerializeMap = MutableDictSerializer;
+    type SerializeStruct = MutableDictSerializer;
+    type SerializeStructVariant = MutableDictSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(&v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(&*v.encode_utf8(&mut [0; 4])))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(v))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(&NullValue))
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        Ok(ValueSlot::new(&UndefinedValue))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok> {
+        Ok(variant_array(variant, None))
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        Ok(variant_array(variant, Some(value.serialize(self)?)))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(MutableArraySerializer {
+            array: MutableArray::new(),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Ok(MutableArraySerializer {
+            array: MutableArray::new(),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(MutableDictSerializer {
+            dict: MutableDict::new(),
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(MutableDictSerializer {
+            dict: MutableDict::new(),
+            key: None,
+            variant: Some(variant),
+        })
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+struct MutableArraySerializer {
+    array: MutableArray,
+    variant: Option<&'static str>,
+}
+
+impl SerializeSeq for MutableArraySerializer {
+    type Ok = ValueSlot;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.array.push_slot(value.serialize(MutableSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        let array = ValueSlot::new_array(self.array);
+        Ok(match self.variant {
+            Some(variant) => variant_array(variant, Some(array)),
+            None => array,
+        })
+    }
+}
+
+impl SerializeTuple for MutableArraySerializer {
+    type Ok = ValueSlot;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for MutableArraySerializer {
+    type Ok = ValueSlot;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for MutableArraySerializer {
+    type Ok = ValueSlot;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        SerializeSeq::end(self)
+    }
+}
+
+struct MutableDictSerializer {
+    dict: MutableDict,
+    key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+impl KeySink for MutableDictSerializer {
+    fn write_key(&mut self, key: &str) -> Result<()> {
+        self.key = Some(key.into());
+        Ok(())
+    }
+}
+
+impl SerializeMap for MutableDictSerializer {
+    type Ok = ValueSlot;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        key.serialize(MapKeySerializer { sink: self })
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        let key = self
+            .key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        self.dict
+            .insert_slot(&key, value.serialize(MutableSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        let dict = ValueSlot::new_dict(self.dict);
+        Ok(match self.variant {
+            Some(variant) => variant_array(variant, Some(dict)),
+            None => dict,
+        })
+    }
+}
+
+impl ser::SerializeStruct for MutableDictSerializer {
+    type Ok = ValueSlot;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.dict
+            .insert_slot(key, value.serialize(MutableSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        SerializeMap::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for MutableDictSerializer {
+    type Ok = ValueSlot;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        SerializeMap::end(self)
+    }
+}
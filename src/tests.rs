@@ -1,11 +1,16 @@
 extern crate std;
 use std::{fs::OpenOptions, io::Write};
 
-use alloc::{collections::BTreeSet, sync::Arc};
+use alloc::{collections::BTreeSet, string::String, sync::Arc, vec::Vec};
 
 use crate::{
     alloced::{AllocedArray, AllocedDict},
-    encoder, fleece, Encoder, MutableArray, MutableDict, SharedKeys, Value, ValueType,
+    delta::{self, DeltaError},
+    encoder, fleece, json,
+    keypath::KeyPathError,
+    value::{DecodeError, PathItem},
+    Array, DeepIterator, Dict, Encoder, Error, KeyPath, MutableArray, MutableDict, Scope,
+    SharedKeys, Value, ValueType,
 };
 
 const PERSON_ENCODED: &[u8] = include_bytes!("../1person.fleece");
@@ -322,3 +327,1710 @@ fn nested_mutable_array() {
     profile_dict.insert("Address", "3250 Olcott St");
     assert_eq!(profile_dict["Address"].to_str(), "3250 Olcott St");
 }
+
+#[test]
+fn encode_mutable_dict() {
+    let dict = Value::clone_from_bytes(PERSON_ENCODED)
+        .unwrap()
+        .to_dict()
+        .unwrap();
+    let mut dict = MutableDict::from(dict);
+    dict.insert("age", 31);
+    dict.remove("balance");
+    dict.insert_array("nicknames", fleece!["Jeff", "Jeffrey"]);
+
+    let encoded = dict.encode();
+    let encoded = Value::from_bytes(&encoded)
+        .expect("Failed to decode Fleece")
+        .as_dict()
+        .expect("Expected root to be a Dict!");
+    assert_eq!(encoded.len(), 21);
+    assert_eq!(encoded["age"].to_short(), 31);
+    assert!(!encoded.contains_key("balance"));
+    let nicknames = encoded["nicknames"].as_array().unwrap();
+    assert_eq!(nicknames[1].to_str(), "Jeffrey");
+    assert_eq!(encoded["_id"].to_str(), dict["_id"].to_str());
+}
+
+#[test]
+fn encode_mutable_dict_shared_keys() {
+    let value = Value::from_bytes(PERSON_ENCODED).unwrap();
+    let mut encoder = Encoder::new();
+    encoder.set_shared_keys(SharedKeys::new());
+    encoder.write_fleece(value).unwrap();
+    let scope = encoder.finish_scoped();
+
+    let mut dict = MutableDict::from_scope(&scope).unwrap();
+    dict.insert("favourite_fruit", "Mango");
+    let scope = dict.encode_scoped();
+    let shared_keys = scope.shared_keys().expect("Scope should have shared keys!");
+    assert!(shared_keys.encode("favourite_fruit").is_some());
+
+    let root = scope.root().unwrap().to_dict().unwrap();
+    assert_eq!(root.len(), 22);
+    assert_eq!(root["favourite_fruit"].to_str(), "Mango");
+    assert_eq!(root["age"].to_short(), 30);
+}
+
+#[test]
+fn encode_mutable_array() {
+    let array: AllocedArray = fleece![23, {"name": "Jeff", "age": 35}];
+    let mut array = MutableArray::from(array);
+    array.get_dict_mut(1).unwrap().insert("age", 36);
+    array.push("Hello, World!");
+
+    let encoded = array.encode();
+    let encoded = Array::from_bytes(&encoded).expect("Failed to decode Fleece");
+    assert_eq!(encoded.len(), 3);
+    assert_eq!(encoded[0].to_short(), 23);
+    assert_eq!(encoded[1].as_dict().unwrap()["age"].to_short(), 36);
+    assert_eq!(encoded[2].to_str(), "Hello, World!");
+}
+
+#[test]
+fn encode_with_base() {
+    let base = Dict::from_bytes(PERSON_ENCODED).unwrap();
+    let mut dict = MutableDict::clone_from(base);
+    dict.insert("age", 31);
+    dict.insert("eyeColor", "green");
+
+    let mut encoder = Encoder::new_with_base(PERSON_ENCODED).unwrap();
+    encoder.write_mutable_dict(&dict).unwrap();
+    let delta = encoder.finish();
+    // Only the changed values and the dict itself should be written
+    assert!(delta.len() < PERSON_ENCODED.len() / 4);
+
+    let document = [PERSON_ENCODED, &delta].concat();
+    let updated = Dict::from_bytes(&document).expect("Failed to decode Fleece");
+    assert_eq!(updated.len(), 21);
+    assert_eq!(updated["age"].to_short(), 31);
+    assert_eq!(updated["eyeColor"].to_str(), "green");
+    for (key, value) in base {
+        if key != "age" && key != "eyeColor" {
+            assert_eq!(format!("{:?}", &updated[key]), format!("{value:?}"));
+        }
+    }
+}
+
+#[test]
+fn encode_with_base_unchanged() {
+    let base = fleece! {
+        "name": "Jeff",
+        "contact": {
+            "email": "contact@jeffbaggins.com",
+            "phone_number": "+1 234 56789",
+        },
+    };
+    let base_bytes = base.buf.clone();
+    let mut dict = MutableDict::from(base);
+
+    let mut encoder = Encoder::new_with_base(base_bytes.clone()).unwrap();
+    encoder.write_mutable_dict(&dict).unwrap();
+    // Just the root pointer back into the base
+    assert_eq!(encoder.finish().len(), 2);
+
+    dict.insert("name", "Jeffrey");
+    let mut encoder = Encoder::new_with_base(base_bytes.clone()).unwrap();
+    encoder.write_mutable_dict(&dict).unwrap();
+    let updated = encoder.finish_value().to_dict().unwrap();
+    assert_eq!(updated["name"].to_str(), "Jeffrey");
+    let contact = updated["contact"].as_dict().unwrap();
+    assert_eq!(contact["email"].to_str(), "contact@jeffbaggins.com");
+    // The unchanged contact dict is not copied
+    let contact_ptr = contact.array.value.bytes.as_ptr() as usize;
+    let base_start = updated.buf.as_ptr() as usize;
+    assert!(contact_ptr < base_start + base_bytes.len());
+}
+
+// An array with a single extern pointer to the first value in the given extern destination
+fn extern_pointer_array(extern_destination: &[u8]) -> Vec<u8> {
+    // Pointer offsets are stored halved
+    let offset = u16::try_from(extern_destination.len() / 2 + 1).unwrap();
+    let pointer = (0xC000 | offset).to_be_bytes();
+    vec![0x60, 0x01, pointer[0], pointer[1], 0x80, 0x02]
+}
+
+#[test]
+fn extern_pointer() {
+    let mut encoder = Encoder::new();
+    encoder.begin_array(1).unwrap();
+    encoder
+        .write_value("This string lives in the extern destination")
+        .unwrap();
+    encoder.end_array().unwrap();
+    let extern_destination = encoder.finish();
+    let data = extern_pointer_array(&extern_destination);
+
+    let scope = Scope::new_with_extern(data, None, extern_destination);
+    let root = scope.root().expect("Failed to decode Fleece");
+    let array = root.as_array().unwrap();
+    assert_eq!(array.len(), 1);
+    assert_eq!(
+        array[0].to_str(),
+        "This string lives in the extern destination"
+    );
+    assert_eq!(
+        array.iter().next().unwrap().to_str(),
+        "This string lives in the extern destination"
+    );
+}
+
+#[test]
+fn extern_pointer_shared_keys() {
+    let mut encoder = Encoder::new();
+    encoder.set_shared_keys(SharedKeys::new());
+    encoder.begin_array(1).unwrap();
+    encoder.begin_dict().unwrap();
+    encoder.write_key("name").unwrap();
+    encoder.write_value(5).unwrap();
+    encoder.end_dict().unwrap();
+    encoder.end_array().unwrap();
+    let base = encoder.finish_scoped();
+    let shared_keys = base.shared_keys().cloned();
+    let extern_destination = base.data().unwrap().to_vec();
+    let data = extern_pointer_array(&extern_destination);
+
+    // The Dict is in the extern destination, but its int keys are resolved with the SharedKeys
+    let scope = Scope::new_with_extern(data, shared_keys, extern_destination);
+    let root = scope.root().expect("Failed to decode Fleece");
+    let dict = root.as_array().unwrap()[0].as_dict().unwrap();
+    assert_eq!(dict.get("name").unwrap().to_int(), 5);
+    assert_eq!(crate::to_json(&root), r#"[{"name":5}]"#);
+
+    // Once the Scope is removed, the extern pointer can still be resolved
+    scope.remove();
+    assert_eq!(root.as_array().unwrap()[0].as_dict().unwrap().len(), 1);
+}
+
+#[test]
+fn extern_pointer_scope_dropped() {
+    let mut encoder = Encoder::new();
+    encoder.begin_array(1).unwrap();
+    encoder.write_value("Hello, World!").unwrap();
+    encoder.end_array().unwrap();
+    let extern_destination = encoder.finish();
+    let data = extern_pointer_array(&extern_destination);
+    let scope = Scope::new_with_extern(data, None, extern_destination);
+    let root = scope.root().unwrap();
+    let data = scope.data().unwrap();
+    drop(scope);
+    // The root retains the extern destination
+    assert_eq!(root.as_array().unwrap()[0].to_str(), "Hello, World!");
+    assert!(Value::from_bytes(&data).is_ok());
+
+    // Once nothing retains it, the extern pointer can't be resolved
+    drop(root);
+    assert!(matches!(
+        Value::from_bytes(&data),
+        Err(DecodeError::UnresolvedExternPointer)
+    ));
+}
+
+#[test]
+fn extern_pointer_layers() {
+    let mut encoder = Encoder::new();
+    encoder.begin_array(1).unwrap();
+    encoder
+        .write_value("This string lives in the base")
+        .unwrap();
+    encoder.end_array().unwrap();
+    let base = encoder.finish();
+    // The first layer is an array with an extern pointer into the base, and the second layer is
+    // an array with an extern pointer to the first layer's array
+    let first = Scope::new_with_extern(extern_pointer_array(&base), None, base);
+    let first_data = first.data().unwrap();
+    let second =
+        Scope::new_with_extern(extern_pointer_array(&first_data), None, first_data.clone());
+
+    // The extern pointer in the first layer is resolved against the base, not the first layer
+    let root = second.root().expect("Failed to decode Fleece");
+    let inner = root.as_array().unwrap()[0].as_array().unwrap();
+    assert_eq!(inner[0].to_str(), "This string lives in the base");
+
+    // An extern destination which wasn't registered with its own extern destination can't
+    // resolve the extern pointers in it
+    let copy = first_data.to_vec();
+    let unregistered = Scope::new_with_extern(extern_pointer_array(&copy), None, copy);
+    assert!(unregistered.root().is_none());
+    assert!(matches!(
+        Value::from_bytes(&unregistered.data().unwrap()),
+        Err(DecodeError::UnresolvedExternPointer)
+    ));
+}
+
+#[test]
+fn extern_pointer_invalid() {
+    let mut encoder = Encoder::new();
+    encoder.begin_array(1).unwrap();
+    encoder.write_value("Hello, World!").unwrap();
+    encoder.end_array().unwrap();
+    let extern_destination = encoder.finish();
+    let data = extern_pointer_array(&extern_destination);
+    assert!(matches!(
+        Value::from_bytes(&data),
+        Err(DecodeError::UnresolvedExternPointer)
+    ));
+    // The pointer overhangs the start of a shorter extern destination
+    let scope = Scope::new_with_extern(data, None, &extern_destination[2..]);
+    assert!(scope.root().is_none());
+    assert!(matches!(
+        Value::from_bytes(&scope.data().unwrap()),
+        Err(DecodeError::PointerTargetOutOfBounds { .. })
+    ));
+}
+
+#[test]
+fn to_json() {
+    let mut encoder = Encoder::new();
+    encoder.begin_dict().unwrap();
+    encoder.write_key("name").unwrap();
+    encoder.write_value("Jeff \"J\" Baggins\n").unwrap();
+    encoder.write_key("data").unwrap();
+    encoder.write_value(&b"Fleece"[..]).unwrap();
+    encoder.write_key("missing").unwrap();
+    encoder.write_value(encoder::UndefinedValue).unwrap();
+    encoder.write_key("scores").unwrap();
+    encoder.begin_array(4).unwrap();
+    encoder.write_value(-3).unwrap();
+    encoder.write_value(2.5).unwrap();
+    encoder.write_value(encoder::UndefinedValue).unwrap();
+    encoder.write_value(true).unwrap();
+    encoder.end_array().unwrap();
+    encoder.end_dict().unwrap();
+    let value = encoder.finish_value();
+
+    assert_eq!(
+        json::to_json(&value),
+        r#"{"data":"RmxlZWNl","name":"Jeff \"J\" Baggins\n","scores":[-3,2.5,null,true]}"#
+    );
+    assert_eq!(
+        json::to_json_pretty(&value),
+        r#"{
+  "data": "RmxlZWNl",
+  "name": "Jeff \"J\" Baggins\n",
+  "scores": [
+    -3,
+    2.5,
+    null,
+    true
+  ]
+}"#
+    );
+
+    let mut dict = MutableDict::from(value.to_dict().unwrap());
+    dict.insert("age", 35);
+    dict.insert_array("empty", MutableArray::new());
+    assert_eq!(
+        json::to_json(&dict),
+        r#"{"age":35,"data":"RmxlZWNl","empty":[],"name":"Jeff \"J\" Baggins\n","scores":[-3,2.5,null,true]}"#
+    );
+}
+
+#[test]
+fn to_json_pointer() {
+    let mut encoder = Encoder::new();
+    encoder.begin_array(1).unwrap();
+    encoder
+        .write_value("A string which is too long to be inline")
+        .unwrap();
+    encoder.end_array().unwrap();
+    let data = encoder.finish();
+    // The raw slot of the array's only item, before the root pointer
+    let slot = &data[data.len() - 4..data.len() - 2];
+    let value = unsafe { &*(core::ptr::from_ref::<[u8]>(slot) as *const Value) };
+    assert_eq!(value.value_type(), ValueType::Pointer);
+    assert_eq!(
+        json::to_json(value),
+        r#""A string which is too long to be inline""#
+    );
+}
+
+#[test]
+fn to_json_shared_keys() {
+    let value = Value::from_bytes(PERSON_ENCODED).unwrap();
+    let mut encoder = Encoder::new();
+    encoder.set_shared_keys(SharedKeys::new());
+    encoder.write_fleece(value).unwrap();
+    let scope = encoder.finish_scoped();
+    let root = scope.root().unwrap();
+
+    // Shared keys are sorted by their int value, which is the order they were first written
+    assert_eq!(
+        json::to_json(&root),
+        concat!(
+            r#"{"_id":"56516c81b864942e1acca6d9","about":"Elit ut duis deserunt excepteur id in "#,
+            r#"tempor ipsum sunt. Pariatur ullamco ullamco aliqua dolore aliqua do ea mollit est "#,
+            r#"aute dolore. Amet qui velit sit aliquip ipsum deserunt veniam cupidatat voluptate "#,
+            r#"nisi elit. Est dolor enim eiusmod amet tempor culpa commodo dolor. Nostrud aute "#,
+            r#"deserunt do qui dolor. Ad exercitation id sit anim deserunt eiusmod elit.\r\n","#,
+            r#""address":"927 Hinsdale Street, Virgie, Ohio, 4436","age":30,"balance":"$1,458.82","#,
+            r#""company":"BLEEKO","email":"glendamorse@bleeko.com","eyeColor":"blue","friends":["#,
+            r#"{"id":0,"name":"Magdalena Moore"},{"id":1,"name":"Watts Townsend"},"#,
+            r#"{"id":2,"name":"Owens Everett"}],"name":"Glenda Morse","gender":"female","#,
+            r#""guid":"c2b61d0d-ac83-47f6-ae59-b6a8e3bf3ab8","index":0,"isActive":true,"#,
+            r#""latitude":40.941286,"longitude":-21.152958,"phone":"+1 (911) 413-2443","#,
+            r#""picture":"http://placehold.it/32x32","registered":"2014-01-28T05:37:03 +08:00","#,
+            r#""tags":["quis","laborum","officia","adipisicing","et","laborum","tempor"],"#,
+            r#""type":"person"}"#,
+        )
+    );
+
+    // Without the SharedKeys, int keys are written as strings of the int
+    let data = scope.data().unwrap().to_vec();
+    let json = json::to_json(Value::from_bytes(&data).unwrap());
+    assert!(json.starts_with(r#"{"0":"56516c81b864942e1acca6d9","1":"Elit"#));
+    assert!(json.contains(r#""8":[{"9":0,"10":"Magdalena Moore"},"#));
+    assert!(json.ends_with(r#""21":"person"}"#));
+}
+
+#[test]
+fn encode_ints() {
+    let ints = [
+        0,
+        2047,
+        -2048,
+        2048,
+        -2049,
+        32767,
+        -32769,
+        1 << 40,
+        -(1 << 40),
+        i64::MAX,
+        i64::MIN,
+    ];
+    let mut encoder = Encoder::new();
+    encoder.begin_array(ints.len() + 1).unwrap();
+    for int in ints {
+        encoder.write_value(int).unwrap();
+    }
+    encoder.write_value(u64::MAX).unwrap();
+    encoder.end_array().unwrap();
+    let array = encoder.finish_value().to_array().unwrap();
+    for (value, int) in array.iter().zip(ints) {
+        assert_eq!(value.to_int(), int);
+    }
+    assert_eq!(array[ints.len()].value_type(), ValueType::UnsignedInt);
+    assert_eq!(array[ints.len()].to_unsigned_int(), u64::MAX);
+}
+
+// Regression test: `fleece_size` must match the number of bytes `write_fleece_to` writes.
+#[test]
+fn int_sizes() {
+    use crate::encoder::Encodable;
+
+    #[allow(clippy::needless_pass_by_value)]
+    fn check<T: Encodable + core::fmt::Debug>(int: T, size: usize) {
+        let mut buf = [0u8; 16];
+        let written = int.write_fleece_to(&mut buf, false).unwrap().get();
+        assert_eq!(int.fleece_size(), size, "fleece_size of {int:?}");
+        assert_eq!(written, size, "bytes written for {int:?}");
+    }
+
+    check(2047i64, 2);
+    check(-2048i64, 2);
+    check(2048i64, 3);
+    check(-2049i64, 3);
+    check(32767i64, 3);
+    check(32768i64, 4);
+    check(-32769i64, 4);
+    check(1i64 << 40, 7);
+    check(i64::MAX, 9);
+    check(i64::MIN, 9);
+    check(2048u64, 3);
+    check(65535u64, 3);
+    check(u64::MAX, 9);
+    check(32767i16, 3);
+    check(-32768i16, 3);
+    check(65535u16, 3);
+}
+
+// Regression test: the exact bytes written for ints at the edges of each size, and for narrow
+// inline values, which must match the Fleece format.
+#[test]
+fn int_wire_format() {
+    use crate::encoder::Encodable;
+
+    #[allow(clippy::needless_pass_by_value)]
+    fn check<T: Encodable + core::fmt::Debug>(value: T, expected: &[u8]) {
+        let mut buf = [0u8; 16];
+        let written = value.write_fleece_to(&mut buf, false).unwrap().get();
+        assert_eq!(&buf[..written], expected, "bytes written for {value:?}");
+    }
+
+    // Shorts hold 12 bits
+    check(2047i64, &[0x07, 0xFF]);
+    check(-2048i64, &[0x08, 0x00]);
+    check(-1i64, &[0x0F, 0xFF]);
+    // Ints have a count of bytes in the tag, followed by the little-endian bytes
+    check(2048i64, &[0x11, 0x00, 0x08]);
+    check(-2049i64, &[0x11, 0xFF, 0xF7]);
+    check(32768i64, &[0x12, 0x00, 0x80, 0x00]);
+    check(i64::MIN, &[0x17, 0, 0, 0, 0, 0, 0, 0, 0x80]);
+    // Unsigned ints set the 0x08 bit of the tag
+    check(65535u64, &[0x19, 0xFF, 0xFF]);
+    check(
+        u64::MAX,
+        &[0x1F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+    );
+    // The tag of a one-byte string keeps its 0x40 bit
+    check("a", &[0x41, b'a']);
+}
+
+// Regression test: signed ints stored in fewer than 8 bytes are sign extended when read.
+#[test]
+fn negative_ints_sign_extended() {
+    for int in [-2049i64, -32769, -(1 << 40), i64::MIN] {
+        let mut encoder = Encoder::new();
+        encoder.begin_array(1).unwrap();
+        encoder.write_value(int).unwrap();
+        encoder.end_array().unwrap();
+        let array = encoder.finish_value().to_array().unwrap();
+        assert_eq!(array[0].value_type(), ValueType::Int);
+        assert_eq!(array[0].to_int(), int);
+    }
+}
+
+// Regression test: the tag of narrow inline values (such as one-byte strings) is written as is.
+#[test]
+fn one_byte_strings() {
+    let strings = ["a", "@", "~", "1"];
+    let mut encoder = Encoder::new();
+    encoder.begin_array(strings.len()).unwrap();
+    for string in strings {
+        encoder.write_value(string).unwrap();
+    }
+    encoder.end_array().unwrap();
+    let array = encoder.finish_value().to_array().unwrap();
+    for (value, string) in array.iter().zip(strings) {
+        assert_eq!(value.value_type(), ValueType::String);
+        assert_eq!(value.to_str(), string);
+    }
+}
+
+#[test]
+fn from_json() {
+    let json = r#"{
+        "name": "Jeff \"J\" Baggins \ud83d\ude00",
+        "age": 35,
+        "balance": -123456789012,
+        "big": 18446744073709551615,
+        "height": 1.0,
+        "scale": 2.5e-3,
+        "tags": ["a", [], {}, null, true, false],
+        "empty": ""
+    }"#;
+    let scope = json::from_json(json, None).unwrap();
+    let dict = scope.root().unwrap().to_dict().unwrap();
+    assert_eq!(dict["name"].to_str(), "Jeff \"J\" Baggins \u{1F600}");
+    assert_eq!(dict["age"].value_type(), ValueType::Short);
+    assert_eq!(dict["age"].to_int(), 35);
+    assert_eq!(dict["balance"].value_type(), ValueType::Int);
+    assert_eq!(dict["balance"].to_int(), -123_456_789_012);
+    assert_eq!(dict["big"].to_unsigned_int(), u64::MAX);
+    assert_eq!(dict["height"].value_type(), ValueType::Double64);
+    assert!((dict["height"].to_double() - 1.0).abs() < f64::EPSILON);
+    assert!((dict["scale"].to_double() - 0.0025).abs() < f64::EPSILON);
+    assert_eq!(dict["tags"].as_array().unwrap().len(), 6);
+    assert_eq!(dict["empty"].to_str(), "");
+
+    assert_eq!(
+        json::to_json(&dict),
+        r#"{"age":35,"balance":-123456789012,"big":18446744073709551615,"empty":"","height":1.0,"name":"Jeff \"J\" Baggins 😀","scale":0.0025,"tags":["a",[],{},null,true,false]}"#
+    );
+
+    let scope = json::from_json(json, Some(SharedKeys::new())).unwrap();
+    assert!(!scope.shared_keys().unwrap().is_empty());
+    let dict = scope.root().unwrap().to_dict().unwrap();
+    assert_eq!(dict["balance"].to_int(), -123_456_789_012);
+}
+
+#[test]
+fn from_json_people() {
+    let people = Value::from_bytes(PEOPLE_ENCODED).unwrap();
+    let json = json::to_json(people);
+    let scope = json::from_json(&json, None).unwrap();
+    assert_eq!(json::to_json(&scope.root().unwrap()), json);
+}
+
+#[test]
+fn write_json() {
+    let mut encoder = Encoder::new();
+    encoder.begin_dict().unwrap();
+    encoder.write_key("from_json").unwrap();
+    encoder.write_json(r#" {"list": [1, 2, 3]} "#).unwrap();
+    encoder.write_key("value").unwrap();
+    encoder.write_json("-0.5").unwrap();
+    encoder.end_dict().unwrap();
+    let dict = encoder.finish_value().to_dict().unwrap();
+    assert_eq!(
+        json::to_json(&dict),
+        r#"{"from_json":{"list":[1,2,3]},"value":-0.5}"#
+    );
+
+    // Scalars are written as the root value
+    let mut encoder = Encoder::new();
+    encoder.write_json("1").unwrap();
+    assert_eq!(encoder.finish_value().to_int(), 1);
+}
+
+#[test]
+fn from_json_errors() {
+    let error = |json: &str| match json::from_json(json, None) {
+        Err(crate::Error::Json(error)) => (error.kind, error.line, error.column),
+        other => panic!("Expected a JSON error, got {other:?}"),
+    };
+    assert!(matches!(
+        error("{\n  \"a\": 1,\n  \"b\": tru\n}"),
+        (json::JsonErrorKind::UnexpectedCharacter('\n'), 3, 11)
+    ));
+    assert!(matches!(
+        error("[1, 2"),
+        (json::JsonErrorKind::UnexpectedEnd, 1, 6)
+    ));
+    assert!(matches!(
+        error("[01]"),
+        (json::JsonErrorKind::UnexpectedCharacter('1'), 1, 3)
+    ));
+    assert!(matches!(
+        error("{\"a\": \"\\x\"}"),
+        (json::JsonErrorKind::InvalidEscape, 1, 9)
+    ));
+    assert!(matches!(
+        error("[] []"),
+        (json::JsonErrorKind::TrailingCharacters, 1, 4)
+    ));
+    assert!(matches!(
+        error("[1,]"),
+        (json::JsonErrorKind::UnexpectedCharacter(']'), 1, 4)
+    ));
+}
+
+#[test]
+fn from_json5() {
+    let json5 = r"
+    // A JSON5 fixture
+    {
+        name: 'Jeff \'J\' Baggins',
+        $id: 0x1F,
+        negative: -0x10,
+        /* Numbers */
+        scale: .5,
+        height: +1.,
+        limit: Infinity,
+        tags: ['a', 'b',],
+        multiline: 'one \
+two',
+        escapes: '\x41\v\0',
+    }
+    ";
+    let scope = json::from_json5(json5, None).unwrap();
+    let dict = scope.root().unwrap().to_dict().unwrap();
+    assert_eq!(dict["name"].to_str(), "Jeff 'J' Baggins");
+    assert_eq!(dict["$id"].to_int(), 31);
+    assert_eq!(dict["negative"].to_int(), -16);
+    assert!((dict["scale"].to_double() - 0.5).abs() < f64::EPSILON);
+    assert!((dict["height"].to_double() - 1.0).abs() < f64::EPSILON);
+    assert!(dict["limit"].to_double().is_infinite());
+    assert_eq!(dict["tags"].as_array().unwrap().len(), 2);
+    assert_eq!(dict["multiline"].to_str(), "one two");
+    assert_eq!(dict["escapes"].to_str(), "A\u{0B}\0");
+
+    // JSON5 extensions are rejected by the strict parser
+    assert!(json::from_json(json5, None).is_err());
+}
+
+#[test]
+fn from_json5_errors() {
+    let error = |json5: &str| match json::from_json5(json5, None) {
+        Err(crate::Error::Json(error)) => (error.kind, error.line, error.column),
+        other => panic!("Expected a JSON error, got {other:?}"),
+    };
+    assert!(matches!(
+        error("{\n  a: 1,\n  /* unterminated\n}"),
+        (json::JsonErrorKind::UnterminatedComment, 3, 3)
+    ));
+    assert!(matches!(
+        error("{\n  a: 1,\n  1b: 2\n}"),
+        (json::JsonErrorKind::UnexpectedCharacter('1'), 3, 3)
+    ));
+    assert!(matches!(
+        error("['é', .]"),
+        (json::JsonErrorKind::InvalidNumber, 1, 8)
+    ));
+}
+
+#[test]
+fn key_path() {
+    let people = Value::from_bytes(PEOPLE_ENCODED).unwrap();
+    let path = KeyPath::new("[3].friends[-1].name").unwrap();
+    let expected = people.as_array().unwrap()[3].as_dict().unwrap()["friends"]
+        .as_array()
+        .unwrap()[2]
+        .as_dict()
+        .unwrap()["name"]
+        .to_str();
+    assert_eq!(path.eval(people).unwrap().to_str(), expected);
+    assert_eq!(path.to_string(), "$[3].friends[-1].name");
+    assert!(KeyPath::new("$[1000]").unwrap().eval(people).is_none());
+    assert!(KeyPath::new("$[-1001]").unwrap().eval(people).is_none());
+    assert!(KeyPath::new("[0].nope.name")
+        .unwrap()
+        .eval(people)
+        .is_none());
+    assert_eq!(
+        KeyPath::new("$")
+            .unwrap()
+            .eval(people)
+            .unwrap()
+            .value_type(),
+        ValueType::Array
+    );
+
+    // Shared keys are decoded via the Scope
+    let mut encoder = Encoder::new();
+    encoder.set_shared_keys(SharedKeys::new());
+    encoder.write_fleece(people).unwrap();
+    let scope = encoder.finish_scoped();
+    let name = KeyPath::new("$[3].friends[-1].name").unwrap();
+    assert_eq!(name.eval_scope(&scope).unwrap().to_str(), expected);
+    let root = scope.root().unwrap();
+    assert_eq!(name.eval(&root).unwrap().to_str(), expected);
+    let person = KeyPath::new("[3]").unwrap().eval(&root).unwrap();
+    let friend = KeyPath::new("friends[2].name").unwrap();
+    assert_eq!(
+        friend
+            .eval_dict(person.as_dict().unwrap())
+            .unwrap()
+            .to_str(),
+        expected
+    );
+
+    let mut dict = MutableDict::clone_from(person.as_dict().unwrap());
+    dict.insert("a.b", "escaped");
+    assert_eq!(
+        friend
+            .eval_mutable(&dict)
+            .unwrap()
+            .value()
+            .unwrap()
+            .to_str(),
+        expected
+    );
+    assert!(KeyPath::new("friends")
+        .unwrap()
+        .eval_mutable(&dict)
+        .unwrap()
+        .is_array());
+    let escaped = KeyPath::new(r"a\.b").unwrap();
+    assert_eq!(
+        escaped
+            .eval_mutable(&dict)
+            .unwrap()
+            .value()
+            .unwrap()
+            .to_str(),
+        "escaped"
+    );
+    assert_eq!(escaped.to_string(), r"$.a\.b");
+}
+
+#[test]
+fn key_path_errors() {
+    assert!(matches!(
+        KeyPath::new("people..name"),
+        Err(KeyPathError::EmptyKey { position: 7 })
+    ));
+    assert!(matches!(
+        KeyPath::new("people[x]"),
+        Err(KeyPathError::InvalidIndex { position: 6 })
+    ));
+    assert!(matches!(
+        KeyPath::new("people[3"),
+        Err(KeyPathError::UnclosedBracket { position: 6 })
+    ));
+    assert!(matches!(
+        KeyPath::new("people[3]name"),
+        Err(KeyPathError::UnexpectedCharacter {
+            position: 9,
+            character: 'n'
+        })
+    ));
+    assert!(matches!(
+        KeyPath::new(r"people.name\"),
+        Err(KeyPathError::TrailingEscape { position: 11 })
+    ));
+}
+
+// Regression test: `Array::get` returned the value after the end of the array for `len()`.
+#[test]
+fn array_get_out_of_bounds() {
+    let mut encoder = Encoder::new();
+    encoder.begin_dict().unwrap();
+    encoder.write_key("array").unwrap();
+    encoder.begin_array(2).unwrap();
+    encoder.write_value(1).unwrap();
+    encoder.write_value(2).unwrap();
+    encoder.end_array().unwrap();
+    encoder.write_key("after").unwrap();
+    encoder.write_value(3).unwrap();
+    encoder.end_dict().unwrap();
+    let root = encoder.finish_value().to_dict().unwrap();
+    let array = root["array"].as_array().unwrap();
+    assert_eq!(array.get(1).unwrap().to_int(), 2);
+    assert!(array.get(2).is_none());
+    assert!(array.get(usize::MAX).is_none());
+}
+
+#[test]
+fn deep_iterator() {
+    let json = r#"{"name": "Jeff", "contact": {"email": "jeff@example.com", "phones": ["1", "2"]}, "tags": []}"#;
+    let scope = json::from_json(json, Some(SharedKeys::new())).unwrap();
+    let root = scope.root().unwrap();
+
+    let mut paths: Vec<String> = Vec::new();
+    let mut iter = DeepIterator::new(&root);
+    while iter.next().is_some() {
+        paths.push(KeyPath::from(iter.path()).to_string());
+    }
+    assert_eq!(
+        paths,
+        // Shared keys are sorted in the order they were added to the SharedKeys
+        [
+            "$",
+            "$.name",
+            "$.contact",
+            "$.contact.email",
+            "$.contact.phones",
+            "$.contact.phones[0]",
+            "$.contact.phones[1]",
+            "$.tags",
+        ]
+    );
+
+    // Every path leads back to its value
+    let mut iter = DeepIterator::new(&root);
+    while let Some(value) = iter.next() {
+        let found = KeyPath::from(iter.path()).eval(&root).unwrap();
+        assert!(core::ptr::eq(found, value));
+    }
+
+    let mut iter = DeepIterator::new(&root);
+    let mut keys = Vec::new();
+    while iter.next().is_some() {
+        if iter.path() == [PathItem::Key("contact")] {
+            iter.skip_children();
+        }
+        if let Some(PathItem::Key(key)) = iter.path().last() {
+            keys.push(*key);
+        }
+    }
+    assert_eq!(keys, ["name", "contact", "tags"]);
+}
+
+#[test]
+fn deep_iterator_people() {
+    let people = Value::from_bytes(PEOPLE_ENCODED).unwrap();
+    let mut iter = DeepIterator::new(people);
+    let mut count = 0;
+    while iter.next().is_some() {
+        if iter.path().last() == Some(&PathItem::Key("name")) {
+            count += 1;
+        }
+    }
+    // Each person has a name, and so do each of their friends
+    let friends: usize = people
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|person| {
+            person.as_dict().unwrap()["friends"]
+                .as_array()
+                .unwrap()
+                .len()
+        })
+        .sum();
+    assert_eq!(count, 1000 + friends);
+}
+
+fn hash_of(value: &Value) -> u64 {
+    use core::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn deep_eq() {
+    let a = json::from_json(
+        r#"{"name": "Jeff", "age": 35, "height": 2.0, "big": 100000, "tags": ["a", null]}"#,
+        None,
+    )
+    .unwrap();
+    // The same document, encoded with shared keys and different number encodings
+    let mut encoder = Encoder::new();
+    encoder.set_shared_keys(SharedKeys::new());
+    encoder.begin_dict().unwrap();
+    encoder.write_key("tags").unwrap();
+    encoder.begin_array(2).unwrap();
+    encoder.write_value("a").unwrap();
+    encoder.write_value(encoder::NullValue).unwrap();
+    encoder.end_array().unwrap();
+    encoder.write_key("height").unwrap();
+    encoder.write_value(2u64).unwrap();
+    encoder.write_key("big").unwrap();
+    encoder.write_value(100_000f32).unwrap();
+    encoder.write_key("age").unwrap();
+    encoder.write_value(35.0f64).unwrap();
+    encoder.write_key("name").unwrap();
+    encoder.write_value("Jeff").unwrap();
+    encoder.end_dict().unwrap();
+    let b = encoder.finish_scoped();
+
+    let a = a.root().unwrap();
+    let b = b.root().unwrap();
+    assert!(a.deep_eq(&b));
+    assert_eq!(*a, *b);
+    assert_eq!(hash_of(&a), hash_of(&b));
+
+    let c = json::from_json(r#"{"name": "Jeff", "age": 35.5}"#, None).unwrap();
+    assert_ne!(*a, *c.root().unwrap());
+    let d = json::from_json(
+        r#"{"name": "Jeff", "age": 35, "height": 2.0, "big": 100000, "tags": [null, "a"]}"#,
+        None,
+    )
+    .unwrap();
+    assert_ne!(*a, *d.root().unwrap());
+
+    // Wide and narrow collections compare equal
+    let people = Value::from_bytes(PEOPLE_ENCODED).unwrap();
+    let json = json::to_json(people);
+    let people2 = json::from_json(&json, Some(SharedKeys::new())).unwrap();
+    let people2 = people2.root().unwrap();
+    assert!(people.as_array().unwrap().is_wide());
+    assert_eq!(*people, *people2);
+    assert_eq!(people.as_array().unwrap(), people2.as_array().unwrap());
+    assert_eq!(hash_of(people), hash_of(&people2));
+
+    assert_ne!(*Value::null(), *Value::undefined());
+    assert_eq!(*Value::bool(true), *Value::bool(true));
+}
+
+#[test]
+fn collate() {
+    use core::cmp::Ordering;
+
+    let scope = json::from_json(
+        r#"[null, false, true, -1e300, -5, -4.5, 0, 0.5, 1, 1.0, 2, 1e300, "", "a", "ab", "b",
+            [], [1], [1, 2], [2], {}, {"a": 1}, {"a": 2}, {"b": 0}]"#,
+        None,
+    )
+    .unwrap();
+    let root = scope.root().unwrap();
+    let values: Vec<&Value> = root.as_array().unwrap().iter().collect();
+    for (i, a) in values.iter().enumerate() {
+        for (j, b) in values.iter().enumerate() {
+            let expected = match (i, j) {
+                // 1 and 1.0 are equal
+                (8 | 9, 8 | 9) => Ordering::Equal,
+                _ => i.cmp(&j),
+            };
+            assert_eq!(a.collate(b), expected, "{i}: {a:?} vs {j}: {b:?}");
+        }
+    }
+
+    assert_eq!(Value::undefined().collate(Value::null()), Ordering::Less);
+    // Data sorts between strings and arrays
+    let mut encoder = Encoder::new();
+    encoder.begin_array(2).unwrap();
+    encoder.write_value(&[0u8, 1][..]).unwrap();
+    encoder.write_value(&[0u8, 2][..]).unwrap();
+    encoder.end_array().unwrap();
+    let data = encoder.finish_scoped();
+    let data = data.root().unwrap();
+    let data = data.as_array().unwrap();
+    assert_eq!(data.get(0).unwrap().collate(values[15]), Ordering::Greater);
+    assert_eq!(data.get(0).unwrap().collate(values[16]), Ordering::Less);
+    assert_eq!(
+        data.get(0).unwrap().collate(data.get(1).unwrap()),
+        Ordering::Less
+    );
+
+    // Dicts are compared by sorted keys, regardless of shared keys
+    let shared = json::from_json(r#"{"b": 0, "a": 2}"#, Some(SharedKeys::new())).unwrap();
+    let shared = shared.root().unwrap();
+    assert_eq!(shared.collate(values[22]), Ordering::Greater);
+    assert_eq!(shared.collate(values[23]), Ordering::Less);
+    assert!(shared.as_dict().unwrap() > values[22].as_dict().unwrap());
+
+    let mut sorted = values.clone();
+    sorted.reverse();
+    sorted.sort();
+    assert_eq!(sorted, values);
+}
+
+#[test]
+fn delta() {
+    let old = json::from_json(
+        r#"{"name": "Jeff", "age": 35, "tags": ["a", "b", "c"], "address": {"city": "Oslo",
+            "zip": "0150"}, "pets": ["cat"], "removed": true}"#,
+        Some(SharedKeys::new()),
+    )
+    .unwrap();
+    let new = json::from_json(
+        r#"{"name": "Jeff", "age": 36, "tags": ["a", "x"], "address": {"city": "Bergen",
+            "zip": "0150"}, "pets": ["cat", {"name": "Rex"}], "added": [1, 2]}"#,
+        None,
+    )
+    .unwrap();
+    let (old, new) = (old.root().unwrap(), new.root().unwrap());
+
+    let delta = delta::create(&old, &new);
+    assert_eq!(
+        core::str::from_utf8(&delta).unwrap(),
+        r#"{"added":[[1,2]],"address":{"city":"Bergen"},"age":36,"pets":{"1":[{"name":"Rex"}]},"removed":[],"tags":{"-":2,"1":"x"}}"#
+    );
+    let applied = delta::apply(&old, &delta).unwrap();
+    assert_eq!(*Value::from_bytes(&applied.encode()).unwrap(), *new);
+    let applied = delta::apply_encoded(&old, &delta).unwrap();
+    assert_eq!(*Value::from_bytes(&applied).unwrap(), *new);
+
+    // Equal values have an empty delta, which changes nothing
+    assert!(delta::create(&old, &old).is_empty());
+    let applied = delta::apply_encoded(&old, &[]).unwrap();
+    assert_eq!(*Value::from_bytes(&applied).unwrap(), *old);
+
+    // Replacing the root
+    let array = json::from_json("[1, 2]", None).unwrap();
+    let array = array.root().unwrap();
+    let delta = delta::create(&old, &array);
+    assert_eq!(delta, b"[[1,2]]");
+    let applied = delta::apply_encoded(&old, &delta).unwrap();
+    assert_eq!(*Value::from_bytes(&applied).unwrap(), *array);
+    assert!(matches!(
+        delta::apply(&old, &delta),
+        Err(Error::Delta(DeltaError::NotADict))
+    ));
+
+    for invalid in [
+        &b"[]"[..],
+        b"[1, 2]",
+        b"{\"age\": {\"x\": 1}}",
+        b"{",
+        b"\xff",
+    ] {
+        assert!(delta::apply_encoded(&old, invalid).is_err(), "{invalid:?}");
+    }
+    let array_delta = |delta: &str| delta::apply_encoded(&array, delta.as_bytes());
+    assert!(array_delta(r#"{"3": 1}"#).is_err());
+    assert!(array_delta(r#"{"-": 3}"#).is_err());
+    assert!(array_delta(r#"{"x": 3}"#).is_err());
+    assert!(array_delta(r#"{"0": []}"#).is_err());
+    // Indices far past the end are rejected without allocating an array of that length
+    assert!(matches!(
+        array_delta(r#"{"999999999999": 1}"#),
+        Err(Error::Delta(DeltaError::InvalidDelta))
+    ));
+    assert!(array_delta(&format!(r#"{{"{}": 1}}"#, usize::MAX)).is_err());
+    let applied = array_delta(r#"{"-": 0}"#).unwrap();
+    assert!(Value::from_bytes(&applied)
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn delta_scalar_root() {
+    let old = json::from_json(r#"{"name": "Jeff"}"#, None).unwrap();
+    let old = old.root().unwrap();
+    let five = json::from_json("5", None).unwrap();
+    let five = five.root().unwrap();
+    let string = json::from_json(r#""Jeff""#, None).unwrap();
+    let string = string.root().unwrap();
+
+    // Scalar roots are replaced without being wrapped, in either direction
+    for (old, new, expected) in [
+        (&old, &five, "5"),
+        (&five, &string, r#""Jeff""#),
+        (&string, &old, r#"[{"name":"Jeff"}]"#),
+    ] {
+        let delta = delta::create(old, new);
+        assert_eq!(core::str::from_utf8(&delta).unwrap(), expected);
+        let applied = delta::apply_encoded(old, &delta).unwrap();
+        assert_eq!(*Value::from_bytes(&applied).unwrap(), **new);
+    }
+    assert!(delta::create(&five, &five).is_empty());
+    let applied = delta::apply_encoded(&five, &[]).unwrap();
+    assert_eq!(*Value::from_bytes(&applied).unwrap(), *five);
+    assert!(matches!(
+        delta::apply_encoded(&five, b"[]"),
+        Err(Error::Delta(DeltaError::InvalidRoot))
+    ));
+}
+
+#[test]
+fn merge_patch() {
+    // The example from RFC 7396
+    let target = json::from_json(
+        r#"{"title": "Goodbye!", "author": {"givenName": "John", "familyName": "Doe"},
+            "tags": ["example", "sample"], "content": "This will be unchanged"}"#,
+        Some(SharedKeys::new()),
+    )
+    .unwrap();
+    let mut dict = MutableDict::from_scope(&target).unwrap();
+    let patch = json::from_json(
+        r#"{"title": "Hello!", "phoneNumber": "+01-234-567-8900", "author": {"familyName": null},
+            "tags": ["example"], "new": {"a": {"b": 1, "c": null}}, "missing": null}"#,
+        None,
+    )
+    .unwrap();
+    dict.merge_patch(&patch.root().unwrap());
+    let expected = json::from_json(
+        r#"{"title": "Hello!", "author": {"givenName": "John"}, "tags": ["example"],
+            "content": "This will be unchanged", "phoneNumber": "+01-234-567-8900",
+            "new": {"a": {"b": 1}}}"#,
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        *Value::from_bytes(&dict.encode()).unwrap(),
+        *expected.root().unwrap()
+    );
+
+    // A patch from Fleece bytes, which replaces a dict with a scalar
+    let null = encoder::NullValue;
+    let patch = fleece! { "author": "Jane", "title": null };
+    dict.merge_patch(&patch.array.value);
+    assert_eq!(dict["author"].to_str(), "Jane");
+    assert!(!dict.contains_key("title"));
+
+    // A patch which isn't a dict is ignored
+    dict.merge_patch(Value::null());
+    assert_eq!(dict.len(), 5);
+
+    // A dict under a new key is copied from the patch once, with its nulls removed
+    let patch = json::from_json(
+        r#"{"added": {"about": "A string which is too long to be inline", "gone": null,
+            "nested": {"gone": null, "kept": [null]}}}"#,
+        None,
+    )
+    .unwrap();
+    dict.merge_patch(&patch.root().unwrap());
+    let added = dict.get_dict("added").unwrap();
+    assert!(matches!(
+        added.get_slot("about"),
+        Some(crate::mutable::ValueSlot::Pointer(_))
+    ));
+    assert_eq!(
+        added["about"].to_str(),
+        "A string which is too long to be inline"
+    );
+    assert!(!added.contains_key("gone"));
+    let nested = added.get_dict("nested").unwrap();
+    assert!(!nested.contains_key("gone"));
+    assert_eq!(nested.get_array("kept").unwrap().len(), 1);
+}
+
+#[test]
+fn dump() {
+    let mut encoder = Encoder::new();
+    encoder.set_shared_keys(SharedKeys::new());
+    encoder.begin_dict().unwrap();
+    encoder.write_key("a").unwrap();
+    encoder.write_value("abc").unwrap();
+    encoder.write_key("tags").unwrap();
+    encoder.begin_array(2).unwrap();
+    encoder.write_value(100_000).unwrap();
+    encoder.write_value("a long string").unwrap();
+    encoder.end_array().unwrap();
+    encoder.end_dict().unwrap();
+    let scope = encoder.finish_scoped();
+    let data = scope.data().unwrap();
+
+    let mut dump = String::new();
+    Value::dump_bytes(&data, &mut dump).unwrap();
+    assert_eq!(
+        dump,
+        r#"0000: 43 61 62 63 : String "abc"
+0004: 12 a0 86 01 : Int 100000
+0008: 4d 61 20 6c…: String "a long string"
+0016: 60 02       : Array[2] [
+0018: 80 0a       :   &Int 100000 (@0004)
+001a: 80 09       :   &String "a long string" (@0008)
+                  : ]
+001c: 70 02       : Dict[2] {
+001e: 00 00       :   Short 0 = "tags":
+0020: 80 05       :   &Array[2] (@0016)
+0022: 41 61       :   String "a":
+0024: 80 12       :   &String "abc" (@0000)
+                  : }
+0026: 80 05       : &Dict[2] (@001c)
+"#
+    );
+
+    // Dumping a value starts at the lowest offset it refers to
+    let mut dump = String::new();
+    scope
+        .root()
+        .unwrap()
+        .as_dict()
+        .unwrap()
+        .get("tags")
+        .unwrap()
+        .dump(&mut dump)
+        .unwrap();
+    assert!(dump.starts_with("0000: 12 a0 86 01 : Int 100000\n"));
+    assert!(dump.contains("0012: 60 02       : Array[2] [\n"));
+
+    let mut dump = String::new();
+    Value::dump_bytes(&data[2..], &mut dump).unwrap();
+    assert!(dump.starts_with("Invalid Fleece data at 0022: Pointer with offset 36 target"));
+    assert!(dump.ends_with("0020: 41 61 80 12 80 05\n"));
+
+    // Inline items which are invalid are reported at their own offset, not the root's: an int
+    // which needs 4 bytes, a string which needs 4 bytes, and a string with a truncated varint size
+    for (item, value_type) in [
+        ([0x12, 0x00], ValueType::Int),
+        ([0x43, 0x61], ValueType::String),
+        ([0x4F, 0x85], ValueType::String),
+    ] {
+        let data = [0x60, 0x02, 0x41, 0x61, item[0], item[1], 0x80, 0x03];
+        let (offset, error) = Value::validation_error(&data).unwrap();
+        assert_eq!(offset, 4, "{item:02x?}");
+        assert!(
+            matches!(error, DecodeError::ValueOutOfBounds { value_type: t, .. } if t == value_type),
+            "{item:02x?}: {error}"
+        );
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn encode_to_writer_too_large() {
+    use std::io;
+
+    // Values in open collections are referred to by their position in the output, so it can't
+    // grow past the largest pointer offset
+    let data = vec![0u8; 64 * 1024 * 1024];
+    let mut encoder = encoder::WriterEncoder::new(io::sink());
+    encoder.begin_array(0).unwrap();
+    let mut written = 0;
+    let error = loop {
+        match encoder.write_value(&data[..]) {
+            Ok(()) => written += data.len(),
+            Err(error) => break error,
+        }
+    };
+    assert!(matches!(error, encoder::EncodeError::OutputTooLarge));
+    assert!(written > crate::value::pointer::MAX_WIDE as usize);
+    assert!(matches!(
+        encoder.end_array(),
+        Err(encoder::EncodeError::OutputTooLarge)
+    ));
+    let error = encoder.finish().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        encoder::EncodeError::OutputTooLarge.to_string()
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn encode_to_writer() {
+    use std::io;
+
+    #[derive(Default)]
+    struct CountingWriter {
+        out: Vec<u8>,
+        writes: usize,
+    }
+    impl io::Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes += 1;
+            self.out.write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Debug)]
+    struct FailingWriter;
+    impl io::Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    let json = json::to_json(Value::from_bytes(PEOPLE_ENCODED).unwrap());
+    let mut encoder = Encoder::new();
+    encoder.write_json(&json).unwrap();
+    let expected = encoder.finish();
+
+    // The writer can be borrowed
+    let mut writer = CountingWriter::default();
+    let mut encoder = encoder::WriterEncoder::new(&mut writer);
+    assert_send(&encoder);
+    encoder.write_json(&json).unwrap();
+    encoder.finish().unwrap();
+    // Most of the output was flushed before the encoder was finished
+    assert!(writer.writes > 1);
+    assert_eq!(writer.out, expected);
+
+    // Values written with write_fleece are flushed as they are written, too
+    let people = Value::from_bytes(PEOPLE_ENCODED).unwrap();
+    let mut writer = CountingWriter::default();
+    let mut encoder = encoder::WriterEncoder::new(&mut writer);
+    encoder.write_fleece(people).unwrap();
+    encoder.finish().unwrap();
+    assert!(writer.writes > 1);
+    assert_eq!(Value::from_bytes(&writer.out).unwrap(), people);
+
+    // The writer is returned by finish
+    let mut encoder = encoder::WriterEncoder::new(Vec::new());
+    encoder.write_json("[1, 2, 3]").unwrap();
+    let out = encoder.finish().unwrap();
+    assert_eq!(
+        Value::from_bytes(&out).unwrap().as_array().unwrap().len(),
+        3
+    );
+
+    let mut encoder = encoder::WriterEncoder::new(FailingWriter);
+    encoder.write_json(&json).unwrap();
+    assert_eq!(encoder.finish().unwrap_err().to_string(), "disk full");
+}
+
+fn slice_encode(
+    encoder: &mut encoder::SliceEncoder,
+    value: &Value,
+) -> Result<(), encoder::EncodeError> {
+    match value.value_type() {
+        ValueType::Array => {
+            encoder.begin_array()?;
+            for item in value.as_array().unwrap() {
+                slice_encode(encoder, item)?;
+            }
+            encoder.end_array()
+        }
+        ValueType::Dict => {
+            encoder.begin_dict()?;
+            for (key, item) in value.as_dict().unwrap() {
+                encoder.write_key(key)?;
+                slice_encode(encoder, item)?;
+            }
+            encoder.end_dict()
+        }
+        ValueType::String => encoder.write_value(value.to_str()),
+        ValueType::Data => encoder.write_value(value.to_data()),
+        ValueType::Float | ValueType::Double32 | ValueType::Double64 => {
+            encoder.write_value(value.to_double())
+        }
+        ValueType::Null => encoder.write_value(encoder::NullValue),
+        ValueType::False | ValueType::True => encoder.write_value(value.to_bool()),
+        _ => encoder.write_value(value.to_int()),
+    }
+}
+
+#[test]
+fn slice_encoder() {
+    let mut buf = [0u8; 128];
+    let mut encoder = encoder::SliceEncoder::new(&mut buf);
+    encoder.begin_dict().unwrap();
+    encoder.write_key("name").unwrap();
+    encoder.write_value("Alice Smith").unwrap();
+    encoder.write_key("age").unwrap();
+    encoder.write_value(42).unwrap();
+    encoder.write_key("tags").unwrap();
+    encoder.begin_array().unwrap();
+    encoder.write_value("admin").unwrap();
+    encoder.write_value(1.5).unwrap();
+    encoder.end_array().unwrap();
+    assert!(matches!(
+        encoder.write_value(1),
+        Err(encoder::EncodeError::DictWaitingForKey)
+    ));
+    encoder.end_dict().unwrap();
+    let slice_encoded = encoder.finish().unwrap();
+
+    let mut encoder = Encoder::new();
+    encoder.begin_dict().unwrap();
+    encoder.write_key("name").unwrap();
+    encoder.write_value("Alice Smith").unwrap();
+    encoder.write_key("age").unwrap();
+    encoder.write_value(42).unwrap();
+    encoder.write_key("tags").unwrap();
+    encoder.begin_array(2).unwrap();
+    encoder.write_value("admin").unwrap();
+    encoder.write_value(1.5).unwrap();
+    encoder.end_array().unwrap();
+    encoder.end_dict().unwrap();
+    assert_eq!(slice_encoded, encoder.finish());
+
+    // Wide pointers, and nested collections which are closed by `finish`
+    let people = Value::from_bytes(PEOPLE_ENCODED).unwrap();
+    let mut buf = std::vec![0u8; PEOPLE_ENCODED.len() * 2];
+    let mut encoder = encoder::SliceEncoder::new(&mut buf);
+    slice_encode(&mut encoder, people).unwrap();
+    let people_encoded = encoder.finish().unwrap();
+    assert_eq!(Value::from_bytes(people_encoded).unwrap(), people);
+
+    let mut buf = [0u8; 1000];
+    let mut encoder = encoder::SliceEncoder::new(&mut buf);
+    encoder.begin_array().unwrap();
+    encoder.begin_dict().unwrap();
+    encoder.write_key("a").unwrap();
+    encoder.write_value("b").unwrap();
+    encoder.write_key("dangling").unwrap();
+    let dangling_encoded = encoder.finish().unwrap();
+    let array = Value::from_bytes(dangling_encoded)
+        .unwrap()
+        .as_array()
+        .unwrap();
+    assert_eq!(array.get(0).unwrap().as_dict().unwrap().len(), 1);
+
+    let mut buf = [0u8; 1000];
+    let mut encoder = encoder::SliceEncoder::new(&mut buf);
+    assert!(matches!(
+        slice_encode(&mut encoder, people),
+        Err(encoder::EncodeError::BufferFull)
+    ));
+
+    // Scalar roots are encoded the same way as by the `Encoder`
+    for value in [
+        Value::from_bytes(&[0x00, 0x2a]).unwrap(),
+        people
+            .as_array()
+            .unwrap()
+            .get(0)
+            .unwrap()
+            .as_dict()
+            .unwrap()
+            .get("name")
+            .unwrap(),
+    ] {
+        let mut buf = [0u8; 64];
+        let mut encoder = encoder::SliceEncoder::new(&mut buf);
+        slice_encode(&mut encoder, value).unwrap();
+        assert!(matches!(
+            encoder.write_value(1),
+            Err(encoder::EncodeError::MultiTopLevelCollection)
+        ));
+        let slice_encoded = encoder.finish().unwrap();
+        let mut encoder = Encoder::new();
+        encoder.write_fleece(value).unwrap();
+        assert_eq!(slice_encoded, encoder.finish());
+    }
+}
+
+#[test]
+fn unique_strings() {
+    let people = Value::from_bytes(PEOPLE_ENCODED).unwrap();
+    let mut encoder = Encoder::new();
+    encoder.set_unique_strings(true);
+    encoder.write_fleece(people).unwrap();
+    let unique = encoder.finish();
+    // De-duplication is off by default
+    let mut encoder = Encoder::new();
+    encoder.write_fleece(people).unwrap();
+    let not_unique = encoder.finish();
+    let mut encoder = Encoder::new();
+    encoder.set_unique_strings(false);
+    encoder.write_fleece(people).unwrap();
+    assert!(encoder.finish() == not_unique);
+    assert!(unique.len() < not_unique.len());
+    assert_eq!(Value::from_bytes(&unique).unwrap(), people);
+
+    let long = "a string which is longer than the limit";
+    let encode = |max_len: usize| {
+        let mut encoder = Encoder::new();
+        encoder.set_unique_strings(true);
+        encoder.set_max_unique_string_len(max_len);
+        encoder.begin_array(3).unwrap();
+        for _ in 0..2 {
+            encoder.begin_dict().unwrap();
+            encoder.write_key("repeated key").unwrap();
+            encoder.write_value(long).unwrap();
+            encoder.end_dict().unwrap();
+        }
+        encoder.end_array().unwrap();
+        encoder.finish()
+    };
+    let data = encode(16);
+    // The key is only written once, but the long string is written twice
+    assert_eq!(data.windows(12).filter(|w| w == b"repeated key").count(), 1);
+    assert_eq!(
+        data.windows(long.len())
+            .filter(|w| *w == long.as_bytes())
+            .count(),
+        2
+    );
+    let array = Value::from_bytes(&data).unwrap().as_array().unwrap();
+    for dict in array {
+        assert_eq!(dict.as_dict().unwrap()["repeated key"].to_str(), long);
+    }
+    let data = encode(100);
+    assert_eq!(
+        data.windows(long.len())
+            .filter(|w| *w == long.as_bytes())
+            .count(),
+        1
+    );
+
+    // The table is bounded, so once it is full, new strings are no longer de-duplicated
+    let strings: Vec<String> = (0..5000).map(|i| format!("string {i:05}")).collect();
+    let mut encoder = Encoder::new();
+    encoder.set_unique_strings(true);
+    encoder.begin_array(strings.len() * 2).unwrap();
+    for _ in 0..2 {
+        for string in &strings {
+            encoder.write_value(string.as_str()).unwrap();
+        }
+    }
+    encoder.end_array().unwrap();
+    let data = encoder.finish();
+    let count = |string: &str| {
+        data.windows(string.len())
+            .filter(|w| *w == string.as_bytes())
+            .count()
+    };
+    assert_eq!(count("string 00000"), 1);
+    assert_eq!(count("string 04999"), 2);
+}
+
+#[test]
+fn encoder_reuse() {
+    let people = Value::from_bytes(PEOPLE_ENCODED)
+        .unwrap()
+        .as_array()
+        .unwrap();
+    let mut encoder = Encoder::new();
+    let mut out = Vec::new();
+    for person in people.into_iter().take(10) {
+        encoder.write_fleece(person).unwrap();
+        out.clear();
+        encoder.finish_into(&mut out);
+
+        let mut new_encoder = Encoder::new();
+        new_encoder.write_fleece(person).unwrap();
+        assert_eq!(out, new_encoder.finish());
+    }
+
+    // An unfinished document is discarded by `reset`
+    encoder.begin_array(1).unwrap();
+    encoder.write_value("discarded").unwrap();
+    encoder.reset();
+    encoder.begin_dict().unwrap();
+    encoder.write_key("kept").unwrap();
+    encoder.write_value(true).unwrap();
+    let data = encoder.finish();
+    let dict = Value::from_bytes(&data).unwrap().as_dict().unwrap();
+    assert_eq!(dict.len(), 1);
+    assert!(dict["kept"].to_bool());
+
+    // SharedKeys are kept, so the same document is encoded the same way again
+    let mut encoder = Encoder::new();
+    encoder.set_shared_keys(SharedKeys::new());
+    let mut first = Vec::new();
+    encoder.write_fleece(people.get(0).unwrap()).unwrap();
+    encoder.finish_into(&mut first);
+    encoder.write_fleece(people.get(0).unwrap()).unwrap();
+    let scope = encoder.finish_scoped();
+    assert_eq!(&scope.data().unwrap()[..], &first[..]);
+    assert_eq!(&*scope.root().unwrap(), people.get(0).unwrap());
+}
+
+#[test]
+fn scalar_root() {
+    fn encode(write: impl FnOnce(&mut Encoder)) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        write(&mut encoder);
+        encoder.finish()
+    }
+
+    // Values which fit in 2 bytes are the whole document
+    let data = encode(|e| e.write_value(42).unwrap());
+    assert_eq!(data.len(), 2);
+    assert_eq!(Value::from_bytes(&data).unwrap().to_int(), 42);
+    let data = encode(|e| e.write_value(true).unwrap());
+    assert_eq!(data, [0x38, 0x00]);
+    assert!(Value::from_bytes(&data).unwrap().to_bool());
+
+    // Other values are followed by a root pointer
+    let data = encode(|e| e.write_value("a counter").unwrap());
+    assert_eq!(&data[data.len() - 2..], [0x80, 0x05]);
+    assert_eq!(Value::from_bytes(&data).unwrap().to_str(), "a counter");
+    let data = encode(|e| e.write_value(i64::MAX).unwrap());
+    assert_eq!(Value::from_bytes(&data).unwrap().to_int(), i64::MAX);
+    let data = encode(|e| e.write_value(-1.5).unwrap());
+    assert!((Value::from_bytes(&data).unwrap().to_double() + 1.5).abs() < f64::EPSILON);
+    let long = "long ".repeat(5000);
+    let data = encode(|e| e.write_value(long.as_str()).unwrap());
+    assert_eq!(Value::from_bytes(&data).unwrap().to_str(), long);
+
+    let mut encoder = Encoder::new();
+    encoder
+        .write_fleece(Value::from_bytes(&data).unwrap())
+        .unwrap();
+    assert!(matches!(
+        encoder.write_value(1),
+        Err(encoder::EncodeError::MultiTopLevelCollection)
+    ));
+    assert!(matches!(
+        encoder.begin_array(1),
+        Err(encoder::EncodeError::MultiTopLevelCollection)
+    ));
+    assert_eq!(encoder.finish_value().to_str(), long);
+
+    // A small value appended to a base is written before the root pointer
+    let base = encode(|e| e.write_value("base").unwrap());
+    let mut encoder = Encoder::new_with_base(base.clone()).unwrap();
+    encoder.write_value(7).unwrap();
+    let document = [base, encoder.finish()].concat();
+    assert_eq!(Value::from_bytes(&document).unwrap().to_int(), 7);
+}
+
+#[test]
+fn encode_collections() {
+    use alloc::collections::BTreeMap;
+
+    let mut tags = BTreeMap::new();
+    tags.insert("colour", vec!["red", "green"]);
+    tags.insert("size", vec![]);
+    let mut encoder = Encoder::new();
+    encoder.begin_dict().unwrap();
+    encoder.write_key("tags").unwrap();
+    encoder.write_value(&tags).unwrap();
+    encoder.write_key("point").unwrap();
+    encoder.write_value((1, 2.5, "three")).unwrap();
+    encoder.write_key("matrix").unwrap();
+    encoder.write_value([[1, 0], [0, 1]]).unwrap();
+    encoder.write_key("maybe").unwrap();
+    encoder.write_value(vec![Some(1), None]).unwrap();
+    encoder.end_dict().unwrap();
+    let dict = encoder.finish_value().to_dict().unwrap();
+    assert_eq!(
+        json::to_json(&dict),
+        r#"{"matrix":[[1,0],[0,1]],"maybe":[1,null],"point":[1,2.5,"three"],"tags":{"colour":["red","green"],"size":[]}}"#
+    );
+
+    // Fleece values and collections are copied
+    let person = Value::from_bytes(PERSON_ENCODED).unwrap();
+    let mut mutable = MutableDict::clone_from(person.as_dict().unwrap());
+    mutable.insert("extra", true);
+    let mut encoder = Encoder::new();
+    encoder.begin_array(4).unwrap();
+    encoder.write_value(person).unwrap();
+    encoder.write_value(person.as_dict().unwrap()).unwrap();
+    encoder.write_value(&mutable).unwrap();
+    encoder
+        .write_value(&person.as_dict().unwrap()["tags"])
+        .unwrap();
+    encoder.end_array().unwrap();
+    let array = encoder.finish_value().to_array().unwrap();
+    assert_eq!(array.get(0).unwrap(), person);
+    assert_eq!(array.get(1).unwrap(), person);
+    assert_eq!(array.get(2).unwrap().as_dict().unwrap().len(), 22);
+    assert_eq!(array.get(3).unwrap(), &person.as_dict().unwrap()["tags"]);
+
+    // A collection can be the root, and an `Alloced` value can be written
+    let mut encoder = Encoder::new();
+    encoder.write_value(vec![(1, "one"), (2, "two")]).unwrap();
+    let root = encoder.finish_value();
+    assert_eq!(json::to_json(&root), r#"[[1,"one"],[2,"two"]]"#);
+    let mut encoder = Encoder::new();
+    encoder.write_value(&root).unwrap();
+    assert_eq!(&*encoder.finish_value(), &*root);
+
+    #[cfg(feature = "std")]
+    {
+        let map: std::collections::HashMap<String, u8> =
+            [(String::from("a"), 1), (String::from("b"), 2)].into();
+        let mut encoder = Encoder::new();
+        encoder.write_value(map).unwrap();
+        assert_eq!(json::to_json(&encoder.finish_value()), r#"{"a":1,"b":2}"#);
+    }
+
+    // Byte arrays and `Vec`s are Arrays of ints, only byte slices are Data
+    let bytes = [1u8, 2, 3];
+    let mut encoder = Encoder::new();
+    encoder.begin_array(3).unwrap();
+    encoder.write_value(bytes).unwrap();
+    encoder.write_value(bytes.to_vec()).unwrap();
+    encoder.write_value(&bytes[..]).unwrap();
+    encoder.end_array().unwrap();
+    let array = encoder.finish_value().to_array().unwrap();
+    assert_eq!(json::to_json(&array[0]), "[1,2,3]");
+    assert_eq!(json::to_json(&array[1]), "[1,2,3]");
+    assert_eq!(array[2].value_type(), ValueType::Data);
+    assert_eq!(array[2].to_data(), &bytes);
+
+    // The SliceEncoder can only write scalars
+    let mut buf = [0u8; 64];
+    let mut slice_encoder = encoder::SliceEncoder::new(&mut buf);
+    slice_encoder.begin_array().unwrap();
+    slice_encoder
+        .write_value(&person.as_dict().unwrap()["name"])
+        .unwrap();
+    assert!(matches!(
+        slice_encoder.write_value(vec![1]),
+        Err(encoder::EncodeError::ValueNotScalar)
+    ));
+}
+
+#[test]
+fn mutable_collections_from_encodable() {
+    use alloc::collections::BTreeMap;
+
+    let person = Value::from_bytes(PERSON_ENCODED).unwrap();
+    let tags = &person.as_dict().unwrap()["tags"];
+    let mut sizes = BTreeMap::new();
+    sizes.insert("small", 1);
+    sizes.insert("large", 3);
+    let items = [1, 2, 3, 4];
+
+    // Collections are stored as nested mutable collections, rather than as a scalar
+    let mut dict = MutableDict::new();
+    dict.insert("vec", vec!["a", "b"]);
+    dict.insert("map", &sizes);
+    dict.insert("tuple", (1, "one"));
+    dict.insert("tags", tags);
+    dict.insert("slice", encoder::ArraySlice(&items[1..]));
+    assert!(dict.get_array("vec").is_some());
+    assert!(dict.get_dict("map").is_some());
+    assert_eq!(
+        dict.get_array("tags").unwrap().len(),
+        tags.as_array().unwrap().len()
+    );
+
+    let mut array = MutableArray::new();
+    array.push(&[[1, 2], [3, 4]]);
+    array.push(&Vec::<i32>::new());
+    array.push(person.as_dict().unwrap());
+    array.set(1, sizes);
+
+    assert_eq!(
+        json::to_json(&dict),
+        r#"{"map":{"large":3,"small":1},"slice":[2,3,4],"tags":["quis","laborum","officia","adipisicing","et","laborum","tempor"],"tuple":[1,"one"],"vec":["a","b"]}"#
+    );
+    assert_eq!(json::to_json(array.get_array(0).unwrap()), "[[1,2],[3,4]]");
+    assert_eq!(
+        json::to_json(array.get_dict(1).unwrap()),
+        r#"{"large":3,"small":1}"#
+    );
+    assert_eq!(
+        array.get_dict(2).unwrap().len(),
+        person.as_dict().unwrap().len()
+    );
+
+    // A slice can also be written directly
+    let mut encoder = Encoder::new();
+    encoder
+        .write_value(encoder::ArraySlice(&items[..2]))
+        .unwrap();
+    assert_eq!(json::to_json(&encoder.finish_value()), "[1,2]");
+}
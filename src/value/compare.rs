@@ -0,0 +1,346 @@
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
+
+use super::{Array, Dict, Value, ValueType};
+
+/// A number decoded from any of the Fleece number encodings, for comparing numbers which were
+/// encoded differently.
+#[derive(Clone, Copy)]
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+impl Number {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value.value_type() {
+            ValueType::Short | ValueType::Int => Some(Number::Int(i128::from(value.to_int()))),
+            ValueType::UnsignedInt => Some(Number::Int(i128::from(value.to_unsigned_int()))),
+            ValueType::Float | ValueType::Double32 | ValueType::Double64 => {
+                Some(Number::Float(value.to_double()).normalized())
+            }
+            _ => None,
+        }
+    }
+
+    /// Floats with an integer value are converted to ints, so each number has one representation.
+    #[allow(clippy::cast_possible_truncation)]
+    fn normalized(self) -> Self {
+        match self {
+            // 2^100 is well within i128, and larger floats can't be equal to any Fleece int
+            Number::Float(float) if float.fract() == 0.0 && float.abs() < 2f64.powi(100) => {
+                Number::Int(float as i128)
+            }
+            other => other,
+        }
+    }
+
+    fn eq(self, other: Self) -> bool {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a == b,
+            // NaN is considered equal to itself, so that deep equality is an equivalence relation
+            (Number::Float(a), Number::Float(b)) => a == b || (a.is_nan() && b.is_nan()),
+            _ => false,
+        }
+    }
+
+    /// A total order of numbers, consistent with [`Number::eq`]. NaN is less than all other
+    /// numbers.
+    #[allow(clippy::cast_possible_truncation)]
+    fn cmp(self, other: Self) -> Ordering {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a.cmp(&b),
+            (Number::Float(a), Number::Float(b)) => match (a.is_nan(), b.is_nan()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                (false, false) => a.total_cmp(&b),
+            },
+            (Number::Int(int), Number::Float(float)) => {
+                if float.is_nan() {
+                    Ordering::Greater
+                } else if float.abs() >= 2f64.powi(100) {
+                    // Normalized floats this large are beyond the range of Fleece ints
+                    0f64.total_cmp(&float)
+                } else if int <= float.floor() as i128 {
+                    // Normalized floats in this range have a fraction, so can't equal an int
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            }
+            (Number::Float(_), Number::Int(_)) => other.cmp(self).reverse(),
+        }
+    }
+
+    fn hash<H: Hasher>(self, state: &mut H) {
+        match self {
+            Number::Int(int) => {
+                0u8.hash(state);
+                int.hash(state);
+            }
+            Number::Float(float) => {
+                1u8.hash(state);
+                let bits = if float.is_nan() {
+                    f64::NAN.to_bits()
+                } else {
+                    float.to_bits()
+                };
+                bits.hash(state);
+            }
+        }
+    }
+}
+
+impl Value {
+    /// Compare two values semantically, rather than by their encoding:
+    /// - Numbers are equal if they have the same value, regardless of whether they are encoded as
+    ///   a short, int, float or double. NaN is equal to NaN.
+    /// - Dicts are equal if they have the same keys and values, regardless of whether the keys
+    ///   are encoded with [`SharedKeys`](crate::SharedKeys).
+    /// - Arrays are equal if they have equal values in the same order.
+    /// - Collections are equal regardless of whether they are wide or narrow.
+    #[must_use]
+    pub fn deep_eq(&self, other: &Value) -> bool {
+        match (self.value_type(), other.value_type()) {
+            (ValueType::Array, ValueType::Array) => {
+                Array::from_value(self).deep_eq(Array::from_value(other))
+            }
+            (ValueType::Dict, ValueType::Dict) => {
+                Dict::from_value(self).deep_eq(Dict::from_value(other))
+            }
+            (ValueType::String, ValueType::String) | (ValueType::Data, ValueType::Data) => {
+                self._get_data() == other._get_data()
+            }
+            (a, b) => match (Number::from_value(self), Number::from_value(other)) {
+                (Some(a), Some(b)) => a.eq(b),
+                (None, None) => a == b,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Value {
+    /// A total order across all values, which is consistent with [`Value::deep_eq`]. Values of
+    /// different types are ordered:
+    ///
+    /// `undefined < null < false < true < numbers < strings < data < arrays < dicts`
+    ///
+    /// - Numbers are compared by value, regardless of how they are encoded. NaN is less than all
+    ///   other numbers.
+    /// - Strings are compared by Unicode code point, and data is compared byte-wise.
+    /// - Arrays are compared element by element, and a shorter array is less than a longer array
+    ///   which starts with the same elements.
+    /// - Dicts are compared as arrays of key-value pairs, sorted by key.
+    #[must_use]
+    pub fn collate(&self, other: &Value) -> Ordering {
+        let rank = Self::_collation_rank(self.value_type());
+        let other_rank = Self::_collation_rank(other.value_type());
+        if rank != other_rank {
+            return rank.cmp(&other_rank);
+        }
+        match self.value_type() {
+            ValueType::Array => Array::from_value(self).collate(Array::from_value(other)),
+            ValueType::Dict => Dict::from_value(self).collate(Dict::from_value(other)),
+            ValueType::String | ValueType::Data => self._get_data().cmp(other._get_data()),
+            _ => match (Number::from_value(self), Number::from_value(other)) {
+                (Some(a), Some(b)) => a.cmp(b),
+                _ => Ordering::Equal,
+            },
+        }
+    }
+
+    fn _collation_rank(value_type: ValueType) -> u8 {
+        match value_type {
+            ValueType::Undefined => 0,
+            ValueType::Null => 1,
+            ValueType::False => 2,
+            ValueType::True => 3,
+            ValueType::Short
+            | ValueType::Int
+            | ValueType::UnsignedInt
+            | ValueType::Float
+            | ValueType::Double32
+            | ValueType::Double64 => 4,
+            ValueType::String => 5,
+            ValueType::Data => 6,
+            ValueType::Array => 7,
+            ValueType::Dict => 8,
+            // Values are always dereferenced before being compared
+            ValueType::Pointer => 9,
+        }
+    }
+}
+
+impl Array {
+    /// See [`Value::collate`].
+    #[must_use]
+    pub fn collate(&self, other: &Array) -> Ordering {
+        for (a, b) in self.iter().zip(other) {
+            match a.collate(b) {
+                Ordering::Equal => {}
+                ordering => return ordering,
+            }
+        }
+        self.len().cmp(&other.len())
+    }
+
+    /// See [`Value::deep_eq`].
+    #[must_use]
+    pub fn deep_eq(&self, other: &Array) -> bool {
+        self.len() == other.len() && self.iter().zip(other).all(|(a, b)| a.deep_eq(b))
+    }
+}
+
+impl Dict {
+    /// See [`Value::deep_eq`].
+    #[must_use]
+    pub fn deep_eq(&self, other: &Dict) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        let mut count = 0;
+        for (key, value) in self {
+            let Some(other_value) = other.get(key) else {
+                return false;
+            };
+            if !value.deep_eq(other_value) {
+                return false;
+            }
+            count += 1;
+        }
+        // All keys should have been decoded, otherwise they weren't compared
+        count == self.len()
+    }
+
+    /// See [`Value::collate`].
+    #[must_use]
+    pub fn collate(&self, other: &Dict) -> Ordering {
+        let entries = self._sorted_entries();
+        let other_entries = other._sorted_entries();
+        for ((key, value), (other_key, other_value)) in entries.iter().zip(&other_entries) {
+            match key.cmp(other_key).then_with(|| value.collate(other_value)) {
+                Ordering::Equal => {}
+                ordering => return ordering,
+            }
+        }
+        entries.len().cmp(&other_entries.len())
+    }
+
+    /// The key-value pairs of this dict, sorted by key. Shared keys are sorted before other keys
+    /// in the encoded dict, so the encoded order can't be used to compare dicts.
+    fn _sorted_entries(&self) -> Vec<(&str, &Value)> {
+        let mut entries: Vec<(&str, &Value)> = self.into_iter().collect();
+        entries.sort_unstable_by_key(|(key, _)| *key);
+        entries
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.deep_eq(other)
+    }
+}
+
+impl Eq for Value {}
+
+impl PartialEq for Array {
+    fn eq(&self, other: &Self) -> bool {
+        self.deep_eq(other)
+    }
+}
+
+impl Eq for Array {}
+
+impl PartialEq for Dict {
+    fn eq(&self, other: &Self) -> bool {
+        self.deep_eq(other)
+    }
+}
+
+impl Eq for Dict {}
+
+/// Consistent with [`Value::deep_eq`], so equal values have equal hashes, however they were
+/// encoded.
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if let Some(number) = Number::from_value(self) {
+            state.write_u8(0);
+            number.hash(state);
+            return;
+        }
+        match self.value_type() {
+            ValueType::Array => Array::from_value(self).hash(state),
+            ValueType::Dict => Dict::from_value(self).hash(state),
+            value_type => {
+                state.write_u8(value_type as u8 + 1);
+                if matches!(value_type, ValueType::String | ValueType::Data) {
+                    self._get_data().hash(state);
+                }
+            }
+        }
+    }
+}
+
+impl Hash for Array {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u8(ValueType::Array as u8 + 1);
+        state.write_usize(self.len());
+        for value in self {
+            value.hash(state);
+        }
+    }
+}
+
+impl Hash for Dict {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u8(ValueType::Dict as u8 + 1);
+        state.write_usize(self.len());
+        // The order of keys depends on whether they are shared keys, so hash in key order
+        for (key, value) in self._sorted_entries() {
+            key.hash(state);
+            value.hash(state);
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Ordered by [`Value::collate`].
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.collate(other)
+    }
+}
+
+impl PartialOrd for Array {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Ordered by [`Value::collate`].
+impl Ord for Array {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.collate(other)
+    }
+}
+
+impl PartialOrd for Dict {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Ordered by [`Value::collate`].
+impl Ord for Dict {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.collate(other)
+    }
+}
@@ -0,0 +1,121 @@
+use alloc::{sync::Arc, vec::Vec};
+
+use super::{array, Value, ValueType};
+use crate::{Scope, SharedKeys};
+
+/// One step of the path to a value found by a [`DeepIterator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathItem<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+/// A depth-first iterator over every value in a Fleece tree, starting with the root.
+///
+/// The path from the root to the value most recently returned is available from
+/// [`DeepIterator::path`], so the root itself has an empty path. The path is kept in a single
+/// buffer which is updated as the iterator moves, so no allocation is made per value.
+///
+/// Dict keys are decoded with the [`SharedKeys`] of the [`Scope`] containing the data. Call
+/// [`DeepIterator::skip_children`] to avoid descending into the last value returned.
+pub struct DeepIterator<'a> {
+    root: Option<&'a Value>,
+    shared_keys: Option<Arc<SharedKeys>>,
+    stack: Vec<Frame<'a>>,
+    path: Vec<PathItem<'a>>,
+    // The last value returned, whose children will be visited next
+    current: Option<&'a Value>,
+}
+
+enum Frame<'a> {
+    Array(array::Iter<'a>, usize),
+    // A Dict is iterated as an Array of alternating keys and values
+    Dict(array::Iter<'a>),
+}
+
+impl<'a> DeepIterator<'a> {
+    #[must_use]
+    pub fn new(root: &'a Value) -> Self {
+        Self {
+            root: Some(root),
+            shared_keys: Scope::find_shared_keys(root.bytes.as_ptr()),
+            stack: Vec::new(),
+            path: Vec::new(),
+            current: None,
+        }
+    }
+
+    /// Don't descend into the children of the value most recently returned by `next`.
+    pub fn skip_children(&mut self) {
+        self.current = None;
+    }
+
+    /// The path of the value most recently returned by `next`.
+    #[must_use]
+    pub fn path(&self) -> &[PathItem<'a>] {
+        &self.path
+    }
+
+    fn _descend(&mut self, value: &'a Value) {
+        if let Some(dict) = value.as_dict() {
+            if !dict.is_empty() {
+                self.stack.push(Frame::Dict(dict.array.iter()));
+            }
+        } else if let Some(array) = value.as_array() {
+            if !array.is_empty() {
+                self.stack.push(Frame::Array(array.iter(), 0));
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for DeepIterator<'a> {
+    type Item = &'a Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            self.current = Some(root);
+            return Some(root);
+        }
+        if let Some(current) = self.current.take() {
+            self._descend(current);
+        }
+        loop {
+            let depth = self.stack.len();
+            let next = match self.stack.last_mut()? {
+                Frame::Array(iter, index) => {
+                    let next = iter.next().map(|value| (PathItem::Index(*index), value));
+                    *index += 1;
+                    next
+                }
+                Frame::Dict(iter) => match (iter.next(), iter.next()) {
+                    (Some(key), Some(value)) => {
+                        // As when iterating a Dict, an undecodable key ends the Dict
+                        _decode_key(key, self.shared_keys.as_deref())
+                            .map(|key| (PathItem::Key(key), value))
+                    }
+                    _ => None,
+                },
+            };
+            // This collection is finished, so continue with its parent
+            let Some((item, value)) = next else {
+                self.stack.pop();
+                continue;
+            };
+            self.path.truncate(depth - 1);
+            self.path.push(item);
+            self.current = Some(value);
+            return Some(value);
+        }
+    }
+}
+
+/// Decode a dict key, returning [`None`] if it is a shared key which can't be decoded.
+fn _decode_key<'a>(key: &'a Value, shared_keys: Option<&SharedKeys>) -> Option<&'a str> {
+    if key.value_type() != ValueType::Short {
+        return Some(key.to_str());
+    }
+    let decoded = shared_keys?.decode(key.to_unsigned_short())?;
+    // Shared keys are never removed, and the SharedKeys are retained by the Scope of the data
+    Some(unsafe { &*core::ptr::from_ref::<str>(decoded) })
+}
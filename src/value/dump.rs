@@ -0,0 +1,329 @@
+use alloc::{collections::BTreeMap, string::String};
+use core::fmt::{self, Write};
+
+use super::pointer::Pointer;
+use super::{Array, DecodeError, Value, ValueType};
+use crate::scope::Scope;
+
+/// The number of raw bytes shown on each line of a dump. Longer values are truncated with `…`.
+const DUMP_BYTES_PER_LINE: usize = 4;
+
+// Debug dumps
+impl Value {
+    /// Write an annotated hex dump of this value and every value reachable from it, for debugging.
+    /// Values are listed in the order they appear in the data, each with its byte offset, its raw
+    /// bytes, its type and its contents. Pointers are shown as `&`, followed by the value they
+    /// point to and the offset of that value.
+    ///
+    /// ```text
+    /// 0000: 43 61 62 63 : String "abc"
+    /// 0004: 70 01       : Dict[1] {
+    /// 0006: 41 61       :   String "a":
+    /// 0008: 80 04       :   &String "abc" (@0000)
+    ///                   : }
+    /// ```
+    ///
+    /// Offsets are relative to the first value in the dump. Use [`Value::dump_bytes`] to dump a
+    /// whole buffer, with offsets relative to its start.
+    /// ## Errors
+    /// If writing to `out` fails.
+    pub fn dump<W: fmt::Write>(&self, out: &mut W) -> fmt::Result {
+        let mut values = BTreeMap::new();
+        self._map_addresses(&mut values);
+        // The first value in the map has the lowest address
+        let base = values.keys().next().copied().unwrap_or_default();
+        Self::_dump_values(&values, base, out)
+    }
+
+    /// Write an annotated hex dump of Fleece data, as described in [`Value::dump`], followed by
+    /// the root pointer at the end of the data.
+    ///
+    /// If the data is not valid Fleece, the validation error is written, followed by a plain hex
+    /// dump of the data, as it isn't safe to follow the pointers in invalid data.
+    /// ## Errors
+    /// If writing to `out` fails.
+    pub fn dump_bytes<W: fmt::Write>(data: &[u8], out: &mut W) -> fmt::Result {
+        let Ok(root) = Value::from_bytes(data) else {
+            if let Some((offset, error)) = Value::validation_error(data) {
+                writeln!(out, "Invalid Fleece data at {offset:04x}: {error}")?;
+            }
+            for (i, line) in data.chunks(16).enumerate() {
+                write!(out, "{:04x}:", i * 16)?;
+                for byte in line {
+                    write!(out, " {byte:02x}")?;
+                }
+                writeln!(out)?;
+            }
+            return Ok(());
+        };
+        let mut values = BTreeMap::new();
+        root._map_addresses(&mut values);
+        let base = data.as_ptr() as usize;
+        Self::_dump_values(&values, base, out)?;
+        if data.len() > 2 {
+            let root_pointer = &data[data.len() - 2..];
+            Self::_dump_line_prefix(root_pointer, data.len() - 2, 0, out)?;
+            Self::_dump_brief(Self::_from_slice(root_pointer), false, base, out)?;
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Validate the data like [`Value::from_bytes`], but if it is invalid, also find the byte
+    /// offset of the value which caused the error. Returns [`None`] if the data is valid.
+    ///
+    /// This is slower than [`Value::from_bytes`], so should only be used to diagnose invalid data.
+    #[must_use]
+    pub fn validation_error(data: &[u8]) -> Option<(usize, DecodeError)> {
+        let error = Value::from_bytes(data).err()?;
+        let mut data_start = data.as_ptr();
+        let mut data_end = unsafe { data_start.add(data.len()) };
+        let root = match Self::_find_root(data, &mut data_start, &mut data_end) {
+            Ok(root) => root,
+            Err(DecodeError::InputIncorrectlySized) => {
+                return Some((0, DecodeError::InputIncorrectlySized))
+            }
+            Err(error) => return Some((data.len() - 2, error)),
+        };
+        let base = data.as_ptr() as usize;
+        // The search should always find the error, but fall back to reporting it at the root
+        root._find_invalid(data_start, data_end, base)
+            .or_else(|| Some((data.len() - 2, error)))
+    }
+
+    /// Validate this value, and the values it points to, one at a time, so the first invalid
+    /// value can be found.
+    fn _find_invalid(
+        &self,
+        data_start: *const u8,
+        data_end: *const u8,
+        base: usize,
+    ) -> Option<(usize, DecodeError)> {
+        let offset = (self.bytes.as_ptr() as usize).wrapping_sub(base);
+        if !matches!(self.value_type(), ValueType::Array | ValueType::Dict) {
+            return self
+                ._validate::<false>(false, data_start, data_end)
+                .err()
+                .map(|error| (offset, error));
+        }
+
+        let array = Array::from_value(self);
+        let width = array.width() as usize;
+        let first = self.bytes.as_ptr() as usize + array.first_pos();
+        if first + array.len() * width > data_end as usize {
+            let error = DecodeError::ArrayOutOfBounds {
+                count: array.len(),
+                width,
+                available_size: (data_end as usize).saturating_sub(first),
+            };
+            return Some((offset, error));
+        }
+        for (i, item) in Self::_raw_items(array).enumerate() {
+            let item_offset = first - base + i * width;
+            if item.value_type() != ValueType::Pointer {
+                // Inline items are validated in place, as `Array::validate` does
+                let next = unsafe { item.bytes.as_ptr().add(width) };
+                let result = Value::_from_raw(item.bytes.as_ptr(), width)
+                    .and_then(|item| item._validate::<true>(array.is_wide(), data_start, next));
+                if let Err(error) = result {
+                    return Some((item_offset, error));
+                }
+                continue;
+            }
+            let mut target_start = data_start;
+            let mut target_end = item.bytes.as_ptr();
+            let target = Pointer::from_value(item).deref_checked(
+                array.is_wide(),
+                &mut target_start,
+                &mut target_end,
+            );
+            let result = match target {
+                Ok(target) => target._find_invalid(target_start, target_end, base),
+                Err(error) => Some((item_offset, error)),
+            };
+            if result.is_some() {
+                return result;
+            }
+        }
+        None
+    }
+
+    /// Find every value reachable from this one through pointers, keyed by address.
+    fn _map_addresses<'a>(&'a self, values: &mut BTreeMap<usize, &'a Value>) {
+        values.insert(self.bytes.as_ptr() as usize, self);
+        if !matches!(self.value_type(), ValueType::Array | ValueType::Dict) {
+            return;
+        }
+        let array = Array::from_value(self);
+        for item in Self::_raw_items(array) {
+            if item.value_type() != ValueType::Pointer {
+                continue;
+            }
+            let pointer = Pointer::from_value(item);
+            // Extern pointers aren't followed, as their targets aren't part of this data
+            if pointer.is_external() {
+                continue;
+            }
+            let target = unsafe { pointer.deref_unchecked(array.is_wide()) };
+            if !values.contains_key(&(target.bytes.as_ptr() as usize)) {
+                target._map_addresses(values);
+            }
+        }
+    }
+
+    /// The items of an array (or the keys and values of a dict), without dereferencing pointers.
+    fn _raw_items(array: &Array) -> impl Iterator<Item = &Value> {
+        let width = array.width();
+        let first_pos = array.first_pos();
+        #[allow(clippy::cast_possible_wrap)]
+        (0..array.len()).map(move |i| unsafe {
+            array
+                .value
+                ._offset_unchecked((first_pos + i * width as usize) as isize, width)
+        })
+    }
+
+    fn _dump_values<W: fmt::Write>(
+        values: &BTreeMap<usize, &Value>,
+        base: usize,
+        out: &mut W,
+    ) -> fmt::Result {
+        for (address, value) in values {
+            let offset = address - base;
+            if matches!(value.value_type(), ValueType::Array | ValueType::Dict) {
+                Self::_dump_collection(Array::from_value(value), offset, base, out)?;
+            } else {
+                let size = value.required_size().min(value.bytes.len());
+                Self::_dump_line_prefix(&value.bytes[..size], offset, 0, out)?;
+                Self::_dump_brief(value, false, base, out)?;
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn _dump_collection<W: fmt::Write>(
+        array: &Array,
+        offset: usize,
+        base: usize,
+        out: &mut W,
+    ) -> fmt::Result {
+        let is_dict = array.value.value_type() == ValueType::Dict;
+        let is_wide = array.is_wide();
+        let width = array.width() as usize;
+        let header_size = array.first_pos();
+        Self::_dump_line_prefix(&array.value.bytes[..header_size], offset, 0, out)?;
+        Self::_dump_brief(&array.value, is_wide, base, out)?;
+        writeln!(out, " {}", if is_dict { '{' } else { '[' })?;
+
+        let shared_keys = if is_dict {
+            Scope::find_shared_keys(array.value.bytes.as_ptr())
+        } else {
+            None
+        };
+        for (i, item) in Self::_raw_items(array).enumerate() {
+            let item_offset = offset + header_size + i * width;
+            Self::_dump_line_prefix(&item.bytes[..width], item_offset, 1, out)?;
+            Self::_dump_brief(item, is_wide, base, out)?;
+            if is_dict && i % 2 == 0 {
+                if item.value_type() == ValueType::Short {
+                    if let Some(key) = shared_keys
+                        .as_ref()
+                        .and_then(|sk| sk.decode(item.to_unsigned_short()))
+                    {
+                        write!(out, " = {key:?}")?;
+                    }
+                }
+                write!(out, ":")?;
+            }
+            writeln!(out)?;
+        }
+        writeln!(
+            out,
+            "{:w$}: {}",
+            "",
+            if is_dict { '}' } else { ']' },
+            w = 6 + DUMP_BYTES_PER_LINE * 3
+        )
+    }
+
+    /// Write the offset and raw bytes of a line, padded to the same width for every line, followed
+    /// by the indentation of the value.
+    fn _dump_line_prefix<W: fmt::Write>(
+        bytes: &[u8],
+        offset: usize,
+        indent: usize,
+        out: &mut W,
+    ) -> fmt::Result {
+        let mut hex = String::new();
+        for (i, byte) in bytes.iter().take(DUMP_BYTES_PER_LINE).enumerate() {
+            if i > 0 {
+                hex.push(' ');
+            }
+            write!(hex, "{byte:02x}")?;
+        }
+        if bytes.len() > DUMP_BYTES_PER_LINE {
+            hex.push('…');
+        }
+        write!(
+            out,
+            "{offset:04x}: {hex:<w$}: {:indent$}",
+            "",
+            w = DUMP_BYTES_PER_LINE * 3,
+            indent = indent * 2
+        )
+    }
+
+    /// Write a one-line description of a value. Collections are described by their type and
+    /// length, and pointers by their target.
+    fn _dump_brief<W: fmt::Write>(
+        value: &Value,
+        is_wide: bool,
+        base: usize,
+        out: &mut W,
+    ) -> fmt::Result {
+        let value_type = value.value_type();
+        match value_type {
+            ValueType::Null | ValueType::Undefined | ValueType::False | ValueType::True => {
+                write!(out, "{value_type:?}")
+            }
+            ValueType::Short | ValueType::Int => write!(out, "{value_type:?} {}", value.to_int()),
+            ValueType::UnsignedInt => {
+                write!(out, "{value_type:?} {}", value.to_unsigned_int())
+            }
+            ValueType::Float | ValueType::Double32 | ValueType::Double64 => {
+                write!(out, "{value_type:?} {:?}", value.to_double())
+            }
+            ValueType::String => write!(out, "String {:?}", value.to_str()),
+            ValueType::Data => write!(out, "Data[{}]", value.to_data().len()),
+            ValueType::Array | ValueType::Dict => {
+                let array = Array::from_value(value);
+                let len = if value_type == ValueType::Dict {
+                    array.len() / 2
+                } else {
+                    array.len()
+                };
+                write!(out, "{value_type:?}[{len}]")?;
+                if array.is_wide() {
+                    write!(out, " (wide)")?;
+                }
+                Ok(())
+            }
+            ValueType::Pointer => {
+                let pointer = Pointer::from_value(value);
+                if pointer.is_external() {
+                    let offset = unsafe { pointer.get_offset(is_wide) };
+                    return write!(out, "&extern (-{offset:04x})");
+                }
+                let target = unsafe { pointer.deref_unchecked(is_wide) };
+                write!(out, "&")?;
+                Self::_dump_brief(target, is_wide, base, out)?;
+                write!(out, " (@{:04x})", target.bytes.as_ptr() as usize - base)
+            }
+        }
+    }
+
+    fn _from_slice(bytes: &[u8]) -> &Value {
+        unsafe { core::mem::transmute(bytes) }
+    }
+}
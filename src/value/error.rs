@@ -20,6 +20,7 @@ pub enum DecodeError {
         target: usize,
         offset: u32,
     },
+    UnresolvedExternPointer,
     ArrayOutOfBounds {
         count: usize,
         width: usize,
@@ -49,6 +50,7 @@ impl fmt::Display for DecodeError {
                 target,
                 offset,
             } => write!(f, "Pointer with offset {offset} target {target:#x} outside of source data (start: {data_start:#x})"),
+            DecodeError::UnresolvedExternPointer => write!(f, "Extern pointer found, but no extern destination to resolve it in"),
             DecodeError::ArrayOutOfBounds {
                 count,
                 width,
@@ -1,7 +1,10 @@
 #![allow(clippy::transmute_ptr_to_ptr)]
 
 pub(crate) mod array;
+mod compare;
+mod deep_iterator;
 pub(crate) mod dict;
+mod dump;
 pub(crate) mod pointer;
 mod sized;
 pub(crate) mod varint;
@@ -12,6 +15,7 @@ use core::cmp::Ordering;
 use core::fmt;
 
 pub use array::Array;
+pub use deep_iterator::{DeepIterator, PathItem};
 pub use dict::Dict;
 pub use sized::SizedValue;
 
@@ -25,7 +29,7 @@ pub struct Value {
     pub(super) bytes: [u8],
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(PartialEq, Eq, Clone, Copy)]
 pub enum ValueType {
     Null,
     Undefined,
@@ -137,9 +141,9 @@ impl Value {
     /// ## Errors
     /// If the data given is not valid Fleece data
     pub fn from_bytes(data: &[u8]) -> Result<&Self> {
-        let root = Self::_find_root(data)?;
-        let data_start = data.as_ptr();
-        let data_end = unsafe { data_start.add(data.len()) };
+        let mut data_start = data.as_ptr();
+        let mut data_end = unsafe { data_start.add(data.len()) };
+        let root = Self::_find_root(data, &mut data_start, &mut data_end)?;
         // wide parameter doesn't matter here, as it's only used for pointers, and find_root will
         // never return a pointer.
         root._validate::<false>(false, data_start, data_end)?;
@@ -266,7 +270,10 @@ impl Value {
             ValueType::Short => i64::from(self.to_short()),
             ValueType::Int | ValueType::UnsignedInt => {
                 let count = (self.bytes[0] & 0x07) as usize + 1;
-                let mut buf = [0u8; 8];
+                // Sign extend signed ints which are stored in fewer than 8 bytes
+                let is_negative =
+                    self.value_type() == ValueType::Int && self.bytes[count] & 0x80 != 0;
+                let mut buf = if is_negative { [0xFFu8; 8] } else { [0u8; 8] };
                 unsafe {
                     core::ptr::copy_nonoverlapping(
                         self.bytes[1..].as_ptr(),
@@ -353,7 +360,12 @@ impl Value {
 impl Value {
     /// Finds the root Fleece value in the data. Performs basic validation that the data is
     /// correctly sized. To ensure the validity of the Fleece data, one should also call `RawValue::validate()`
-    fn _find_root(data: &[u8]) -> Result<&Self> {
+    /// If the root is in an extern destination, `data_start` and `data_end` are updated to its bounds.
+    fn _find_root<'a>(
+        data: &'a [u8],
+        data_start: &mut *const u8,
+        data_end: &mut *const u8,
+    ) -> Result<&'a Self> {
         // Data must be at least 2 bytes, and evenly sized
         if data.is_empty() || data.len() % 2 != 0 {
             return Err(DecodeError::InputIncorrectlySized);
@@ -363,7 +375,7 @@ impl Value {
         let root: &Value = unsafe { core::mem::transmute(root) };
 
         if root.value_type() == ValueType::Pointer {
-            return Pointer::from_value(root).deref_checked(false, data.as_ptr());
+            return Pointer::from_value(root).deref_checked(false, data_start, data_end);
         } else if data.len() == 2 {
             return Ok(root);
         }
@@ -381,8 +393,15 @@ impl Value {
                 Array::from_value(self).validate(data_start, data_end)
             }
             ValueType::Pointer => {
-                let target = Pointer::from_value(self).deref_checked(is_wide, data_start)?;
-                target._validate::<false>(is_wide, data_start, self.bytes.as_ptr())
+                let mut target_start = data_start;
+                // A pointer can only point backwards, so its target must end before the pointer
+                let mut target_end = self.bytes.as_ptr();
+                let target = Pointer::from_value(self).deref_checked(
+                    is_wide,
+                    &mut target_start,
+                    &mut target_end,
+                )?;
+                target._validate::<false>(is_wide, target_start, target_end)
             }
             _ => {
                 // We don't need to validate that array elements fit within the data, as
@@ -402,6 +421,14 @@ impl Value {
         }
     }
 
+    /// The encoded bytes of a scalar value, or [`None`] if this is a collection or a pointer.
+    pub(crate) fn _scalar_bytes(&self) -> Option<&[u8]> {
+        match self.value_type() {
+            ValueType::Array | ValueType::Dict | ValueType::Pointer => None,
+            _ => self.bytes.get(..self.required_size()),
+        }
+    }
+
     // The number of bytes required to hold this value
     // For Dict and Array, this does not include the size of elements, only the header
     #[allow(clippy::match_same_arms)]
@@ -417,11 +444,18 @@ impl Value {
             ValueType::Float | ValueType::Double32 => 6,
             ValueType::Double64 => 10,
             ValueType::String | ValueType::Data => {
-                let len = self._get_data().len();
-                match len {
-                    0 | 1 => 2,
-                    2..=0x0E => 1 + len,
-                    _ => 1 + varint::size_required(len as u64) + len,
+                // Read the size from the header, as the data itself may not fit in `bytes`
+                let inline_size = self.bytes[0] & 0x0F;
+                if inline_size == 0x0F {
+                    let (bytes_read, len) = varint::read(&self.bytes[1..]);
+                    if bytes_read == 0 {
+                        // The varint is truncated, so it continues past the available bytes
+                        return self.bytes.len() + 1;
+                    }
+                    #[allow(clippy::cast_possible_truncation)]
+                    (1 + bytes_read).saturating_add(len as usize)
+                } else {
+                    (1 + inline_size as usize).max(2)
                 }
             }
             // TODO: This is not correct for MutableArray / MutableDict
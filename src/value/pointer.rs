@@ -1,5 +1,6 @@
 use super::error::Result;
 use super::{Value, ValueType};
+use crate::scope::Scope;
 use crate::value::error::DecodeError;
 
 /// Internally identical to `RawValue`, this is just used to separate out some functionality.
@@ -11,6 +12,8 @@ pub(crate) struct Pointer {
 // The maximum offset that can be stored by a Fleece pointer, while being able to fit the tag, and the external tag
 pub const MAX_NARROW: u16 = 0x3fff;
 pub const MAX_WIDE: u32 = 0x3fff_ffff;
+// Set in the first byte of pointers which point into an extern destination
+pub const EXTERN_FLAG: u8 = 0x40;
 
 impl Pointer {
     #[allow(clippy::transmute_ptr_to_ptr)]
@@ -19,7 +22,17 @@ impl Pointer {
         unsafe { core::mem::transmute(value) }
     }
 
-    pub(crate) fn deref_checked(&self, wide: bool, data_start: *const u8) -> Result<&Value> {
+    /// Dereferences the pointer, validating that the target lies within the data. If the target
+    /// is in an extern destination, `data_start` and `data_end` are updated to the bounds of the
+    /// extern destination.
+    /// Extern pointers are resolved using the extern destination registered for the data they
+    /// are in, by [`Scope::new_with_extern`].
+    pub(crate) fn deref_checked(
+        &self,
+        wide: bool,
+        data_start: &mut *const u8,
+        data_end: &mut *const u8,
+    ) -> Result<&Value> {
         if (wide && self.value.bytes.len() < 4) || self.value.bytes.len() < 2 {
             return Err(DecodeError::PointerTooSmall {
                 actual: self.value.bytes.len(),
@@ -33,26 +46,43 @@ impl Pointer {
         }
 
         // First get the pointer given by offset, so we can validate before de-referencing
-        #[allow(clippy::cast_possible_wrap)]
-        let target_ptr = unsafe { self.offset(-(offset as isize)) };
+        let mut target_ptr = self.target(offset);
 
         // Is this pointer external to the source data?
-        if self.value.bytes[0] & 0x40 != 0 {
-            // return resolve_external_pointer(target_ptr, data_start, data_end);
-            unimplemented!()
+        if self.is_external() {
+            // Look up the data this pointer is in, rather than the data being validated, as the
+            // pointer may be in an extern destination which has an extern destination of its own
+            let (source_start, extern_destination) =
+                Scope::find_extern_destination(self.value.bytes.as_ptr())
+                    .ok_or(DecodeError::UnresolvedExternPointer)?;
+            let Some(extern_ptr) = resolve_extern(offset, self, source_start, &extern_destination)
+            else {
+                return Err(DecodeError::PointerTargetOutOfBounds {
+                    data_start: extern_destination.as_ptr() as usize,
+                    target: target_ptr as usize,
+                    offset,
+                });
+            };
+            target_ptr = extern_ptr;
+            *data_start = extern_destination.as_ptr();
+            *data_end = unsafe { extern_destination.as_ptr().add(extern_destination.len()) };
             // If the pointer isn't external, it should fit within the source data
-        } else if target_ptr < data_start {
+        } else if !matches!(
+            (self.value.bytes.as_ptr() as usize).checked_sub(*data_start as usize),
+            Some(distance) if offset as usize <= distance
+        ) {
             return Err(DecodeError::PointerTargetOutOfBounds {
-                data_start: data_start as usize,
+                data_start: *data_start as usize,
                 target: target_ptr as usize,
                 offset,
             });
         }
 
-        let target = unsafe { Value::_from_raw_unchecked(target_ptr, offset as usize) };
+        let available_size = *data_end as usize - target_ptr as usize;
+        let target = unsafe { Value::_from_raw_unchecked(target_ptr, available_size) };
 
         if target.value_type() == ValueType::Pointer {
-            return Pointer::from_value(target).deref_checked(true, data_start);
+            return Pointer::from_value(target).deref_checked(true, data_start, data_end);
         }
         Ok(target)
     }
@@ -64,8 +94,11 @@ impl Pointer {
         let offset = unsafe { self.get_offset(wide) };
         debug_assert_ne!(offset, 0);
 
-        #[allow(clippy::cast_possible_wrap)]
-        let target_ptr = self.offset(-(offset as isize));
+        if self.is_external() {
+            return self.deref_extern_unchecked(offset);
+        }
+
+        let target_ptr = self.target(offset);
 
         let target = Value::_from_raw_unchecked(target_ptr, offset as usize);
 
@@ -75,9 +108,43 @@ impl Pointer {
         target
     }
 
+    /// Dereferences an extern pointer, using the extern destination registered for the data which
+    /// contains this pointer. The destination is retained by the [`Scope`], and by values such as
+    /// [`Scope::root`] which are read from it, so it outlives the [`Scope`] itself.
+    /// # Panics
+    /// If the extern destination has been deallocated, so the pointer can't be resolved. Data which
+    /// may be in this state should be checked with [`Value::from_bytes`], which returns
+    /// [`DecodeError::UnresolvedExternPointer`].
+    #[cold]
+    #[inline(never)]
+    unsafe fn deref_extern_unchecked(&self, offset: u32) -> &Value {
+        let (source_start, extern_destination) =
+            Scope::find_extern_destination(self.value.bytes.as_ptr())
+                .expect("extern pointer read after its extern destination was deallocated");
+        let target_ptr = resolve_extern(offset, self, source_start, &extern_destination)
+            .expect("extern pointer target outside of the extern destination");
+        let available_size =
+            extern_destination.as_ptr() as usize + extern_destination.len() - target_ptr as usize;
+
+        let target = Value::_from_raw_unchecked(target_ptr, available_size);
+
+        if target.value_type() == ValueType::Pointer {
+            return Pointer::from_value(target).deref_unchecked(true);
+        }
+        target
+    }
+
+    /// Whether this pointer points into an extern destination, rather than the data it is in.
+    #[inline]
+    pub fn is_external(&self) -> bool {
+        self.value.bytes[0] & EXTERN_FLAG != 0
+    }
+
+    /// The address `offset` bytes before this pointer. This may lie outside of the data (extern
+    /// pointers always do), so it must be bounds checked before it is dereferenced.
     #[inline]
-    unsafe fn offset(&self, offset: isize) -> *const u8 {
-        self.value.bytes.as_ptr().offset(offset)
+    fn target(&self, offset: u32) -> *const u8 {
+        self.value.bytes.as_ptr().wrapping_sub(offset as usize)
     }
 
     #[inline]
@@ -93,3 +160,28 @@ impl Pointer {
         }
     }
 }
+
+/// Map the target of an extern pointer into the extern destination. An extern pointer points to
+/// some distance before the start of its data; that same distance before the end of the extern
+/// destination is the real target.
+/// Returns [`None`] if the pointer is not in the data starting at `data_start`, or if the target
+/// does not lie within the extern destination.
+fn resolve_extern(
+    offset: u32,
+    pointer: &Pointer,
+    data_start: *const u8,
+    extern_destination: &[u8],
+) -> Option<*const u8> {
+    // Work with addresses, as the target is outside of any allocation
+    let pointer_address = pointer.value.bytes.as_ptr() as usize;
+    let distance = pointer_address.checked_sub(data_start as usize)?;
+    let overhang = (offset as usize).checked_sub(distance)?;
+    if overhang == 0 || overhang > extern_destination.len() {
+        return None;
+    }
+    Some(unsafe {
+        extern_destination
+            .as_ptr()
+            .add(extern_destination.len() - overhang)
+    })
+}
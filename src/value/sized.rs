@@ -17,6 +17,13 @@ impl SizedValue {
         }
     }
 
+    /// Construct a [`SizedValue`] from its raw bytes, as returned by [`SizedValue::as_bytes`].
+    #[must_use]
+    #[inline]
+    pub(crate) fn from_bytes(bytes: [u8; 4]) -> Self {
+        Self { bytes }
+    }
+
     #[must_use]
     #[inline]
     pub(crate) fn new_pointer(offset: u32) -> Option<Self> {
@@ -49,7 +49,7 @@ pub const fn size_required(value: u64) -> usize {
     if value == 0 {
         1
     } else {
-        (63 - value.leading_zeros()) as usize / 7 + 1
+        value.ilog2() as usize / 7 + 1
     }
 }
 
@@ -0,0 +1,75 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn fleece(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fleece"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    assert!(output.status.success(), "{output:?}");
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+#[test]
+fn encode_json_round_trip() {
+    let encoded = fleece(&["encode", "-"], br#"{"name": "Jeff", "tags": [1, 2.5]}"#);
+    assert!(encoded.status.success());
+    let json = fleece(&["json", "-"], &encoded.stdout);
+    assert_eq!(stdout(&json), "{\"name\":\"Jeff\",\"tags\":[1,2.5]}\n");
+
+    let encoded = fleece(&["encode", "--json5", "-"], b"{name: 'Jeff'}");
+    let query = fleece(&["query", "name", "-"], &encoded.stdout);
+    assert_eq!(stdout(&query), "\"Jeff\"\n");
+}
+
+#[test]
+fn inspect_people() {
+    let validate = fleece(&["validate", "1000people.fleece"], b"");
+    assert!(stdout(&validate).contains("valid"));
+
+    let query = fleece(&["query", "[2].name", "1000people.fleece"], b"");
+    assert!(stdout(&query).starts_with('"'));
+    let missing = fleece(&["query", "[5000]", "1000people.fleece"], b"");
+    assert_eq!(missing.status.code(), Some(1));
+
+    let stats = fleece(&["stats", "1000people.fleece"], b"");
+    assert!(stdout(&stats).contains("Root:       Array"));
+
+    let dump = fleece(&["dump", "1person.fleece"], b"");
+    assert!(stdout(&dump).contains(": Dict[21] {"));
+}
+
+#[test]
+fn invalid_input() {
+    let mut person = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/1person.fleece")).unwrap();
+    // Point the root pointer at itself
+    let len = person.len();
+    person[len - 2..].copy_from_slice(&[0x80, 0x00]);
+    let validate = fleece(&["validate", "-"], &person);
+    assert_eq!(validate.status.code(), Some(1));
+    assert_eq!(
+        std::str::from_utf8(&validate.stderr).unwrap(),
+        "fleece: invalid Fleece data at offset 0x042c: Pointer offset of 0\n"
+    );
+
+    let usage = fleece(&["frobnicate"], b"");
+    assert_eq!(usage.status.code(), Some(2));
+
+    // Options are only accepted by the commands they apply to
+    let unknown = fleece(&["json", "--prety", "-"], &person);
+    assert_eq!(unknown.status.code(), Some(2));
+    assert!(std::str::from_utf8(&unknown.stderr)
+        .unwrap()
+        .starts_with("fleece: unknown option `--prety` for `json`\n"));
+    let misplaced = fleece(&["stats", "--json5", "-"], &person);
+    assert_eq!(misplaced.status.code(), Some(2));
+}
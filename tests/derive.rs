@@ -0,0 +1,270 @@
+use fleece::convert::FromFleeceError;
+use fleece::encoder::EncodeError;
+use fleece::{Encoder, FromFleece, MutableArray, MutableDict, ToFleece, Value, ValueType};
+
+#[derive(ToFleece, FromFleece, PartialEq, Debug)]
+struct Address {
+    street: String,
+    city: String,
+}
+
+#[derive(ToFleece, FromFleece, PartialEq, Debug)]
+struct Contact {
+    email: Option<String>,
+    phone: Option<String>,
+}
+
+#[derive(ToFleece, FromFleece, PartialEq, Debug)]
+struct Person {
+    name: String,
+    #[fleece(rename = "yearsOld")]
+    age: u32,
+    #[fleece(default)]
+    tags: Vec<String>,
+    #[fleece(skip)]
+    cached_score: u64,
+    #[fleece(flatten)]
+    contact: Contact,
+    addresses: Vec<Address>,
+}
+
+#[derive(ToFleece, FromFleece, PartialEq, Debug)]
+struct Wrapper<T> {
+    inner: T,
+}
+
+// Neither `ToFleece` nor `FromFleece`, so it can only be used by skipped fields
+#[derive(Default, PartialEq, Debug)]
+struct NotFleece;
+
+#[derive(ToFleece, FromFleece, PartialEq, Debug)]
+struct Cached<T, C> {
+    value: T,
+    #[fleece(skip)]
+    cache: Option<C>,
+}
+
+#[derive(ToFleece)]
+struct ContactCard {
+    email: String,
+    #[fleece(flatten)]
+    contact: Contact,
+}
+
+#[derive(ToFleece)]
+struct TwoWrappers<T> {
+    #[fleece(flatten)]
+    first: Wrapper<String>,
+    #[fleece(flatten)]
+    second: T,
+}
+
+fn encode<T: fleece::encoder::Encodable>(value: T) -> Vec<u8> {
+    let mut encoder = Encoder::new();
+    encoder.write_value(value).unwrap();
+    encoder.finish()
+}
+
+fn person() -> Person {
+    Person {
+        name: "Alice".into(),
+        age: 37,
+        tags: vec!["admin".into()],
+        cached_score: 12,
+        contact: Contact {
+            email: Some("alice@example.com".into()),
+            phone: None,
+        },
+        addresses: vec![
+            Address {
+                street: "1 Main St".into(),
+                city: "Springfield".into(),
+            },
+            Address {
+                street: "2 High St".into(),
+                city: "Shelbyville".into(),
+            },
+        ],
+    }
+}
+
+#[test]
+fn round_trip() {
+    let data = encode(person());
+    let dict = Value::from_bytes(&data).unwrap().as_dict().unwrap();
+    assert_eq!(dict.get("yearsOld").unwrap().to_unsigned_int(), 37);
+    assert!(dict.get("age").is_none());
+    assert!(dict.get("cached_score").is_none());
+    // Flattened fields are in the same Dict, and `None` is written as null
+    assert_eq!(dict.get("email").unwrap().to_str(), "alice@example.com");
+    assert_eq!(dict.get("phone").unwrap().value_type(), ValueType::Null);
+    assert!(dict.get("contact").is_none());
+
+    let decoded = Person::from_fleece(Value::from_bytes(&data).unwrap()).unwrap();
+    assert_eq!(
+        decoded,
+        Person {
+            cached_score: 0,
+            ..person()
+        }
+    );
+}
+
+#[test]
+fn write_fields_into_open_dict() {
+    let mut encoder = Encoder::new();
+    encoder.begin_dict().unwrap();
+    encoder.write_key("extra").unwrap();
+    encoder.write_value(true).unwrap();
+    person().contact.write_fields(&mut encoder).unwrap();
+    encoder.end_dict().unwrap();
+    let data = encoder.finish();
+    let dict = Value::from_bytes(&data).unwrap().as_dict().unwrap();
+    assert_eq!(dict.len(), 3);
+}
+
+#[test]
+fn generic_struct() {
+    let data = encode(&Wrapper {
+        inner: vec![1, 2, 3],
+    });
+    let decoded = Wrapper::<Vec<i32>>::from_fleece(Value::from_bytes(&data).unwrap()).unwrap();
+    assert_eq!(decoded.inner, [1, 2, 3]);
+}
+
+#[test]
+fn missing_fields() {
+    let mut encoder = Encoder::new();
+    encoder.begin_dict().unwrap();
+    encoder.write_key("name").unwrap();
+    encoder.write_value("Bob").unwrap();
+    encoder.write_key("yearsOld").unwrap();
+    encoder.write_value(30).unwrap();
+    encoder.write_key("addresses").unwrap();
+    encoder.begin_array(0).unwrap();
+    encoder.end_array().unwrap();
+    encoder.end_dict().unwrap();
+    let data = encoder.finish();
+
+    // `tags` has a default, and the `Option` fields of `contact` may be missing
+    let decoded = Person::from_fleece(Value::from_bytes(&data).unwrap()).unwrap();
+    assert!(decoded.tags.is_empty());
+    assert_eq!(
+        decoded.contact,
+        Contact {
+            email: None,
+            phone: None
+        }
+    );
+
+    let error = Wrapper::<u8>::from_fleece(Value::from_bytes(&data).unwrap()).unwrap_err();
+    assert!(matches!(error, FromFleeceError::MissingField("inner")));
+}
+
+#[test]
+fn invalid_types() {
+    let data = encode(&Wrapper { inner: "text" });
+    let root = Value::from_bytes(&data).unwrap();
+    let error = Wrapper::<u32>::from_fleece(root).unwrap_err();
+    assert!(matches!(
+        error,
+        FromFleeceError::InvalidType {
+            expected: "Integer",
+            found: ValueType::String
+        }
+    ));
+
+    let data = encode(&Wrapper { inner: 300 });
+    let error = Wrapper::<u8>::from_fleece(Value::from_bytes(&data).unwrap()).unwrap_err();
+    assert!(matches!(error, FromFleeceError::OutOfRange));
+
+    let data = encode([1, 2]);
+    let error = Address::from_fleece(Value::from_bytes(&data).unwrap()).unwrap_err();
+    assert!(matches!(
+        error,
+        FromFleeceError::InvalidType {
+            expected: "Dict",
+            found: ValueType::Array
+        }
+    ));
+}
+
+#[test]
+fn insert_into_mutable_collections() {
+    // A derived struct is stored as a nested MutableDict, like the built-in collections
+    let mut dict = MutableDict::new();
+    dict.insert("person", person());
+    let nested = dict.get_dict("person").unwrap();
+    assert_eq!(nested.get("name").unwrap().to_str(), "Alice");
+    assert_eq!(nested.get_array("addresses").unwrap().len(), 2);
+
+    let mut array = MutableArray::new();
+    array.push(&person().addresses[1]);
+    let address = array.get_dict(0).unwrap();
+    assert_eq!(address.get("city").unwrap().to_str(), "Shelbyville");
+}
+
+#[test]
+fn skipped_generic_fields() {
+    let data = encode(&Cached {
+        value: 1,
+        cache: Some(NotFleece),
+    });
+    let decoded = Cached::<u8, NotFleece>::from_fleece(Value::from_bytes(&data).unwrap()).unwrap();
+    assert_eq!(
+        decoded,
+        Cached {
+            value: 1,
+            cache: None
+        }
+    );
+}
+
+#[test]
+fn flattened_duplicate_keys() {
+    // `email` is written by both the struct and the flattened `Contact`
+    let card = ContactCard {
+        email: "alice@example.com".into(),
+        contact: person().contact,
+    };
+    let mut encoder = Encoder::new();
+    let error = encoder.write_value(&card).unwrap_err();
+    assert!(matches!(error, EncodeError::DuplicateKey("email")));
+
+    // And `inner` by two flattened fields
+    let wrappers = TwoWrappers {
+        first: Wrapper {
+            inner: "Alice".into(),
+        },
+        second: Wrapper { inner: 1 },
+    };
+    let mut encoder = Encoder::new();
+    let error = encoder.write_value(&wrappers).unwrap_err();
+    assert!(matches!(error, EncodeError::DuplicateKey("inner")));
+
+    // Keys of flattened fields are listed, so collisions with them can be found
+    assert_eq!(Person::KEYS.keys, ["name", "yearsOld", "tags", "addresses"]);
+    assert_eq!(Person::KEYS.flattened[0].keys, ["email", "phone"]);
+    assert_eq!(Person::KEYS.duplicate(), None);
+}
+
+// `ToFleece` can be implemented without the derive, and makes a type `Encodable`
+struct Point(i32, i32);
+
+impl ToFleece for Point {
+    fn write_fields(&self, encoder: &mut Encoder) -> Result<(), EncodeError> {
+        encoder.write_key("x")?;
+        encoder.write_value(self.0)?;
+        encoder.write_key("y")?;
+        encoder.write_value(self.1)
+    }
+}
+
+#[test]
+fn manual_to_fleece() {
+    let data = encode(vec![Point(1, 2), Point(3, 4)]);
+    let array = Value::from_bytes(&data).unwrap().as_array().unwrap();
+    let point = array.get(1).unwrap().as_dict().unwrap();
+    assert_eq!(point.get("x").unwrap().to_int(), 3);
+    assert_eq!(point.get("y").unwrap().to_int(), 4);
+}
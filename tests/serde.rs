@@ -15,6 +15,7 @@ enum ScienceClass {
     Chemistry,
 }
 
+#[allow(dead_code)]
 #[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone)]
 enum Grade {
     A,
@@ -180,3 +181,305 @@ fn serde_shared_keys() {
 
     assert!(fleece::to_bytes(Class::Maths).is_ok());
 }
+
+#[derive(Deserialize, PartialEq, Debug)]
+struct StudentSummary {
+    #[serde(alias = "student_name")]
+    name: String,
+    age: u16,
+    #[serde(default)]
+    nickname: Option<String>,
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_struct_subset() {
+    let student = Student {
+        id: 7,
+        name: "Jens".to_string(),
+        age: 17,
+        favourite_class: None,
+        favourites: vec![Favourite::Movie("Alien".to_string())],
+        lucky_floats: None,
+    };
+    let expected = StudentSummary {
+        name: "Jens".to_string(),
+        age: 17,
+        nickname: None,
+    };
+
+    // Only the fields of `StudentSummary` are looked up, the others are ignored
+    let bytes = fleece::to_bytes(&student).unwrap();
+    let summary: StudentSummary = fleece::from_bytes_lookup_fields(&bytes).unwrap();
+    assert_eq!(summary, expected);
+
+    let scope = fleece::to_bytes_with_shared_keys(&student).unwrap();
+    let summary: StudentSummary = fleece::from_bytes_lookup_fields(&scope.data().unwrap()).unwrap();
+    assert_eq!(summary, expected);
+
+    let bytes = fleece::to_bytes(std::collections::BTreeMap::from([
+        ("student_name", "Bork"),
+        ("nickname", "B"),
+    ]))
+    .unwrap();
+    assert!(fleece::from_bytes_lookup_fields::<StudentSummary>(&bytes)
+        .unwrap_err()
+        .to_string()
+        .contains("missing field `age`"));
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+struct StrictSummary {
+    name: String,
+    age: u16,
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_strict_unknown_fields() {
+    let summary = StrictSummary {
+        name: "Jens".to_string(),
+        age: 17,
+    };
+    let student = Student {
+        id: 7,
+        name: "Jens".to_string(),
+        age: 17,
+        favourite_class: None,
+        favourites: vec![Favourite::Movie("Alien".to_string())],
+        lucky_floats: None,
+    };
+
+    // Unknown keys are rejected, as every key is visited
+    let bytes = fleece::to_bytes(vec![&student]).unwrap();
+    assert!(fleece::from_bytes::<Vec<StrictSummary>>(&bytes)
+        .unwrap_err()
+        .to_string()
+        .contains("unknown field `favourite_class`"));
+
+    let scope = fleece::to_bytes_with_shared_keys(&student).unwrap();
+    let error = fleece::from_bytes::<StrictSummary>(&scope.data().unwrap()).unwrap_err();
+    assert!(error.to_string().contains("unknown field `id`"));
+
+    // Looking up each field never visits the unknown keys
+    assert_eq!(
+        fleece::from_bytes_lookup_fields::<Vec<StrictSummary>>(&bytes).unwrap(),
+        [summary]
+    );
+
+    // Both give the same result for structs which accept every key
+    let bytes = fleece::to_bytes(&student).unwrap();
+    assert_eq!(
+        fleece::from_bytes_lookup_fields::<StudentSummary>(&bytes).unwrap(),
+        fleece::from_bytes::<StudentSummary>(&bytes).unwrap()
+    );
+    assert_eq!(
+        fleece::from_bytes_lookup_fields::<Student>(&bytes).unwrap(),
+        student
+    );
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+struct BorrowedStudent<'a> {
+    name: &'a str,
+    photo: &'a [u8],
+    grades: std::collections::BTreeMap<&'a str, u8>,
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_borrowed() {
+    let mut encoder = fleece::Encoder::new();
+    encoder.begin_dict().unwrap();
+    encoder.write_key("name").unwrap();
+    encoder.write_value("Jens").unwrap();
+    encoder.write_key("photo").unwrap();
+    encoder.write_value(&b"\x89PNG"[..]).unwrap();
+    encoder.write_key("grades").unwrap();
+    let grades = std::collections::BTreeMap::from([("Maths", 1), ("English", 2)]);
+    encoder.write_value(grades).unwrap();
+    encoder.end_dict().unwrap();
+    let bytes = encoder.finish();
+
+    let student: BorrowedStudent = fleece::from_bytes(&bytes).unwrap();
+    assert_eq!(student.name, "Jens");
+    assert_eq!(student.photo, b"\x89PNG");
+    assert_eq!(student.grades["Maths"], 1);
+    // Strings, data and keys point into the encoded bytes, rather than being copied
+    let range = bytes.as_ptr_range();
+    assert!(range.contains(&student.name.as_ptr()));
+    assert!(range.contains(&student.photo.as_ptr()));
+    assert!(student
+        .grades
+        .keys()
+        .all(|key| range.contains(&key.as_ptr())));
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    id: u32,
+    body: &'a fleece::Value,
+}
+
+#[derive(Deserialize)]
+struct OwnedEnvelope {
+    id: u32,
+    body: fleece::alloced::AllocedValue,
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_fleece_values() {
+    let scope = fleece::from_json(
+        r#"{"name": "Jens", "grades": [1, 2.5, null], "photo": {"size": 3}}"#,
+        Some(fleece::SharedKeys::new()),
+    )
+    .unwrap();
+    let body = scope.root().unwrap();
+    let json = fleece::to_json(&body);
+    // Re-encoding without the shared keys changes the order of the keys
+    let parse = |json: &str| serde_json::from_str::<serde_json::Value>(json).unwrap();
+
+    // Embedded Values are copied into the Fleece output
+    let bytes = fleece::to_bytes(Envelope { id: 7, body: &body }).unwrap();
+    let envelope: OwnedEnvelope = fleece::from_bytes(&bytes).unwrap();
+    assert_eq!(envelope.id, 7);
+    assert_eq!(parse(&fleece::to_json(&envelope.body)), parse(&json));
+
+    // They're copied with `write_fleece` with the `std` feature, and visited without it. Either
+    // way, the output is the same as writing them with `write_fleece`
+    let mut encoder = fleece::Encoder::new();
+    encoder.begin_dict().unwrap();
+    encoder.write_key("id").unwrap();
+    encoder.write_value(7).unwrap();
+    encoder.write_key("body").unwrap();
+    encoder.write_fleece(&body).unwrap();
+    encoder.end_dict().unwrap();
+    assert_eq!(bytes, encoder.finish());
+
+    // Values can be serialized with other formats, and collections deserialized from them
+    assert_eq!(serde_json::to_string(&*body).unwrap(), json);
+    let dict = body.as_dict().unwrap();
+    assert_eq!(serde_json::to_string(dict).unwrap(), json);
+
+    let mutable: fleece::MutableDict = serde_json::from_str(&json).unwrap();
+    assert_eq!(mutable.get("name").unwrap().to_str(), "Jens");
+    assert_eq!(mutable.get_array("grades").unwrap().len(), 3);
+    assert_eq!(
+        parse(&serde_json::to_string(&mutable).unwrap()),
+        parse(&json)
+    );
+    let bytes = fleece::to_bytes(&mutable).unwrap();
+    let value = fleece::Value::from_bytes(&bytes).unwrap();
+    assert_eq!(parse(&fleece::to_json(value)), parse(&json));
+
+    let array: fleece::MutableArray = serde_json::from_str("[1, \"two\", [3]]").unwrap();
+    assert_eq!(array.len(), 3);
+    assert_eq!(array.get_array(2).unwrap().get(0).unwrap().to_int(), 3);
+
+    let scalar: fleece::alloced::AllocedValue = serde_json::from_str("\"text\"").unwrap();
+    assert_eq!(scalar.to_str(), "text");
+}
+
+// Uses the name which the Fleece `Serializer` recognises for embedded Values
+struct Forged(&'static [u8]);
+
+impl Serialize for Forged {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Bytes(&'static [u8]);
+        impl Serialize for Bytes {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(self.0)
+            }
+        }
+        serializer.serialize_newtype_struct("$fleece::private::RawValue", &Bytes(self.0))
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_forged_value() {
+    // Bytes which aren't the address of a Value are written as Data, rather than dereferenced
+    let forged = [Forged(&[0xff; 16]), Forged(&[0x70, 0x01, 0xff, 0xff])];
+    let bytes = fleece::to_bytes(&forged).unwrap();
+    let array = fleece::Value::from_bytes(&bytes)
+        .unwrap()
+        .as_array()
+        .unwrap();
+    assert_eq!(array.get(0).unwrap().to_data(), [0xff; 16]);
+    assert_eq!(array.get(1).unwrap().to_data(), [0x70, 0x01, 0xff, 0xff]);
+
+    // Values serialized with other formats aren't left behind for a later forgery
+    let data = bytes.clone();
+    serde_json::to_string(fleece::Value::from_bytes(&data).unwrap()).unwrap();
+    drop(data);
+    let bytes = fleece::to_bytes([Forged(b"data")]).unwrap();
+    let array = fleece::Value::from_bytes(&bytes)
+        .unwrap()
+        .as_array()
+        .unwrap();
+    assert_eq!(array.get(0).unwrap().to_data(), b"data");
+}
+
+#[derive(Serialize)]
+struct Patch {
+    age: u16,
+    name: &'static str,
+}
+
+#[derive(Deserialize)]
+struct Name<'a> {
+    name: &'a str,
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_mutable() {
+    let student = Student {
+        id: 946,
+        name: "Bork".to_string(),
+        age: 16,
+        favourite_class: Some(Class::Science(ScienceClass::Physics)),
+        favourites: vec![
+            Favourite::Movie("Rogue One: A Star Wars Story".to_string()),
+            Favourite::Game {
+                name: "Doom".to_string(),
+                platform: GamePlatform::PC,
+            },
+        ],
+        lucky_floats: Some((7.689, -31.48501, 56_587_462.21)),
+    };
+
+    // The same layout as encoding to Fleece
+    let mut dict = fleece::to_mutable(&student).unwrap();
+    let bytes = fleece::to_bytes(&student).unwrap();
+    assert_eq!(
+        fleece::to_json(fleece::Value::from_bytes(&dict.encode()).unwrap()),
+        fleece::to_json(fleece::Value::from_bytes(&bytes).unwrap())
+    );
+    assert_eq!(fleece::from_mutable::<Student>(&dict).unwrap(), student);
+
+    // Apply a typed patch to the open document
+    let patch = fleece::to_mutable(&Patch {
+        age: 17,
+        name: "Jens",
+    })
+    .unwrap();
+    for (key, item) in &patch {
+        dict.insert(key, item.value().unwrap());
+    }
+    let patched: Name = fleece::from_mutable(&dict).unwrap();
+    assert_eq!(patched.name, "Jens");
+    let student: Student = fleece::from_mutable(&dict).unwrap();
+    assert_eq!((student.age, student.name.as_str()), (17, "Jens"));
+
+    assert!(matches!(
+        fleece::to_mutable(&[1, 2]).unwrap_err(),
+        fleece::Error::Serialize(fleece::error::SerializeError::ValueNotDict)
+    ));
+    assert!(matches!(
+        fleece::to_mutable(&std::collections::BTreeMap::from([(1, 2)])).unwrap_err(),
+        fleece::Error::Serialize(fleece::error::SerializeError::KeyNotString(_))
+    ));
+}